@@ -15,9 +15,17 @@
 //! Bridging round state between rounds.
 
 use crate::round::State as RoundState;
-use futures::task;
-use parking_lot::{RwLock, RwLockReadGuard};
-use std::{sync::Arc, task::Context};
+use futures::{
+	stream::{self, BoxStream, Stream, StreamExt},
+	task,
+};
+use log::warn;
+use parking_lot::RwLock;
+use std::{
+	fmt::Debug,
+	sync::Arc,
+	task::{Context, Poll},
+};
 
 // round state bridged across rounds.
 struct Bridged<H, N> {
@@ -43,13 +51,92 @@ impl<H, N> PriorView<H, N> {
 }
 
 /// A latter view of a round-state.
-pub(crate) struct LatterView<H, N>(Arc<Bridged<H, N>>);
+///
+/// The voter bridges the view in-process to the prior round. It can instead be driven from any
+/// stream of round-state updates, e.g. the receiver of a channel of the host's runtime, see
+/// `from_stream`.
+///
+/// Updates for a different round than the initial round-state's, which must have been
+/// misrouted, and updates which regress the round-state are ignored, see `regresses`.
+pub struct LatterView<H, N> {
+	source: Source<H, N>,
+	latest: RoundState<H, N>,
+}
 
-impl<H, N> LatterView<H, N> {
-	/// Fetch a handle to the last round-state.
-	pub(crate) fn get(&self, cx: &mut Context) -> RwLockReadGuard<RoundState<H, N>> {
-		self.0.waker.register(cx.waker());
-		self.0.inner.read()
+// where a latter view takes its updates from.
+enum Source<H, N> {
+	Bridged(Arc<Bridged<H, N>>),
+	Stream(stream::Fuse<BoxStream<'static, RoundState<H, N>>>),
+}
+
+// whether the update would move the round-state backwards. the estimate can
+// legitimately move backwards as more votes are seen, but the prevote-GHOST
+// and finalized block only ever move forward and a completable round stays
+// completable.
+fn regresses<H, N: Ord>(latest: &RoundState<H, N>, update: &RoundState<H, N>) -> bool {
+	fn number<H, N>(block: &Option<(H, N)>) -> Option<&N> {
+		block.as_ref().map(|(_, n)| n)
+	}
+
+	number(&update.prevote_ghost) < number(&latest.prevote_ghost) ||
+		number(&update.finalized) < number(&latest.finalized) ||
+		(latest.completable && !update.completable)
+}
+
+fn note_update<H: Debug, N: Ord + Debug>(latest: &mut RoundState<H, N>, update: RoundState<H, N>) {
+	if update.round != latest.round {
+		warn!(target: "afg", "Ignoring round-state update {:?} for the wrong round, latest = {:?}",
+			update,
+			latest,
+		);
+	} else if regresses(latest, &update) {
+		warn!(target: "afg", "Ignoring regressing round-state update {:?}, latest = {:?}",
+			update,
+			latest,
+		);
+	} else {
+		*latest = update;
+	}
+}
+
+impl<H: Clone + Debug, N: Clone + Ord + Debug> LatterView<H, N> {
+	/// Create a latter view which starts at `initial` and is updated with every item yielded by
+	/// the given stream.
+	pub fn from_stream<S>(initial: RoundState<H, N>, updates: S) -> Self
+	where
+		S: Stream<Item = RoundState<H, N>> + Send + 'static,
+	{
+		LatterView { source: Source::Stream(updates.boxed().fuse()), latest: initial }
+	}
+
+	/// The round-state as of the last call to `get`.
+	pub fn latest(&self) -> &RoundState<H, N> {
+		&self.latest
+	}
+
+	/// Whether the view is bridged to a prior round, rather than driven from a
+	/// stream.
+	pub(crate) fn is_bridged(&self) -> bool {
+		matches!(self.source, Source::Bridged(_))
+	}
+
+	/// Fetch the last round-state, registering the task to be woken on updates.
+	///
+	/// If several updates are pending only the latest one is returned.
+	pub fn get(&mut self, cx: &mut Context) -> RoundState<H, N> {
+		let LatterView { ref mut source, ref mut latest } = *self;
+		match *source {
+			Source::Bridged(ref bridged) => {
+				bridged.waker.register(cx.waker());
+				note_update(latest, bridged.inner.read().clone());
+			},
+			Source::Stream(ref mut updates) =>
+				while let Poll::Ready(Some(update)) = updates.poll_next_unpin(cx) {
+					note_update(latest, update);
+				},
+		}
+
+		latest.clone()
 	}
 }
 
@@ -60,22 +147,30 @@ impl<H, N> LatterView<H, N> {
 ///
 /// The latter view is held by the subsequent round, which blocks certain activity
 /// while waiting for events on an older round.
-pub(crate) fn bridge_state<H, N>(initial: RoundState<H, N>) -> (PriorView<H, N>, LatterView<H, N>) {
-	let inner = Arc::new(Bridged::new(RwLock::new(initial)));
-	(PriorView(inner.clone()), LatterView(inner))
+pub(crate) fn bridge_state<H: Clone, N: Clone>(
+	initial: RoundState<H, N>,
+) -> (PriorView<H, N>, LatterView<H, N>) {
+	let inner = Arc::new(Bridged::new(RwLock::new(initial.clone())));
+	(PriorView(inner.clone()), LatterView { source: Source::Bridged(inner), latest: initial })
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
-	use std::{sync::Barrier, task::Poll};
+	use futures::channel::mpsc;
+	use std::sync::Barrier;
 
 	#[test]
 	fn bridging_state() {
-		let initial =
-			RoundState { prevote_ghost: None, finalized: None, estimate: None, completable: false };
+		let initial = RoundState {
+			round: 1,
+			prevote_ghost: None,
+			finalized: None,
+			estimate: None,
+			completable: false,
+		};
 
-		let (prior, latter) = bridge_state(initial);
+		let (prior, mut latter) = bridge_state(initial);
 		let waits_for_finality = ::futures::future::poll_fn(move |cx| -> Poll<()> {
 			if latter.get(cx).finalized.is_some() {
 				Poll::Ready(())
@@ -89,6 +184,7 @@ mod tests {
 		::std::thread::spawn(move || {
 			barrier_other.wait();
 			prior.update(RoundState {
+				round: 1,
 				prevote_ghost: Some(("5", 5)),
 				finalized: Some(("1", 1)),
 				estimate: Some(("3", 3)),
@@ -99,4 +195,134 @@ mod tests {
 		barrier.wait();
 		futures::executor::block_on(waits_for_finality);
 	}
+
+	#[test]
+	fn latter_view_from_custom_stream() {
+		let initial = RoundState {
+			round: 1,
+			prevote_ghost: None,
+			finalized: None,
+			estimate: None,
+			completable: false,
+		};
+
+		let (tx, rx) = mpsc::unbounded();
+		let mut latter = LatterView::from_stream(initial.clone(), rx);
+
+		let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+		assert_eq!(latter.get(&mut cx), initial);
+
+		let update = RoundState {
+			round: 1,
+			prevote_ghost: Some(("5", 5)),
+			finalized: Some(("1", 1)),
+			estimate: Some(("3", 3)),
+			completable: true,
+		};
+
+		tx.unbounded_send(update.clone()).unwrap();
+		assert_eq!(latter.get(&mut cx), update);
+
+		// the last known state is kept once the stream terminates.
+		drop(tx);
+		assert_eq!(latter.get(&mut cx), update);
+	}
+
+	#[test]
+	fn regressing_updates_are_ignored() {
+		let initial = RoundState {
+			round: 1,
+			prevote_ghost: Some(("5", 5)),
+			finalized: Some(("2", 2)),
+			estimate: Some(("4", 4)),
+			completable: true,
+		};
+
+		let (prior, mut latter) = bridge_state(initial.clone());
+		let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+
+		// finality can't move backwards.
+		prior.update(RoundState { finalized: Some(("1", 1)), ..initial.clone() });
+		assert_eq!(latter.get(&mut cx), initial);
+
+		// neither can a round become incompletable.
+		prior.update(RoundState { completable: false, ..initial.clone() });
+		assert_eq!(latter.get(&mut cx), initial);
+
+		// but the estimate can move backwards while finality advances.
+		let update = RoundState {
+			round: 1,
+			prevote_ghost: Some(("6", 6)),
+			finalized: Some(("3", 3)),
+			estimate: Some(("3", 3)),
+			completable: true,
+		};
+		prior.update(update.clone());
+		assert_eq!(latter.get(&mut cx), update);
+	}
+
+	#[test]
+	fn updates_for_other_rounds_are_ignored() {
+		let initial = RoundState {
+			round: 1,
+			prevote_ghost: Some(("5", 5)),
+			finalized: Some(("2", 2)),
+			estimate: Some(("4", 4)),
+			completable: true,
+		};
+
+		let (prior, mut latter) = bridge_state(initial.clone());
+		let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+
+		// an otherwise valid update tagged with another round.
+		let update = RoundState { finalized: Some(("3", 3)), ..initial.clone() };
+		prior.update(RoundState { round: 2, ..update.clone() });
+		assert_eq!(latter.get(&mut cx), initial);
+
+		prior.update(update.clone());
+		assert_eq!(latter.get(&mut cx), update);
+	}
+
+	#[test]
+	fn bursts_of_updates_are_coalesced() {
+		use futures::task::{waker, ArcWake};
+		use std::sync::atomic::{AtomicUsize, Ordering};
+
+		struct CountingWaker(AtomicUsize);
+
+		impl ArcWake for CountingWaker {
+			fn wake_by_ref(arc_self: &Arc<Self>) {
+				arc_self.0.fetch_add(1, Ordering::SeqCst);
+			}
+		}
+
+		let initial = RoundState {
+			round: 1,
+			prevote_ghost: None,
+			finalized: None,
+			estimate: None,
+			completable: false,
+		};
+		let updates = (1..4)
+			.map(|i| RoundState { prevote_ghost: Some((i, i)), ..initial.clone() })
+			.collect::<Vec<_>>();
+
+		let wakes = Arc::new(CountingWaker(AtomicUsize::new(0)));
+		let waker = waker(wakes.clone());
+		let mut cx = Context::from_waker(&waker);
+
+		// a burst of bridged updates wakes the latter view once, which then
+		// sees the last one.
+		let (prior, mut latter) = bridge_state(initial.clone());
+		assert_eq!(latter.get(&mut cx), initial);
+		for update in &updates {
+			prior.update(update.clone());
+		}
+		assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+		assert_eq!(latter.get(&mut cx), updates[2]);
+
+		// all queued updates are drained at once.
+		let mut latter = LatterView::from_stream(initial, stream::iter(updates.clone()));
+		assert_eq!(latter.get(&mut cx), updates[2]);
+	}
 }
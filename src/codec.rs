@@ -0,0 +1,158 @@
+// Copyright 2018-2019 Parity Technologies (UK) Ltd
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Selection of the format used to encode messages, commits and other
+//! serializable types.
+//!
+//! SCALE is the canonical format: it is the encoding which is signed and
+//! exchanged between voters, so only it may be used where the bytes matter for
+//! consensus. Other formats, e.g. JSON for RPC and debugging, can be provided by
+//! implementing `Codec` and are never canonical.
+
+use crate::std::vec::Vec;
+#[cfg(feature = "derive-codec")]
+use parity_scale_codec::{Decode, Encode};
+
+/// A format in which values of type `T` can be encoded and decoded.
+pub trait Codec<T> {
+	/// The error returned when decoding fails.
+	type Error;
+
+	/// Whether this is the canonical consensus encoding. Encodings from
+	/// different formats of the same value differ, so only the canonical one may
+	/// be signed or compared with encodings from other voters.
+	const CANONICAL: bool;
+
+	/// Encode the given value.
+	fn encode(value: &T) -> Vec<u8>;
+
+	/// Decode a value from the given bytes.
+	fn decode(bytes: &[u8]) -> Result<T, Self::Error>;
+}
+
+/// The SCALE format, which is canonical.
+#[cfg(feature = "derive-codec")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scale;
+
+#[cfg(feature = "derive-codec")]
+impl<T: Encode + Decode> Codec<T> for Scale {
+	type Error = parity_scale_codec::Error;
+
+	const CANONICAL: bool = true;
+
+	fn encode(value: &T) -> Vec<u8> {
+		value.encode()
+	}
+
+	fn decode(mut bytes: &[u8]) -> Result<T, Self::Error> {
+		T::decode(&mut bytes)
+	}
+}
+
+/// The JSON format, which is not canonical.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Json;
+
+#[cfg(feature = "json")]
+impl<T: serde::Serialize + serde::de::DeserializeOwned> Codec<T> for Json {
+	type Error = serde_json::Error;
+
+	const CANONICAL: bool = false;
+
+	/// # Panics
+	///
+	/// If serializing the value fails, which it doesn't for the types of this
+	/// crate.
+	fn encode(value: &T) -> Vec<u8> {
+		serde_json::to_vec(value).expect("values of this crate serialize to JSON; qed")
+	}
+
+	fn decode(bytes: &[u8]) -> Result<T, Self::Error> {
+		serde_json::from_slice(bytes)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{Commit, Precommit, SignedPrecommit};
+
+	fn commit() -> Commit<u64, u32, u8, u16> {
+		Commit {
+			target_hash: 5,
+			target_number: 2,
+			precommits: vec![SignedPrecommit {
+				precommit: Precommit::new(5, 2),
+				signature: 7,
+				id: 1,
+			}],
+		}
+	}
+
+	#[cfg(feature = "derive-codec")]
+	#[test]
+	fn commit_round_trips_through_scale() {
+		let commit = commit();
+
+		let encoded = <Scale as Codec<Commit<u64, u32, u8, u16>>>::encode(&commit);
+		assert_eq!(encoded, commit.encode());
+		assert_eq!(Scale::decode(&encoded), Ok(commit));
+	}
+
+	#[cfg(feature = "derive-codec")]
+	#[test]
+	fn round_state_encoding_leaves_out_the_round() {
+		let state = crate::round::State {
+			round: 3,
+			prevote_ghost: Some((5u64, 2u32)),
+			finalized: None,
+			estimate: Some((4, 1)),
+			completable: true,
+		};
+
+		let encoded = state.encode();
+		assert_eq!(
+			encoded,
+			(state.prevote_ghost, state.finalized, state.estimate, state.completable).encode(),
+		);
+		assert_eq!(Scale::decode(&encoded), Ok(crate::round::State { round: 0, ..state }));
+	}
+
+	#[cfg(feature = "json")]
+	#[test]
+	fn commit_round_trips_through_json() {
+		let commit = commit();
+
+		let encoded = <Json as Codec<Commit<u64, u32, u8, u16>>>::encode(&commit);
+		assert_eq!(encoded, serde_json::to_vec(&commit).unwrap());
+		assert_eq!(Json::decode(&encoded).ok(), Some(commit));
+	}
+
+	#[cfg(all(feature = "derive-codec", feature = "json"))]
+	#[test]
+	fn only_scale_is_canonical() {
+		let commit = commit();
+
+		// the formats encode the same commit to different bytes, so only the
+		// canonical one may be signed.
+		const { assert!(<Scale as Codec<Commit<u64, u32, u8, u16>>>::CANONICAL) };
+		const { assert!(!<Json as Codec<Commit<u64, u32, u8, u16>>>::CANONICAL) };
+		assert_ne!(
+			<Scale as Codec<Commit<u64, u32, u8, u16>>>::encode(&commit),
+			<Json as Codec<Commit<u64, u32, u8, u16>>>::encode(&commit),
+		);
+	}
+}
@@ -0,0 +1,116 @@
+// Copyright 2018-2019 Parity Technologies (UK) Ltd
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! GRANDPA fork choice without running a voter.
+//!
+//! Block authors can build on the prevote-GHOST of a set of prevotes, the
+//! highest block which has supermajority prevote weight, computed here in the
+//! same way as by the rounds of the voter.
+
+use crate::{
+	round::{Round, RoundParams},
+	std::fmt::Debug,
+	voter_set::VoterSet,
+	BlockNumberOps, Chain, SignedPrevote,
+};
+
+/// Compute the prevote-GHOST of the given prevotes, cast by the given voters on
+/// top of the given base block.
+///
+/// Prevotes by voters outside the set and prevotes for blocks which aren't
+/// descendents of the base are ignored, and equivocating voters are accounted
+/// for as they are in a round. Returns `None` if no block has supermajority
+/// prevote weight.
+pub fn best_block<H, N, S, Id, C, I>(
+	prevotes: I,
+	voters: &VoterSet<Id>,
+	chain: &C,
+	base: (H, N),
+) -> Option<(H, N)>
+where
+	H: Clone + Eq + Ord + Debug,
+	N: Copy + BlockNumberOps + Debug,
+	S: Clone + Eq,
+	Id: Clone + Ord + Eq + Debug,
+	C: Chain<H, N>,
+	I: IntoIterator<Item = SignedPrevote<H, N, S, Id>>,
+{
+	let mut round = Round::new(RoundParams { round_number: 0, voters: voters.clone(), base });
+
+	for SignedPrevote { prevote, signature, id } in prevotes {
+		// a prevote which can't be imported can't contribute to the GHOST.
+		let _ = round.import_prevote(chain, prevote, id, signature);
+	}
+
+	round.state().prevote_ghost
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		testing::chain::{DummyChain, GENESIS_HASH},
+		Prevote,
+	};
+
+	fn prevote(
+		hash: &'static str,
+		number: u32,
+		id: &'static str,
+	) -> SignedPrevote<&'static str, u32, &'static str, &'static str> {
+		SignedPrevote { prevote: Prevote::new(hash, number), signature: id, id }
+	}
+
+	#[test]
+	fn best_block_is_prevote_ghost() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E", "F"]);
+		chain.push_blocks("E", &["EA", "EB", "EC", "ED"]);
+		chain.push_blocks("F", &["FA", "FB", "FC"]);
+
+		let voters = VoterSet::new([("Alice", 4), ("Bob", 7), ("Eve", 3)].iter().cloned()).unwrap();
+
+		let prevotes =
+			vec![prevote("FC", 10, "Alice"), prevote("ED", 10, "Bob"), prevote("F", 7, "Eve")];
+
+		// the same prevotes imported into a round.
+		let mut round =
+			Round::new(RoundParams { round_number: 1, voters: voters.clone(), base: ("C", 4) });
+		for SignedPrevote { prevote, signature, id } in prevotes.clone() {
+			round.import_prevote(&chain, prevote, id, signature).unwrap();
+		}
+
+		let best = best_block(prevotes.clone(), &voters, &chain, ("C", 4));
+		assert_eq!(best, Some(("E", 6)));
+		assert_eq!(best, round.state().prevote_ghost);
+
+		// without Bob there is no supermajority.
+		let without_bob = prevotes.iter().filter(|p| p.id != "Bob").cloned();
+		assert_eq!(best_block(without_bob, &voters, &chain, ("C", 4)), None);
+
+		// votes by unknown voters and for blocks below the base don't count.
+		let invalid = vec![
+			prevote("FC", 10, "Alice"),
+			prevote("B", 3, "Bob"),
+			prevote("FC", 10, "Mallory"),
+			prevote("FC", 10, "Eve"),
+		];
+		assert_eq!(best_block(invalid, &voters, &chain, ("C", 4)), None);
+
+		// the weight of an equivocating voter counts towards every block.
+		let equivocation =
+			vec![prevote("FC", 10, "Bob"), prevote("FB", 9, "Eve"), prevote("ED", 10, "Eve")];
+		assert_eq!(best_block(equivocation, &voters, &chain, ("C", 4)), Some(("FC", 10)));
+	}
+}
@@ -13,6 +13,12 @@
 // limitations under the License.
 
 //! Fuzzing utilities for the vote graph.
+//!
+//! With the `std` feature a voter can also be driven through a [`Schedule`]
+//! of incoming votes and ticks of a logical clock, see
+//! [`execute_fuzzed_voter`]. Timers only fire on a tick and the voter is
+//! polled to completion after every step, so that the same schedule always
+//! leads to the same execution and any crash is reproducible.
 
 use crate::{
 	round::{Round, RoundParams},
@@ -48,13 +54,13 @@ impl FuzzChain {
 		match hash {
 			0 => 0,
 
-			1 | 2 | 3 => 1,
+			1..=3 => 1,
 
-			4 | 5 | 6 => 2,
-			7 | 8 | 9 => 2,
+			4..=6 => 2,
+			7..=9 => 2,
 
-			10 | 11 | 12 => 3,
-			13 | 14 | 15 => 3,
+			10..=12 => 3,
+			13..=15 => 3,
 
 			_ => panic!("invalid block hash"),
 		}
@@ -122,11 +128,11 @@ impl Chain<Hash, BlockNumber> for FuzzChain {
 
 		let full_ancestry: &[Hash] = match block {
 			0 => &[],
-			1 | 2 | 3 => &[0],
-			4 | 5 | 6 => &[0, 1],
-			7 | 8 | 9 => &[0, 2],
-			10 | 11 | 12 => &[0, 1, 4],
-			13 | 14 | 15 => &[0, 2, 7],
+			1..=3 => &[0],
+			4..=6 => &[0, 1],
+			7..=9 => &[0, 2],
+			10..=12 => &[0, 1, 4],
+			13..=15 => &[0, 2, 7],
 			_ => panic!("invalid block hash"),
 		};
 
@@ -170,7 +176,7 @@ impl<'a> RandomnessStream<'a> {
 			self.half_nibble = false;
 		}
 		self.pos += 1;
-		self.inner.get(self.pos).map(|&b| b)
+		self.inner.get(self.pos).copied()
 	}
 }
 
@@ -271,7 +277,7 @@ fn rand_block(s: &mut RandomnessStream) -> Option<Block> {
 
 /// Execute a fuzzed voting process on a `Round`.
 pub fn execute_fuzzed_vote(data: &[u8]) {
-	assert!(voters().len() <= core::u8::MAX as usize);
+	assert!(voters().len() <= u8::MAX as usize);
 
 	let n = voters().len() as u8;
 	let f = (n - 1) / 3;
@@ -334,7 +340,7 @@ pub fn execute_fuzzed_vote(data: &[u8]) {
 
 	// Import precommits.
 	for (i, &voter) in voters().iter().enumerate() {
-		let round = &mut rounds[i as usize];
+		let round = &mut rounds[i];
 
 		// Import enough precommits (including our own) to reach supermajority.
 		let k = match stream.read_byte() {
@@ -349,7 +355,7 @@ pub fn execute_fuzzed_vote(data: &[u8]) {
 
 		// Start tracking completability and estimate.
 		let mut completable = round.state().completable;
-		let mut last_estimate = round.state().estimate.clone();
+		let mut last_estimate = round.state().estimate;
 
 		// Import the remaining precommits.
 		for j in omit {
@@ -384,7 +390,7 @@ pub fn execute_fuzzed_vote(data: &[u8]) {
 
 		// Now (re-)import _all_ prevotes, checking the prevote-ghost along the way.
 		for &v in voters().iter() {
-			let old_ghost = round.state().prevote_ghost.clone().expect("supermajority seen");
+			let old_ghost = round.state().prevote_ghost.expect("supermajority seen");
 
 			let vote = prevotes[v as usize].clone();
 			let result = round.import_prevote(&FuzzChain, vote, v, v).unwrap();
@@ -443,7 +449,7 @@ pub fn execute_fuzzed_graph(data: &[u8]) {
 
 		graph.insert(target_hash, target_number, new_prevote(), &FuzzChain).unwrap();
 
-		let new_prevote_ghost = graph.find_ghost(prevote_ghost.clone(), |v| v.prevote >= T);
+		let new_prevote_ghost = graph.find_ghost(prevote_ghost, |v| v.prevote >= T);
 		if let Some(old_ghost) = prevote_ghost {
 			let new_ghost = new_prevote_ghost.expect("ghost does not disappear with more votes.");
 			check_prevote_ghost(old_ghost, new_ghost);
@@ -453,7 +459,7 @@ pub fn execute_fuzzed_graph(data: &[u8]) {
 			// Check maximality of the prevote-ghost's block number w.r.t threshold weight.
 			for descendent in FuzzChain::all_descendents(hash) {
 				let desc_nr = FuzzChain::number(descendent);
-				assert!(graph.cumulative_vote(descendent, desc_nr).prevote < T);
+				assert!(graph.cumulative_vote(&descendent, desc_nr).is_none_or(|v| v.prevote < T));
 			}
 		}
 
@@ -482,8 +488,7 @@ pub fn execute_fuzzed_graph(data: &[u8]) {
 
 		// The already calculated prevote ghost should not change as a result of
 		// adding precommit weights.
-		let new_prevote_ghost =
-			graph.find_ghost(Some(prevote_ghost.clone()), |v| v.prevote >= T).unwrap();
+		let new_prevote_ghost = graph.find_ghost(Some(prevote_ghost), |v| v.prevote >= T).unwrap();
 		assert_eq!(new_prevote_ghost, prevote_ghost, "prevote ghost changed");
 
 		// The number of voters who did not yet cast a vote.
@@ -499,7 +504,7 @@ pub fn execute_fuzzed_graph(data: &[u8]) {
 		let new_estimate =
 			graph.find_ancestor(prevote_ghost.0, prevote_ghost.1, possible_to_precommit);
 
-		let newly_completable = new_estimate.map_or(false, |(hash, nr)| {
+		let newly_completable = new_estimate.is_some_and(|(hash, nr)| {
 			// Every estimate must be on the chain with head prevote ghost.
 			if hash != prevote_ghost.0 {
 				assert!(FuzzChain.ancestry(hash, prevote_ghost.0).is_ok());
@@ -523,7 +528,7 @@ pub fn execute_fuzzed_graph(data: &[u8]) {
 			check_estimate(old_estimate, new_estimate);
 		}
 
-		estimate = new_estimate.clone();
+		estimate = new_estimate;
 		completable = newly_completable;
 	}
 
@@ -533,6 +538,397 @@ pub fn execute_fuzzed_graph(data: &[u8]) {
 	assert!(estimate.is_some());
 }
 
+#[cfg(feature = "std")]
+pub use self::scheduled::{execute_fuzzed_voter, execute_schedule, Schedule, Step};
+
+#[cfg(feature = "std")]
+mod scheduled {
+	use super::{Block, BlockNumber, FuzzChain, Hash, RandomnessStream, Signature, Voter};
+	use crate::{
+		round::State as RoundState,
+		voter::{self, CommunicationOut, Environment, RoundData},
+		voter_set::VoterSet,
+		Chain, Commit, Error, HistoricalVotes, Message, Precommit, Prevote, PrimaryPropose,
+		SignedMessage,
+	};
+	use futures::{future, prelude::*, stream, task::ArcWake};
+	use parking_lot::Mutex;
+	use std::{
+		collections::{BTreeMap, VecDeque},
+		pin::Pin,
+		sync::{
+			atomic::{AtomicBool, AtomicU64, Ordering},
+			Arc,
+		},
+		task::{Context, Poll, Waker},
+	};
+
+	/// The voters of a scheduled execution, the local voter is 0.
+	const VOTERS: Voter = 4;
+
+	/// The highest round a scheduled vote can be cast in.
+	const MAX_ROUND: u64 = 4;
+
+	/// The maximum number of times the voter is polled after a step, in case it
+	/// keeps waking itself up.
+	const MAX_POLLS: usize = 1000;
+
+	/// A step of a [`Schedule`].
+	#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+	pub enum Step {
+		/// A remote voter prevotes for a block.
+		Prevote {
+			/// The round of the vote.
+			round: u64,
+			/// The remote voter, between 1 and 3.
+			voter: Voter,
+			/// The hash of the block voted for.
+			target: Hash,
+		},
+		/// A remote voter precommits for a block.
+		Precommit {
+			/// The round of the vote.
+			round: u64,
+			/// The remote voter, between 1 and 3.
+			voter: Voter,
+			/// The hash of the block voted for.
+			target: Hash,
+		},
+		/// The logical clock advances by one tick, firing the timers due by then.
+		Tick,
+	}
+
+	/// A sequence of steps driving a voter, see [`execute_schedule`].
+	///
+	/// The local voter is 0 out of the voters 0 to 3, all with the same weight,
+	/// voting on the blocks of [`FuzzChain`]. Its prevote timer fires two ticks
+	/// after a round starts and its precommit timer after four.
+	#[derive(Debug, Clone, Default, PartialEq, Eq)]
+	pub struct Schedule(pub Vec<Step>);
+
+	impl Schedule {
+		/// Decode a schedule from fuzzer input. Each step starts with a nibble
+		/// selecting its kind and round, which votes follow by a nibble for the
+		/// voter and one for the target.
+		pub fn decode(data: &[u8]) -> Self {
+			let mut stream = RandomnessStream::new(data);
+			let mut steps = Vec::new();
+			while let Some(kind) = stream.read_nibble() {
+				let round = u64::from(kind >> 2) % MAX_ROUND + 1;
+				let step = match kind & 0b11 {
+					0 | 3 => Step::Tick,
+					kind => {
+						let (voter, target) = match (stream.read_nibble(), stream.read_nibble()) {
+							(Some(voter), Some(target)) => (1 + voter % (VOTERS - 1), target),
+							_ => break,
+						};
+
+						if kind == 1 {
+							Step::Prevote { round, voter, target }
+						} else {
+							Step::Precommit { round, voter, target }
+						}
+					},
+				};
+				steps.push(step);
+			}
+
+			Schedule(steps)
+		}
+	}
+
+	/// Execute a fuzzed schedule on a voter, see [`Schedule::decode`].
+	pub fn execute_fuzzed_voter(data: &[u8]) {
+		execute_schedule(&Schedule::decode(data));
+	}
+
+	/// Drive a voter through the given schedule, returning the blocks it
+	/// finalized in order.
+	///
+	/// After each step the voter is polled until it stops waking itself up,
+	/// with votes processed before the timers of the logical clock fire. The
+	/// voter must never fail nor finalize a block below the last finalized
+	/// one.
+	pub fn execute_schedule(schedule: &Schedule) -> Vec<Block> {
+		let env = Arc::new(ScheduledEnvironment::default());
+		let voters = VoterSet::new((0..VOTERS).map(|v| (v, 1))).expect("nonempty");
+		let global_comms = (stream::pending(), Drain);
+		let mut voter =
+			voter::Voter::new(env.clone(), voters, global_comms, 0, Vec::new(), (0, 0), (0, 0));
+
+		let woken = Arc::new(Woken(AtomicBool::new(false)));
+		let waker = futures::task::waker(woken.clone());
+		let mut cx = Context::from_waker(&waker);
+
+		let mut steps = schedule.0.iter();
+		loop {
+			for _ in 0..MAX_POLLS {
+				woken.0.store(false, Ordering::SeqCst);
+				if let Poll::Ready(result) = voter.poll_unpin(&mut cx) {
+					panic!("voter exited: {:?}", result);
+				}
+
+				if !woken.0.load(Ordering::SeqCst) {
+					break
+				}
+			}
+
+			match steps.next() {
+				Some(Step::Prevote { round, voter, target }) => env.network.deliver(
+					*round,
+					Message::Prevote(Prevote::new(*target, FuzzChain::number(*target))),
+					*voter,
+				),
+				Some(Step::Precommit { round, voter, target }) => env.network.deliver(
+					*round,
+					Message::Precommit(Precommit::new(*target, FuzzChain::number(*target))),
+					*voter,
+				),
+				Some(Step::Tick) => {
+					env.now.fetch_add(1, Ordering::SeqCst);
+				},
+				None => break,
+			}
+		}
+
+		let finalized = env.finalized.lock().clone();
+		finalized
+	}
+
+	/// Notes whether the voter woke itself up while being polled.
+	struct Woken(AtomicBool);
+
+	impl ArcWake for Woken {
+		fn wake_by_ref(arc_self: &Arc<Self>) {
+			arc_self.0.store(true, Ordering::SeqCst);
+		}
+	}
+
+	/// A timer firing once the logical clock reaches its deadline.
+	struct Timer {
+		now: Arc<AtomicU64>,
+		deadline: u64,
+	}
+
+	impl Future for Timer {
+		type Output = Result<(), Error>;
+
+		fn poll(self: Pin<&mut Self>, _: &mut Context) -> Poll<Self::Output> {
+			if self.now.load(Ordering::SeqCst) >= self.deadline {
+				Poll::Ready(Ok(()))
+			} else {
+				Poll::Pending
+			}
+		}
+	}
+
+	type Queue = VecDeque<SignedMessage<Hash, BlockNumber, Signature, Voter>>;
+
+	/// The votes of each round which weren't received yet, including the
+	/// votes of the local voter.
+	#[derive(Default)]
+	struct Network {
+		rounds: Mutex<BTreeMap<u64, Queue>>,
+		wakers: Mutex<Vec<Waker>>,
+	}
+
+	impl Network {
+		fn deliver(&self, round: u64, message: Message<Hash, BlockNumber>, id: Voter) {
+			let signed = SignedMessage { message, signature: id, id };
+			self.rounds.lock().entry(round).or_default().push_back(signed);
+			for waker in self.wakers.lock().drain(..) {
+				waker.wake();
+			}
+		}
+	}
+
+	struct Incoming {
+		network: Arc<Network>,
+		round: u64,
+	}
+
+	impl Stream for Incoming {
+		type Item = Result<SignedMessage<Hash, BlockNumber, Signature, Voter>, Error>;
+
+		fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+			match self.network.rounds.lock().entry(self.round).or_default().pop_front() {
+				Some(signed) => Poll::Ready(Some(Ok(signed))),
+				None => {
+					self.network.wakers.lock().push(cx.waker().clone());
+					Poll::Pending
+				},
+			}
+		}
+	}
+
+	/// Sends the votes of the local voter back to it.
+	struct Outgoing {
+		network: Arc<Network>,
+		round: u64,
+	}
+
+	impl Sink<Message<Hash, BlockNumber>> for Outgoing {
+		type Error = Error;
+
+		fn poll_ready(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Error>> {
+			Poll::Ready(Ok(()))
+		}
+
+		fn start_send(
+			self: Pin<&mut Self>,
+			message: Message<Hash, BlockNumber>,
+		) -> Result<(), Error> {
+			self.network.deliver(self.round, message, 0);
+			Ok(())
+		}
+
+		fn poll_flush(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Error>> {
+			Poll::Ready(Ok(()))
+		}
+
+		fn poll_close(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Error>> {
+			Poll::Ready(Ok(()))
+		}
+	}
+
+	/// Drops the commits of the local voter.
+	struct Drain;
+
+	impl Sink<CommunicationOut<Hash, BlockNumber, Signature, Voter>> for Drain {
+		type Error = Error;
+
+		fn poll_ready(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Error>> {
+			Poll::Ready(Ok(()))
+		}
+
+		fn start_send(
+			self: Pin<&mut Self>,
+			_: CommunicationOut<Hash, BlockNumber, Signature, Voter>,
+		) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn poll_flush(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Error>> {
+			Poll::Ready(Ok(()))
+		}
+
+		fn poll_close(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Error>> {
+			Poll::Ready(Ok(()))
+		}
+	}
+
+	#[derive(Default)]
+	struct ScheduledEnvironment {
+		now: Arc<AtomicU64>,
+		network: Arc<Network>,
+		finalized: Mutex<Vec<Block>>,
+	}
+
+	impl ScheduledEnvironment {
+		fn timer(&self, ticks: u64) -> Timer {
+			Timer { now: self.now.clone(), deadline: self.now.load(Ordering::SeqCst) + ticks }
+		}
+	}
+
+	impl Chain<Hash, BlockNumber> for ScheduledEnvironment {
+		fn ancestry(&self, base: Hash, block: Hash) -> Result<Vec<Hash>, Error> {
+			FuzzChain.ancestry(base, block)
+		}
+	}
+
+	impl Environment<Hash, BlockNumber> for ScheduledEnvironment {
+		type Timer = Timer;
+		type BestChain = future::Ready<Result<Option<Block>, Error>>;
+		type Id = Voter;
+		type Signature = Signature;
+		type In = Incoming;
+		type Out = Outgoing;
+		type Error = Error;
+
+		fn best_chain_containing(&self, base: Hash) -> Self::BestChain {
+			// the highest descendent, ties broken by the lowest hash.
+			let best = FuzzChain::all_descendents(base)
+				.chain(Some(base))
+				.map(|hash| (hash, FuzzChain::number(hash)))
+				.min_by_key(|&(hash, number)| (core::cmp::Reverse(number), hash));
+
+			future::ready(Ok(best))
+		}
+
+		fn round_data(&self, round: u64) -> RoundData<Voter, Timer, Incoming, Outgoing> {
+			RoundData {
+				voter_id: Some(0),
+				prevote_timer: self.timer(2),
+				precommit_timer: self.timer(4),
+				incoming: Incoming { network: self.network.clone(), round },
+				outgoing: Outgoing { network: self.network.clone(), round },
+			}
+		}
+
+		fn round_commit_timer(&self) -> Timer {
+			self.timer(1)
+		}
+
+		fn proposed(
+			&self,
+			_round: u64,
+			_propose: PrimaryPropose<Hash, BlockNumber>,
+		) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn prevoted(&self, _round: u64, _prevote: Prevote<Hash, BlockNumber>) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn precommitted(
+			&self,
+			_round: u64,
+			_precommit: Precommit<Hash, BlockNumber>,
+		) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn completed(
+			&self,
+			_round: u64,
+			_state: RoundState<Hash, BlockNumber>,
+			_base: (Hash, BlockNumber),
+			_votes: &HistoricalVotes<Hash, BlockNumber, Signature, Voter>,
+		) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn concluded(
+			&self,
+			_round: u64,
+			_state: RoundState<Hash, BlockNumber>,
+			_base: (Hash, BlockNumber),
+			_votes: &HistoricalVotes<Hash, BlockNumber, Signature, Voter>,
+		) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn finalize_block(
+			&self,
+			hash: Hash,
+			number: BlockNumber,
+			_round: u64,
+			commit: Commit<Hash, BlockNumber, Signature, Voter>,
+		) -> Result<(), Error> {
+			assert_eq!((commit.target_hash, commit.target_number), (hash, number));
+
+			let mut finalized = self.finalized.lock();
+			if let Some(&(_, last_number)) = finalized.last() {
+				assert!(number > last_number, "finalized block below the last finalized one");
+			}
+			finalized.push((hash, number));
+
+			Ok(())
+		}
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	#[test]
@@ -552,4 +948,27 @@ mod tests {
 		let data = include_bytes!("../fuzz_corpus/crash-499bf756959c90958d05c669b77b4a6f85e4fbf5");
 		super::execute_fuzzed_graph(&data[..]);
 	}
+
+	#[test]
+	fn scheduled_voter_finalizes_supermajority_votes() {
+		use super::{execute_schedule, Schedule, Step};
+
+		let mut steps = vec![Step::Tick, Step::Tick];
+		steps.extend((1..=3).map(|voter| Step::Prevote { round: 1, voter, target: 10 }));
+		steps.extend((1..=3).map(|voter| Step::Precommit { round: 1, voter, target: 10 }));
+		steps.extend(vec![Step::Tick; 4]);
+
+		assert_eq!(execute_schedule(&Schedule(steps)), vec![(10, 3)]);
+	}
+
+	#[test]
+	fn scheduled_voter_is_deterministic() {
+		use super::{execute_schedule, Schedule};
+
+		let data = include_bytes!("../fuzz_corpus/be9e58ec5a0d4dce97bd1f07a3d1ffddd7d4b48b");
+		let schedule = Schedule::decode(&data[..]);
+		assert!(!schedule.0.is_empty());
+
+		assert_eq!(execute_schedule(&schedule), execute_schedule(&schedule));
+	}
 }
@@ -34,6 +34,11 @@ extern crate alloc;
 #[cfg(feature = "std")]
 extern crate std;
 
+#[cfg(feature = "std")]
+pub mod bridge_state;
+#[cfg(any(feature = "derive-codec", feature = "json"))]
+pub mod codec;
+pub mod fork_choice;
 pub mod round;
 pub mod vote_graph;
 #[cfg(feature = "std")]
@@ -41,11 +46,9 @@ pub mod voter;
 pub mod voter_set;
 
 mod bitfield;
-#[cfg(feature = "std")]
-mod bridge_state;
 #[cfg(any(test, feature = "fuzz-helpers"))]
 pub mod fuzz_helpers;
-#[cfg(any(test))]
+#[cfg(test)]
 mod testing;
 mod weights;
 #[cfg(not(feature = "std"))]
@@ -56,6 +59,14 @@ mod std {
 		pub use alloc::vec::Vec;
 	}
 
+	pub mod boxed {
+		pub use alloc::boxed::Box;
+	}
+
+	pub mod sync {
+		pub use alloc::sync::Arc;
+	}
+
 	pub mod collections {
 		pub use alloc::collections::{
 			btree_map::{self, BTreeMap},
@@ -64,7 +75,7 @@ mod std {
 	}
 
 	pub mod fmt {
-		pub use core::fmt::{Display, Formatter, Result};
+		pub use core::fmt::{Display, Formatter, Result, Write};
 
 		pub trait Debug {}
 		impl<T> Debug for T {}
@@ -81,6 +92,7 @@ use scale_info::TypeInfo;
 /// A prevote for a block and its ancestors.
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "derive-codec", derive(Encode, Decode, TypeInfo))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Prevote<H, N> {
 	/// The target block's hash.
 	pub target_hash: H,
@@ -98,6 +110,7 @@ impl<H, N> Prevote<H, N> {
 /// A precommit for a block and its ancestors.
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "derive-codec", derive(Encode, Decode, TypeInfo))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Precommit<H, N> {
 	/// The target block's hash.
 	pub target_hash: H,
@@ -116,6 +129,7 @@ impl<H, N> Precommit<H, N> {
 #[derive(Clone, PartialEq, Eq)]
 #[cfg_attr(any(feature = "std", test), derive(Debug))]
 #[cfg_attr(feature = "derive-codec", derive(Encode, Decode, TypeInfo))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PrimaryPropose<H, N> {
 	/// The target block's hash.
 	pub target_hash: H,
@@ -136,6 +150,34 @@ impl<H, N> PrimaryPropose<H, N> {
 pub enum Error {
 	/// The block is not a descendent of the given base block.
 	NotDescendent,
+	/// The base of a round is not equal to or a descendent of the base of the round it follows.
+	BaseRegression,
+	/// The prevote-GHOST of the previous round is not a descendent of its estimate,
+	/// which is only possible in case of massive equivocation.
+	MassiveEquivocation,
+	/// The persisted votes of the local voter conflict with each other.
+	InconsistentPersistence,
+	/// A persisted vote has an invalid signature.
+	CorruptPersistence,
+	/// The signer of a message is not part of the voter set.
+	UnknownVoter,
+	/// The signature of a message is invalid.
+	InvalidSignature,
+	/// The ancestry returned by the chain doesn't end at the base it was
+	/// requested for.
+	InvalidAncestry,
+	/// The parts a vote-graph is reconstructed from are inconsistent.
+	InvalidVoteGraph,
+	/// A voter was given more than once for a voter set.
+	DuplicateVoter,
+	/// The weights given for a voter set are invalid, i.e. a voter has no
+	/// weight, the total weight is zero or it exceeds `u64::MAX`.
+	InvalidVoterWeight,
+	/// The local voter failed to sign a vote, e.g. since its key is unavailable.
+	SigningFailed,
+	/// A block was finalized which is not on the same chain as the last
+	/// finalized block, which is only possible if safety was violated.
+	ConflictingFinality,
 }
 
 #[cfg(feature = "std")]
@@ -143,6 +185,20 @@ impl std::fmt::Display for Error {
 	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
 		match *self {
 			Error::NotDescendent => write!(f, "Block not descendent of base"),
+			Error::BaseRegression => write!(f, "Round base regressed from previous round"),
+			Error::MassiveEquivocation =>
+				write!(f, "Prevote-GHOST not descendent of estimate in previous round"),
+			Error::InconsistentPersistence => write!(f, "Persisted votes are conflicting"),
+			Error::CorruptPersistence => write!(f, "Persisted vote has an invalid signature"),
+			Error::UnknownVoter => write!(f, "Message signer not in voter set"),
+			Error::InvalidSignature => write!(f, "Message signature is invalid"),
+			Error::InvalidAncestry => write!(f, "Block ancestry doesn't reach base"),
+			Error::InvalidVoteGraph => write!(f, "Vote-graph parts are inconsistent"),
+			Error::DuplicateVoter => write!(f, "Voter given more than once"),
+			Error::InvalidVoterWeight => write!(f, "Voter weights are invalid"),
+			Error::SigningFailed => write!(f, "Failed to sign vote"),
+			Error::ConflictingFinality =>
+				write!(f, "Finalized block conflicts with last finalized block"),
 		}
 	}
 }
@@ -152,11 +208,62 @@ impl std::error::Error for Error {
 	fn description(&self) -> &str {
 		match *self {
 			Error::NotDescendent => "Block not descendent of base",
+			Error::BaseRegression => "Round base regressed from previous round",
+			Error::MassiveEquivocation =>
+				"Prevote-GHOST not descendent of estimate in previous round",
+			Error::InconsistentPersistence => "Persisted votes are conflicting",
+			Error::CorruptPersistence => "Persisted vote has an invalid signature",
+			Error::UnknownVoter => "Message signer not in voter set",
+			Error::InvalidSignature => "Message signature is invalid",
+			Error::InvalidAncestry => "Block ancestry doesn't reach base",
+			Error::InvalidVoteGraph => "Vote-graph parts are inconsistent",
+			Error::DuplicateVoter => "Voter given more than once",
+			Error::InvalidVoterWeight => "Voter weights are invalid",
+			Error::SigningFailed => "Failed to sign vote",
+			Error::ConflictingFinality => "Finalized block conflicts with last finalized block",
+		}
+	}
+}
+
+/// When the voter checks consensus invariants which are costly to verify, e.g.
+/// that the base of each round doesn't regress from the previous round.
+///
+/// A violated invariant is returned as an error by the voter whenever it is
+/// checked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InvariantLevel {
+	/// Never check the invariants.
+	Off,
+	/// Only check the invariants in builds with debug assertions enabled.
+	#[default]
+	Debug,
+	/// Always check the invariants.
+	Always,
+}
+
+impl InvariantLevel {
+	/// Whether invariants should be checked in this build.
+	pub fn is_enabled(&self) -> bool {
+		match *self {
+			InvariantLevel::Off => false,
+			InvariantLevel::Debug => cfg!(debug_assertions),
+			InvariantLevel::Always => true,
 		}
 	}
 }
 
 /// Arithmetic necessary for a block number.
+///
+/// This is implemented for all types providing the arithmetic, e.g. all
+/// primitive unsigned integers including `u128`. A newtype block number only
+/// needs to implement the supertraits to get it.
+///
+/// Block numbers are converted to `usize` to index ancestries by the distance
+/// between two blocks. A distance taken from a vote is converted with the
+/// checked `TryInto`, since the `as` cast of `AsPrimitive` would truncate one
+/// above `usize::MAX` to the index of a wrong block, e.g. for `u128` numbers on
+/// 64-bit targets. `AsPrimitive` is only used for distances within an ancestry
+/// which is already held in memory.
 pub trait BlockNumberOps:
 	std::fmt::Debug
 	+ std::cmp::Ord
@@ -165,6 +272,7 @@ pub trait BlockNumberOps:
 	+ num::One
 	+ num::Zero
 	+ num::AsPrimitive<usize>
+	+ core::convert::TryInto<usize>
 {
 }
 
@@ -177,6 +285,7 @@ where
 	T: num::One,
 	T: num::Zero,
 	T: num::AsPrimitive<usize>,
+	T: core::convert::TryInto<usize>,
 {
 }
 
@@ -188,25 +297,38 @@ pub trait Chain<H: Eq, N: Copy + BlockNumberOps> {
 	/// If the block is not a descendent of `base`, returns an error.
 	fn ancestry(&self, base: H, block: H) -> Result<Vec<H>, Error>;
 
+	/// Get at most `max` entries of the ancestry of a block, starting from its
+	/// parent. This is the same as `ancestry` truncated to `max` entries, i.e.
+	/// if the block is not a descendent of `base`, returns an error.
+	///
+	/// The default implementation fetches the whole ancestry and truncates it,
+	/// on every call. A vote-graph calls this once for each number of a
+	/// vote-node passed on the way from an inserted block to its nearest
+	/// vote-node ancestor, so chains that can fetch a span of the ancestry
+	/// without materializing all of it should override this.
+	fn ancestry_bounded(&self, base: H, block: H, max: usize) -> Result<Vec<H>, Error> {
+		let mut ancestry = self.ancestry(base, block)?;
+		ancestry.truncate(max);
+		Ok(ancestry)
+	}
+
 	/// Returns true if `block` is a descendent of or equal to the given `base`.
 	fn is_equal_or_descendent_of(&self, base: H, block: H) -> bool {
 		if base == block {
 			return true
 		}
 
-		// TODO: currently this function always succeeds since the only error
-		// variant is `Error::NotDescendent`, this may change in the future as
-		// other errors (e.g. IO) are not being exposed.
-		match self.ancestry(base, block) {
-			Ok(_) => true,
-			Err(Error::NotDescendent) => false,
-		}
+		// TODO: any error fetching the ancestry is treated as the block not
+		// being a descendent, this may change in the future as other errors
+		// (e.g. IO) are not being exposed.
+		self.ancestry(base, block).is_ok()
 	}
 }
 
 /// An equivocation (double-vote) in a given round.
 #[derive(Clone, Debug, PartialEq)]
 #[cfg_attr(feature = "derive-codec", derive(Encode, Decode, TypeInfo))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Equivocation<Id, V, S> {
 	/// The round number equivocated in.
 	pub round_number: u64,
@@ -222,6 +344,7 @@ pub struct Equivocation<Id, V, S> {
 #[derive(Clone, PartialEq, Eq)]
 #[cfg_attr(any(feature = "std", test), derive(Debug))]
 #[cfg_attr(feature = "derive-codec", derive(Encode, Decode, TypeInfo))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Message<H, N> {
 	/// A prevote message.
 	#[cfg_attr(feature = "derive-codec", codec(index = 0))]
@@ -243,12 +366,23 @@ impl<H, N: Copy> Message<H, N> {
 			Message::PrimaryPropose(ref v) => (&v.target_hash, v.target_number),
 		}
 	}
+
+	/// Get the hash of the target block of the vote.
+	pub fn target_hash(&self) -> &H {
+		self.target().0
+	}
+
+	/// Get the number of the target block of the vote.
+	pub fn target_number(&self) -> N {
+		self.target().1
+	}
 }
 
 /// A signed message.
 #[derive(Clone, PartialEq, Eq)]
 #[cfg_attr(any(feature = "std", test), derive(Debug))]
 #[cfg_attr(feature = "derive-codec", derive(Encode, Decode, TypeInfo))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SignedMessage<H, N, S, Id> {
 	/// The internal message which has been signed.
 	pub message: Message<H, N>,
@@ -265,12 +399,23 @@ impl<H, N: Copy, S, Id> SignedMessage<H, N, S, Id> {
 	pub fn target(&self) -> (&H, N) {
 		self.message.target()
 	}
+
+	/// Get the hash of the target block of the vote.
+	pub fn target_hash(&self) -> &H {
+		self.message.target_hash()
+	}
+
+	/// Get the number of the target block of the vote.
+	pub fn target_number(&self) -> N {
+		self.message.target_number()
+	}
 }
 
 /// A commit message which is an aggregate of precommits.
 #[derive(Clone, PartialEq, Eq)]
 #[cfg_attr(any(feature = "std", test), derive(Debug))]
 #[cfg_attr(feature = "derive-codec", derive(Encode, Decode, TypeInfo))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Commit<H, N, S, Id> {
 	/// The target block's hash.
 	pub target_hash: H,
@@ -280,10 +425,23 @@ pub struct Commit<H, N, S, Id> {
 	pub precommits: Vec<SignedPrecommit<H, N, S, Id>>,
 }
 
+impl<H: Ord, N: Ord, S, Id: Ord> Commit<H, N, S, Id> {
+	/// Sort the precommits in their canonical order, see `SignedPrecommit`'s
+	/// `Ord` implementation. Commits justifying the same block with the same
+	/// set of precommits are then encoded identically.
+	pub fn canonicalize(&mut self) {
+		self.precommits.sort_by(|a, b| a.canonical_key().cmp(&b.canonical_key()));
+	}
+}
+
 /// A signed prevote message.
+///
+/// Signed prevotes are ordered by target number, then target hash, then voter
+/// id, and finally by signature.
 #[derive(Clone, PartialEq, Eq)]
 #[cfg_attr(any(feature = "std", test), derive(Debug))]
 #[cfg_attr(feature = "derive-codec", derive(Encode, Decode, TypeInfo))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SignedPrevote<H, N, S, Id> {
 	/// The prevote message which has been signed.
 	pub prevote: Prevote<H, N>,
@@ -293,10 +451,34 @@ pub struct SignedPrevote<H, N, S, Id> {
 	pub id: Id,
 }
 
+impl<H, N, S, Id> SignedPrevote<H, N, S, Id> {
+	fn canonical_key(&self) -> (&N, &H, &Id) {
+		(&self.prevote.target_number, &self.prevote.target_hash, &self.id)
+	}
+}
+
+impl<H: Ord, N: Ord, S: Ord, Id: Ord> PartialOrd for SignedPrevote<H, N, S, Id> {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl<H: Ord, N: Ord, S: Ord, Id: Ord> Ord for SignedPrevote<H, N, S, Id> {
+	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+		self.canonical_key()
+			.cmp(&other.canonical_key())
+			.then_with(|| self.signature.cmp(&other.signature))
+	}
+}
+
 /// A signed precommit message.
+///
+/// Signed precommits are ordered by target number, then target hash, then
+/// voter id, and finally by signature.
 #[derive(Clone, PartialEq, Eq)]
 #[cfg_attr(any(feature = "std", test), derive(Debug))]
 #[cfg_attr(feature = "derive-codec", derive(Encode, Decode, TypeInfo))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SignedPrecommit<H, N, S, Id> {
 	/// The precommit message which has been signed.
 	pub precommit: Precommit<H, N>,
@@ -306,10 +488,31 @@ pub struct SignedPrecommit<H, N, S, Id> {
 	pub id: Id,
 }
 
+impl<H, N, S, Id> SignedPrecommit<H, N, S, Id> {
+	fn canonical_key(&self) -> (&N, &H, &Id) {
+		(&self.precommit.target_number, &self.precommit.target_hash, &self.id)
+	}
+}
+
+impl<H: Ord, N: Ord, S: Ord, Id: Ord> PartialOrd for SignedPrecommit<H, N, S, Id> {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl<H: Ord, N: Ord, S: Ord, Id: Ord> Ord for SignedPrecommit<H, N, S, Id> {
+	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+		self.canonical_key()
+			.cmp(&other.canonical_key())
+			.then_with(|| self.signature.cmp(&other.signature))
+	}
+}
+
 /// A commit message with compact representation of authentication data.
 #[derive(Clone, PartialEq, Eq)]
 #[cfg_attr(any(feature = "std", test), derive(Debug))]
 #[cfg_attr(feature = "derive-codec", derive(Encode, Decode, TypeInfo))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CompactCommit<H, N, S, Id> {
 	/// The target block's hash.
 	pub target_hash: H,
@@ -329,6 +532,7 @@ pub struct CompactCommit<H, N, S, Id> {
 #[derive(Clone, PartialEq, Eq)]
 #[cfg_attr(any(feature = "std", test), derive(Debug))]
 #[cfg_attr(feature = "derive-codec", derive(Encode, Decode, TypeInfo))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CatchUp<H, N, S, Id> {
 	/// Round number.
 	pub round_number: u64,
@@ -342,6 +546,15 @@ pub struct CatchUp<H, N, S, Id> {
 	pub base_number: N,
 }
 
+impl<H: Ord, N: Ord, S, Id: Ord> CatchUp<H, N, S, Id> {
+	/// Sort the prevotes and precommits in their canonical order, see the `Ord`
+	/// implementations of `SignedPrevote` and `SignedPrecommit`.
+	pub fn canonicalize(&mut self) {
+		self.prevotes.sort_by(|a, b| a.canonical_key().cmp(&b.canonical_key()));
+		self.precommits.sort_by(|a, b| a.canonical_key().cmp(&b.canonical_key()));
+	}
+}
+
 /// Authentication data for a set of many messages, currently a set of precommit signatures but
 /// in the future could be optimized with BLS signature aggregation.
 pub type MultiAuthData<S, Id> = Vec<(S, Id)>;
@@ -354,7 +567,7 @@ impl<H, N, S, Id> From<CompactCommit<H, N, S, Id>> for Commit<H, N, S, Id> {
 			precommits: commit
 				.precommits
 				.into_iter()
-				.zip(commit.auth_data.into_iter())
+				.zip(commit.auth_data)
 				.map(|(precommit, (signature, id))| SignedPrecommit { precommit, signature, id })
 				.collect(),
 		}
@@ -376,6 +589,112 @@ impl<H: Clone, N: Clone, S, Id> From<Commit<H, N, S, Id>> for CompactCommit<H, N
 	}
 }
 
+/// A precommit of a `ReferencedCommit`.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(any(feature = "std", test), derive(Debug))]
+#[cfg_attr(feature = "derive-codec", derive(Encode, Decode, TypeInfo))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PrecommitRef<H, N, S, Id, R> {
+	/// The precommit, included in full.
+	#[cfg_attr(feature = "derive-codec", codec(index = 0))]
+	Full(SignedPrecommit<H, N, S, Id>),
+	/// A compact reference to a precommit already known to the receiver, e.g.
+	/// its hash.
+	#[cfg_attr(feature = "derive-codec", codec(index = 1))]
+	Known(R),
+}
+
+/// A commit message which references the precommits already known to the
+/// receiver instead of including them in full, to save bandwidth.
+///
+/// The receiver reconstructs the commit with `reconcile`, which yields the
+/// precommits in the same order as the original commit so that it validates
+/// identically.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(any(feature = "std", test), derive(Debug))]
+#[cfg_attr(feature = "derive-codec", derive(Encode, Decode, TypeInfo))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReferencedCommit<H, N, S, Id, R> {
+	/// The target block's hash.
+	pub target_hash: H,
+	/// The target block's number.
+	pub target_number: N,
+	/// Precommits for target block or any block after it that justify this commit.
+	pub precommits: Vec<PrecommitRef<H, N, S, Id, R>>,
+}
+
+/// A local store of precommits, used to reconcile a `ReferencedCommit`.
+pub trait PrecommitStore<H, N, S, Id, R> {
+	/// Get the precommit with the given reference, if it is known.
+	fn precommit(&self, reference: &R) -> Option<SignedPrecommit<H, N, S, Id>>;
+}
+
+impl<H, N, S, Id, R> PrecommitStore<H, N, S, Id, R>
+	for crate::std::collections::BTreeMap<R, SignedPrecommit<H, N, S, Id>>
+where
+	H: Clone,
+	N: Clone,
+	S: Clone,
+	Id: Clone,
+	R: Ord,
+{
+	fn precommit(&self, reference: &R) -> Option<SignedPrecommit<H, N, S, Id>> {
+		self.get(reference).cloned()
+	}
+}
+
+impl<H, N, S, Id, R> ReferencedCommit<H, N, S, Id, R> {
+	/// Create a referenced commit from the given commit. The precommits for
+	/// which `reference` returns a reference, e.g. those known to the receiver,
+	/// are referenced, the others are included in full.
+	pub fn new<F>(commit: Commit<H, N, S, Id>, mut reference: F) -> Self
+	where
+		F: FnMut(&SignedPrecommit<H, N, S, Id>) -> Option<R>,
+	{
+		ReferencedCommit {
+			target_hash: commit.target_hash,
+			target_number: commit.target_number,
+			precommits: commit
+				.precommits
+				.into_iter()
+				.map(|signed| match reference(&signed) {
+					Some(reference) => PrecommitRef::Known(reference),
+					None => PrecommitRef::Full(signed),
+				})
+				.collect(),
+		}
+	}
+
+	/// Reconstruct the commit, filling in the referenced precommits from the
+	/// given store.
+	///
+	/// Returns the references which are missing from the store, if any. Their
+	/// precommits should be requested in full from the sender.
+	pub fn reconcile<P>(self, store: &P) -> Result<Commit<H, N, S, Id>, Vec<R>>
+	where
+		P: PrecommitStore<H, N, S, Id, R>,
+	{
+		let mut precommits = Vec::with_capacity(self.precommits.len());
+		let mut missing = Vec::new();
+
+		for precommit in self.precommits {
+			match precommit {
+				PrecommitRef::Full(signed) => precommits.push(signed),
+				PrecommitRef::Known(reference) => match store.precommit(&reference) {
+					Some(signed) => precommits.push(signed),
+					None => missing.push(reference),
+				},
+			}
+		}
+
+		if !missing.is_empty() {
+			return Err(missing)
+		}
+
+		Ok(Commit { target_hash: self.target_hash, target_number: self.target_number, precommits })
+	}
+}
+
 /// Struct returned from `validate_commit` function with information
 /// about the validation result.
 #[derive(Debug, Default)]
@@ -385,6 +704,7 @@ pub struct CommitValidationResult {
 	num_duplicated_precommits: usize,
 	num_equivocations: usize,
 	num_invalid_voters: usize,
+	num_invalid_signatures: usize,
 }
 
 impl CommitValidationResult {
@@ -414,6 +734,12 @@ impl CommitValidationResult {
 	pub fn num_invalid_voters(&self) -> usize {
 		self.num_invalid_voters
 	}
+
+	/// Returns the number of precommits in the commit whose signature was
+	/// rejected, see `verify_commit`.
+	pub fn num_invalid_signatures(&self) -> usize {
+		self.num_invalid_signatures
+	}
 }
 
 /// Validates a GRANDPA commit message.
@@ -488,8 +814,17 @@ where
 		base,
 	});
 
+	// the signatures are only compared to tell equivocations from duplicates,
+	// so they are borrowed instead of cloned.
 	for SignedPrecommit { precommit, id, signature } in &valid_precommits {
-		match round.import_precommit(chain, precommit.clone(), id.clone(), signature.clone())? {
+		let import_result =
+			match round.import_precommit(chain, precommit.clone(), id.clone(), signature) {
+				Ok(import_result) => import_result,
+				// the precommit's target doesn't match the chain.
+				Err(_) => return Ok(validation_result),
+			};
+
+		match import_result {
 			ImportResult { equivocation: Some(_), .. } => {
 				validation_result.num_equivocations += 1;
 				// allow only one equivocation per voter, as extras are redundant.
@@ -519,6 +854,96 @@ where
 	Ok(validation_result)
 }
 
+/// Verifies the signatures of messages.
+///
+/// The signed payload should commit to the round number and the voter set id,
+/// so that a signature can't be replayed in a different round or set.
+pub trait MessageVerifier<H, N, S, Id> {
+	/// Returns `true` if `signature` is a valid signature by `id` of the given
+	/// message in the given round of the voter set with the given id.
+	fn verify(
+		&self,
+		id: &Id,
+		signature: &S,
+		message: &Message<H, N>,
+		round: u64,
+		set_id: u64,
+	) -> bool;
+}
+
+/// Verifies a signed message in the given round of the voter set with the
+/// given id, without importing it into a round.
+///
+/// Returns `Error::UnknownVoter` if the signer isn't part of the voter set and
+/// `Error::InvalidSignature` if the verifier rejects the signature.
+pub fn verify_message<H, N, S, Id, V>(
+	signed: &SignedMessage<H, N, S, Id>,
+	round: u64,
+	set_id: u64,
+	voters: &VoterSet<Id>,
+	verifier: &V,
+) -> Result<(), Error>
+where
+	Id: Ord + Eq,
+	V: MessageVerifier<H, N, S, Id>,
+{
+	if !voters.contains(&signed.id) {
+		return Err(Error::UnknownVoter)
+	}
+
+	if !verifier.verify(&signed.id, &signed.signature, &signed.message, round, set_id) {
+		return Err(Error::InvalidSignature)
+	}
+
+	Ok(())
+}
+
+/// Verifies the precommit signatures of a GRANDPA commit message made in the
+/// given round of the voter set with the given id, and then validates it with
+/// `validate_commit`.
+///
+/// A commit with any invalid signature is rejected without being validated,
+/// in which case `num_invalid_signatures` is non-zero. Otherwise the commit is
+/// invalid only if its precommits don't finalize the commit target, e.g. when
+/// they lack the weight to reach the threshold.
+pub fn verify_commit<H, N, S, I, C, V>(
+	commit: &Commit<H, N, S, I>,
+	round: u64,
+	set_id: u64,
+	voters: &VoterSet<I>,
+	chain: &C,
+	verifier: &V,
+) -> Result<CommitValidationResult, crate::Error>
+where
+	H: Clone + Eq + Ord + std::fmt::Debug,
+	N: Copy + BlockNumberOps + std::fmt::Debug,
+	I: Clone + Ord + Eq + std::fmt::Debug,
+	S: Clone + Eq,
+	C: Chain<H, N>,
+	V: MessageVerifier<H, N, S, I>,
+{
+	// votes from voters outside the set are counted by `validate_commit`.
+	let num_invalid_signatures = commit
+		.precommits
+		.iter()
+		.filter(|signed| voters.contains(&signed.id))
+		.filter(|signed| {
+			let message = Message::Precommit(signed.precommit.clone());
+			!verifier.verify(&signed.id, &signed.signature, &message, round, set_id)
+		})
+		.count();
+
+	if num_invalid_signatures > 0 {
+		return Ok(CommitValidationResult {
+			num_precommits: commit.precommits.len(),
+			num_invalid_signatures,
+			..Default::default()
+		})
+	}
+
+	validate_commit(commit, voters, chain)
+}
+
 /// Runs the callback with the appropriate `CommitProcessingOutcome` based on
 /// the given `CommitValidationResult`. Outcome is bad if ghost is undefined,
 /// good otherwise.
@@ -538,16 +963,19 @@ pub fn process_commit_validation_result(
 #[derive(Default, Clone, PartialEq, Eq)]
 #[cfg_attr(any(feature = "std", test), derive(Debug))]
 #[cfg_attr(feature = "derive-codec", derive(Encode, Decode, TypeInfo))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HistoricalVotes<H, N, S, Id> {
 	seen: Vec<SignedMessage<H, N, S, Id>>,
 	prevote_idx: Option<u64>,
 	precommit_idx: Option<u64>,
+	prevote_ghost_idx: Option<u64>,
+	finalized_idx: Option<u64>,
 }
 
 impl<H, N, S, Id> HistoricalVotes<H, N, S, Id> {
 	/// Create a new HistoricalVotes.
 	pub fn new() -> Self {
-		HistoricalVotes { seen: Vec::new(), prevote_idx: None, precommit_idx: None }
+		HistoricalVotes::new_with(Vec::new(), None, None)
 	}
 
 	/// Create a new HistoricalVotes initialized from the parameters.
@@ -556,7 +984,13 @@ impl<H, N, S, Id> HistoricalVotes<H, N, S, Id> {
 		prevote_idx: Option<u64>,
 		precommit_idx: Option<u64>,
 	) -> Self {
-		HistoricalVotes { seen, prevote_idx, precommit_idx }
+		HistoricalVotes {
+			seen,
+			prevote_idx,
+			precommit_idx,
+			prevote_ghost_idx: None,
+			finalized_idx: None,
+		}
 	}
 
 	/// Push a vote into the list. The value of `self` before this call
@@ -582,6 +1016,18 @@ impl<H, N, S, Id> HistoricalVotes<H, N, S, Id> {
 		self.precommit_idx
 	}
 
+	/// Return the number of messages seen when the round first had a
+	/// prevote-GHOST. None in case it had none yet.
+	pub fn prevote_ghost_idx(&self) -> Option<u64> {
+		self.prevote_ghost_idx
+	}
+
+	/// Return the number of messages seen when the round first finalized a
+	/// block. None in case it finalized none yet.
+	pub fn finalized_idx(&self) -> Option<u64> {
+		self.finalized_idx
+	}
+
 	/// Set the number of messages seen before prevoting.
 	pub fn set_prevoted_idx(&mut self) {
 		self.prevote_idx = Some(self.seen.len() as u64)
@@ -591,6 +1037,17 @@ impl<H, N, S, Id> HistoricalVotes<H, N, S, Id> {
 	pub fn set_precommitted_idx(&mut self) {
 		self.precommit_idx = Some(self.seen.len() as u64)
 	}
+
+	/// Set the number of messages seen when the round first had a
+	/// prevote-GHOST.
+	pub fn set_prevote_ghost_idx(&mut self) {
+		self.prevote_ghost_idx = Some(self.seen.len() as u64)
+	}
+
+	/// Set the number of messages seen when the round first finalized a block.
+	pub fn set_finalized_idx(&mut self) {
+		self.finalized_idx = Some(self.seen.len() as u64)
+	}
 }
 
 #[cfg(test)]
@@ -617,6 +1074,145 @@ mod tests {
 		assert_eq!(signed, signed2);
 	}
 
+	// signatures are the signer, round and set id of the message.
+	struct TupleVerifier;
+
+	impl MessageVerifier<&'static str, u32, (u32, u64, u64), u32> for TupleVerifier {
+		fn verify(
+			&self,
+			id: &u32,
+			signature: &(u32, u64, u64),
+			_message: &Message<&'static str, u32>,
+			round: u64,
+			set_id: u64,
+		) -> bool {
+			*signature == (*id, round, set_id)
+		}
+	}
+
+	#[test]
+	fn verifying_messages() {
+		let voters = VoterSet::new([(1, 1), (2, 1)].iter().cloned()).unwrap();
+		let signed = |id, signature| SignedMessage {
+			message: Message::Prevote(Prevote::new("A", 2)),
+			signature,
+			id,
+		};
+
+		assert_eq!(verify_message(&signed(1, (1, 5, 0)), 5, 0, &voters, &TupleVerifier), Ok(()));
+
+		// signed for a different set or round.
+		assert_eq!(
+			verify_message(&signed(1, (1, 5, 1)), 5, 0, &voters, &TupleVerifier),
+			Err(Error::InvalidSignature),
+		);
+		assert_eq!(
+			verify_message(&signed(2, (2, 4, 0)), 5, 0, &voters, &TupleVerifier),
+			Err(Error::InvalidSignature),
+		);
+
+		// signed by a voter outside the set.
+		assert_eq!(
+			verify_message(&signed(3, (3, 5, 0)), 5, 0, &voters, &TupleVerifier),
+			Err(Error::UnknownVoter),
+		);
+	}
+
+	#[test]
+	fn verifying_commits() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B"]);
+
+		let voters = VoterSet::new((1..=4).map(|id| (id, 1))).unwrap();
+		let precommit =
+			|id, signature| SignedPrecommit { precommit: Precommit::new("B", 3), signature, id };
+		let commit = |precommits| Commit { target_hash: "B", target_number: 3, precommits };
+		let verify = |commit: &Commit<_, _, _, _>| {
+			verify_commit(commit, 5, 0, &voters, &chain, &TupleVerifier)
+		};
+
+		let valid = commit((1..=3).map(|id| precommit(id, (id, 5, 0))).collect());
+		let result = verify(&valid).unwrap();
+		assert!(result.is_valid());
+		assert_eq!(result.num_invalid_signatures(), 0);
+
+		// correctly signed, but lacking the weight to finalize the target.
+		let insufficient = commit((1..=2).map(|id| precommit(id, (id, 5, 0))).collect());
+		let result = verify(&insufficient).unwrap();
+		assert!(!result.is_valid());
+		assert_eq!(result.num_invalid_signatures(), 0);
+
+		// one of the precommits was signed in a different round.
+		let mut forged = valid.clone();
+		forged.precommits.push(precommit(4, (4, 4, 0)));
+		let result = verify(&forged).unwrap();
+		assert!(!result.is_valid());
+		assert_eq!(result.num_invalid_signatures(), 1);
+		assert_eq!(result.num_precommits(), 4);
+	}
+
+	#[test]
+	fn message_target_accessors() {
+		let messages = [
+			Message::Prevote(Prevote::new("A", 2)),
+			Message::Precommit(Precommit::new("B", 3)),
+			Message::PrimaryPropose(PrimaryPropose::new("C", 4)),
+		];
+
+		for (message, expected) in messages.iter().zip([("A", 2), ("B", 3), ("C", 4)]) {
+			assert_eq!(message.target(), (&expected.0, expected.1));
+			assert_eq!(message.target_hash(), message.target().0);
+			assert_eq!(message.target_number(), message.target().1);
+
+			let signed = SignedMessage { message: message.clone(), signature: 1u32, id: 1u32 };
+			assert_eq!(signed.target_hash(), &expected.0);
+			assert_eq!(signed.target_number(), expected.1);
+		}
+	}
+
+	#[test]
+	fn canonical_commits_are_identical() {
+		let precommit = |target_hash: &'static str, target_number, id| SignedPrecommit {
+			precommit: Precommit { target_hash: target_hash.as_bytes().to_vec(), target_number },
+			signature: vec![id as u8],
+			id,
+		};
+
+		let precommits = vec![
+			precommit("B", 3u32, 2u64),
+			precommit("C", 4, 1),
+			precommit("A", 3, 3),
+			precommit("B", 3, 1),
+		];
+
+		let mut a =
+			Commit { target_hash: b"A".to_vec(), target_number: 2, precommits: precommits.clone() };
+		let mut b = Commit { precommits: precommits.iter().rev().cloned().collect(), ..a.clone() };
+
+		a.canonicalize();
+		b.canonicalize();
+
+		assert_eq!(a, b);
+		assert_eq!(
+			a.precommits
+				.iter()
+				.map(|p| (p.precommit.target_number, p.id))
+				.collect::<Vec<_>>(),
+			vec![(3, 3), (3, 1), (3, 2), (4, 1)],
+		);
+
+		// the canonical order agrees with `Ord`.
+		let mut sorted = precommits;
+		sorted.sort();
+		assert_eq!(a.precommits, sorted);
+
+		#[cfg(feature = "derive-codec")]
+		{
+			use parity_scale_codec::Encode;
+			assert_eq!(a.encode(), b.encode());
+		}
+	}
+
 	#[test]
 	fn commit_validation() {
 		let mut chain = DummyChain::new();
@@ -672,6 +1268,45 @@ mod tests {
 		assert!(!result.is_valid());
 	}
 
+	#[test]
+	fn reconciling_referenced_commits() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C"]);
+
+		let voters = VoterSet::new((1..=10).map(|id| (id, 1))).unwrap();
+		let precommit = |target_hash, target_number, id| SignedPrecommit {
+			precommit: Precommit { target_hash, target_number },
+			id,
+			signature: id * 10,
+		};
+
+		let mut precommits: Vec<_> = (1..=4).map(|id| precommit("C", 4, id)).collect();
+		precommits.extend((5..=7).map(|id| precommit("B", 3, id)));
+		let commit = Commit { target_hash: "B", target_number: 3, precommits };
+		assert!(validate_commit(&commit, &voters, &chain).unwrap().is_valid());
+
+		// the receiver already knows the precommits of the first five voters,
+		// which are referenced by their signature.
+		let known: crate::std::collections::BTreeMap<_, _> =
+			commit.precommits[..5].iter().map(|p| (p.signature, p.clone())).collect();
+		let referenced = ReferencedCommit::new(commit.clone(), |p| {
+			known.contains_key(&p.signature).then_some(p.signature)
+		});
+
+		let full = referenced.precommits.iter().filter(|p| matches!(p, PrecommitRef::Full(_)));
+		assert_eq!(full.count(), 2);
+
+		let reconciled = referenced.clone().reconcile(&known).unwrap();
+		assert_eq!(reconciled, commit);
+		assert!(validate_commit(&reconciled, &voters, &chain).unwrap().is_valid());
+
+		// precommits missing from the store must be requested in full.
+		let mut partial = known.clone();
+		partial.remove(&20);
+		partial.remove(&50);
+		assert_eq!(referenced.reconcile(&partial), Err(vec![20, 50]));
+	}
+
 	#[test]
 	fn commit_validation_with_equivocation() {
 		let mut chain = DummyChain::new();
@@ -747,4 +1382,30 @@ mod tests {
 		// there is one invalid voter in the commit
 		assert_eq!(result.num_invalid_voters(), 1);
 	}
+
+	#[test]
+	fn commit_validation_with_invalid_target_number() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C"]);
+
+		let voters = VoterSet::new((1..=100).map(|id| (id, 1))).unwrap();
+
+		let make_precommit = |target_hash, target_number, id| SignedPrecommit {
+			precommit: Precommit { target_hash, target_number },
+			id,
+			signature: (),
+		};
+
+		// `C` is block 4, but most precommits claim a higher number for it.
+		let mut precommits = vec![make_precommit("A", 2, 1)];
+		precommits.extend((2..=67).map(|id| make_precommit("C", 6, id)));
+
+		let result = validate_commit(
+			&Commit { target_hash: "C", target_number: 6, precommits },
+			&voters,
+			&chain,
+		);
+
+		assert!(!result.unwrap().is_valid());
+	}
 }
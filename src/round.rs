@@ -22,14 +22,9 @@ use context::{Context, Vote, VoteNode};
 use parity_scale_codec::{Decode, Encode};
 
 use crate::{
-	std::{
-		self,
-		collections::btree_map::{BTreeMap, Entry},
-		fmt,
-		vec::Vec,
-	},
+	std::{self, collections::btree_map::BTreeMap, fmt, vec::Vec},
 	vote_graph::VoteGraph,
-	voter_set::{VoterInfo, VoterSet},
+	voter_set::{PrimarySelector, VoterInfo, VoterSet},
 	weights::{VoteWeight, VoterWeight},
 };
 
@@ -99,33 +94,43 @@ impl<Id: Ord + Eq + Clone, Vote: Clone + Eq, Signature: Clone + Eq>
 		&mut self,
 		id: Id,
 		vote: Vote,
-		signature: Signature,
+		signature: &Signature,
 		weight: VoterWeight,
-	) -> AddVoteResult<Vote, Signature> {
-		match self.votes.entry(id) {
-			Entry::Vacant(vacant) => {
-				self.current_weight = self.current_weight + weight;
-				let multiplicity = vacant.insert(VoteMultiplicity::Single(vote, signature));
+	) -> AddVoteResult<'_, Vote, Signature> {
+		if self.votes.get(&id).is_some_and(|m| m.contains(&vote, signature)) {
+			return AddVoteResult { multiplicity: None, duplicated: true }
+		}
 
-				AddVoteResult { multiplicity: Some(multiplicity), duplicated: false }
+		// signatures may be expensive to clone, so they are only cloned when the
+		// vote is kept and the first vote of an equivocator is moved.
+		let multiplicity = match self.votes.remove(&id) {
+			None => {
+				self.current_weight = self.current_weight + weight;
+				VoteMultiplicity::Single(vote, signature.clone())
 			},
-			Entry::Occupied(mut occupied) => {
-				if occupied.get().contains(&vote, &signature) {
-					return AddVoteResult { multiplicity: None, duplicated: true }
-				}
-
+			Some(VoteMultiplicity::Single(v, s)) =>
+				VoteMultiplicity::Equivocated((v, s), (vote, signature.clone())),
+			Some(equivocated) => {
 				// import, but ignore further equivocations.
-				let new_val = match *occupied.get_mut() {
-					VoteMultiplicity::Single(ref v, ref s) =>
-						VoteMultiplicity::Equivocated((v.clone(), s.clone()), (vote, signature)),
-					VoteMultiplicity::Equivocated(_, _) =>
-						return AddVoteResult { multiplicity: None, duplicated: false },
-				};
+				self.votes.insert(id, equivocated);
+				return AddVoteResult { multiplicity: None, duplicated: false }
+			},
+		};
 
-				*occupied.get_mut() = new_val;
+		let multiplicity = self.votes.entry(id).or_insert(multiplicity);
+		AddVoteResult { multiplicity: Some(&*multiplicity), duplicated: false }
+	}
 
-				AddVoteResult { multiplicity: Some(&*occupied.into_mut()), duplicated: false }
+	// undo the last vote added for the given ID, e.g. when it couldn't be
+	// imported into the vote-graph.
+	fn undo_vote(&mut self, id: &Id, weight: VoterWeight) {
+		match self.votes.remove(id) {
+			Some(VoteMultiplicity::Single(..)) =>
+				self.current_weight = self.current_weight - weight,
+			Some(VoteMultiplicity::Equivocated(first, _)) => {
+				self.votes.insert(id.clone(), VoteMultiplicity::Single(first.0, first.1));
 			},
+			None => {},
 		}
 	}
 
@@ -146,6 +151,22 @@ impl<Id: Ord + Eq + Clone, Vote: Clone + Eq, Signature: Clone + Eq>
 		votes
 	}
 
+	// Returns proofs of all equivocations, ordered by the id of the equivocator.
+	fn equivocations(
+		&self,
+		round_number: u64,
+	) -> impl Iterator<Item = Equivocation<Id, Vote, Signature>> + '_ {
+		self.votes.iter().filter_map(move |(id, vote)| match vote {
+			VoteMultiplicity::Single(..) => None,
+			VoteMultiplicity::Equivocated(first, second) => Some(Equivocation {
+				round_number,
+				identity: id.clone(),
+				first: first.clone(),
+				second: second.clone(),
+			}),
+		})
+	}
+
 	// Current vote weight and number of participants.
 	fn participation(&self) -> (VoteWeight, usize) {
 		(self.current_weight, self.votes.len())
@@ -156,7 +177,14 @@ impl<Id: Ord + Eq + Clone, Vote: Clone + Eq, Signature: Clone + Eq>
 #[derive(PartialEq, Clone)]
 #[cfg_attr(any(feature = "std", test), derive(Debug))]
 #[cfg_attr(feature = "derive-codec", derive(Encode, Decode, scale_info::TypeInfo))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct State<H, N> {
+	/// The number of the round this is the state of.
+	///
+	/// It's not part of the SCALE encoding, so that states encoded before it
+	/// was added still decode. Decoded states are of round zero.
+	#[cfg_attr(feature = "derive-codec", codec(skip))]
+	pub round: u64,
 	/// The prevote-GHOST block.
 	pub prevote_ghost: Option<(H, N)>,
 	/// The finalized block.
@@ -168,9 +196,10 @@ pub struct State<H, N> {
 }
 
 impl<H: Clone, N: Clone> State<H, N> {
-	/// Genesis state.
+	/// Genesis state, i.e. of round zero.
 	pub fn genesis(genesis: (H, N)) -> Self {
 		State {
+			round: 0,
 			prevote_ghost: Some(genesis.clone()),
 			finalized: Some(genesis.clone()),
 			estimate: Some(genesis),
@@ -179,6 +208,23 @@ impl<H: Clone, N: Clone> State<H, N> {
 	}
 }
 
+/// A condition which keeps a round from being completed, see
+/// `Round::completability_blockers`.
+#[derive(PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(any(feature = "std", test), derive(Debug))]
+pub enum Blocker {
+	/// There is no prevote-GHOST, i.e. no block has supermajority prevotes.
+	NoPrevoteGhost,
+	/// There is no round-estimate.
+	NoEstimate,
+	/// There aren't enough precommits to rule out that a block higher than the
+	/// round-estimate is finalized in the round.
+	InsufficientPrecommits,
+	/// The estimate of the previous round is finalized neither in the previous
+	/// round nor in this one.
+	PreviousEstimateNotFinalized,
+}
+
 /// Parameters for starting a round.
 pub struct RoundParams<Id: Ord + Eq, H, N> {
 	/// The round number for votes.
@@ -193,30 +239,39 @@ pub struct RoundParams<Id: Ord + Eq, H, N> {
 pub struct Round<Id: Ord + Eq, H: Ord + Eq, N, Signature> {
 	round_number: u64,
 	context: Context<Id>,
+	base: (H, N),                     // the round base, the graph may be pruned above it.
 	graph: VoteGraph<H, N, VoteNode>, // DAG of blocks which have been voted on.
 	prevote: VoteTracker<Id, Prevote<H, N>, Signature>, // tracks prevotes that have been counted
 	precommit: VoteTracker<Id, Precommit<H, N>, Signature>, // tracks precommits
 	historical_votes: HistoricalVotes<H, N, Signature, Id>,
-	prevote_ghost: Option<(H, N)>,   // current memoized prevote-GHOST block
+	prevote_ghost: Option<(H, N)>, // current memoized prevote-GHOST block
 	precommit_ghost: Option<(H, N)>, // current memoized precommit-GHOST block
-	finalized: Option<(H, N)>,       // best finalized block in this round.
-	estimate: Option<(H, N)>,        // current memoized round-estimate
-	completable: bool,               // whether the round is completable
+	finalized: Option<(H, N)>,     // best finalized block in this round.
+	finalized_floor: Option<(H, N)>, // block known to be finalized, see `set_finalized_floor`.
+	estimate: Option<(H, N)>,      // current memoized round-estimate
+	completable: bool,             // whether the round is completable
 }
 
 /// Result of importing a Prevote or Precommit.
-pub(crate) struct ImportResult<Id, P, Signature> {
+pub(crate) struct ImportResult<Id, H, N, P, Signature> {
 	/// Indicates if the voter is part of the voter set.
 	pub(crate) valid_voter: bool,
 	/// Indicates if the vote is duplicated.
 	pub(crate) duplicated: bool,
 	/// An equivocation proof, if the vote is an equivocation.
 	pub(crate) equivocation: Option<Equivocation<Id, P, Signature>>,
+	/// The new finalized block, if importing the vote changed it.
+	pub(crate) finalized_changed: Option<(H, N)>,
 }
 
-impl<Id, P, Signature> Default for ImportResult<Id, P, Signature> {
+impl<Id, H, N, P, Signature> Default for ImportResult<Id, H, N, P, Signature> {
 	fn default() -> Self {
-		ImportResult { valid_voter: false, duplicated: false, equivocation: None }
+		ImportResult {
+			valid_voter: false,
+			duplicated: false,
+			equivocation: None,
+			finalized_changed: None,
+		}
 	}
 }
 
@@ -234,6 +289,7 @@ where
 		Round {
 			round_number: round_params.round_number,
 			context: Context::new(round_params.voters),
+			base: (base_hash.clone(), base_number),
 			graph: VoteGraph::new(base_hash, base_number, VoteNode::default()),
 			prevote: VoteTracker::new(),
 			precommit: VoteTracker::new(),
@@ -241,11 +297,77 @@ where
 			prevote_ghost: None,
 			precommit_ghost: None,
 			finalized: None,
+			finalized_floor: None,
 			estimate: None,
 			completable: false,
 		}
 	}
 
+	/// Set a block which is already known to be finalized, e.g. from a trusted
+	/// checkpoint. The round never reports a block below it as finalized, and
+	/// no longer searches the blocks below it for finality.
+	///
+	/// The floor must be equal to or a descendent of the base of the round.
+	pub fn set_finalized_floor<C: Chain<H, N>>(
+		&mut self,
+		chain: &C,
+		hash: H,
+		number: N,
+	) -> Result<(), crate::Error> {
+		let (base_hash, base_number) = self.graph.base();
+		if number < base_number || !chain.is_equal_or_descendent_of(base_hash, hash.clone()) {
+			return Err(crate::Error::NotDescendent)
+		}
+
+		self.finalized_floor = Some((hash, number));
+		self.update();
+
+		Ok(())
+	}
+
+	/// Prune the blocks below one known to be finalized from the vote-graph, so
+	/// that they don't have to be kept in memory. The block is set as the
+	/// finalized floor, see `set_finalized_floor`.
+	///
+	/// The graph is only pruned if the block finalized in the round is equal to
+	/// or a descendent of the given one, so that the round state isn't
+	/// affected. Votes for blocks which aren't equal to or descendents of the
+	/// given one can't be imported anymore afterwards. Returns whether the
+	/// graph was pruned.
+	pub fn prune<C: Chain<H, N>>(
+		&mut self,
+		chain: &C,
+		hash: H,
+		number: N,
+	) -> Result<bool, crate::Error> {
+		let above = |block: &Option<(H, N)>| {
+			block.as_ref().is_some_and(|(block_hash, block_number)| {
+				*block_number >= number &&
+					chain.is_equal_or_descendent_of(hash.clone(), block_hash.clone())
+			})
+		};
+
+		if !above(&self.finalized) {
+			return Ok(false)
+		}
+
+		self.set_finalized_floor(chain, hash.clone(), number)?;
+		if !self.graph.prune(hash.clone(), number) {
+			return Ok(false)
+		}
+
+		if !above(&self.precommit_ghost) {
+			self.precommit_ghost = None;
+		}
+
+		Ok(true)
+	}
+
+	/// Return the block set with `set_finalized_floor`, if any.
+	pub fn finalized_floor(&self) -> Option<&(H, N)> {
+		self.finalized_floor.as_ref()
+	}
+
 	/// Return the round number.
 	pub fn number(&self) -> u64 {
 		self.round_number
@@ -254,7 +376,9 @@ where
 	/// Import a prevote. Returns an equivocation proof, if the vote is an equivocation,
 	/// and a bool indicating if the vote is duplicated (see `ImportResult`).
 	///
-	/// Ignores duplicate prevotes (not equivocations).
+	/// Ignores duplicate prevotes (not equivocations). If the prevote's target
+	/// can't be added to the vote-graph, an error is returned and the round is
+	/// left as if the prevote was never imported.
 	#[cfg_attr(not(feature = "std"), allow(unused))]
 	pub(crate) fn import_prevote<C: Chain<H, N>>(
 		&mut self,
@@ -262,7 +386,7 @@ where
 		prevote: Prevote<H, N>,
 		signer: Id,
 		signature: Signature,
-	) -> Result<ImportResult<Id, Prevote<H, N>, Signature>, crate::Error> {
+	) -> Result<ImportResult<Id, H, N, Prevote<H, N>, Signature>, crate::Error> {
 		let mut import_result = ImportResult::default();
 
 		let info = match self.context.voters().get(&signer) {
@@ -274,30 +398,29 @@ where
 		let weight = info.weight();
 
 		let equivocation = {
-			let multiplicity = match self.prevote.add_vote(
-				signer.clone(),
-				prevote.clone(),
-				signature.clone(),
-				weight,
-			) {
-				AddVoteResult { multiplicity: Some(m), .. } => m,
-				AddVoteResult { duplicated, .. } => {
-					import_result.duplicated = duplicated;
-					return Ok(import_result)
-				},
-			};
+			let multiplicity =
+				match self.prevote.add_vote(signer.clone(), prevote.clone(), &signature, weight) {
+					AddVoteResult { multiplicity: Some(m), .. } => m,
+					AddVoteResult { duplicated, .. } => {
+						import_result.duplicated = duplicated;
+						return Ok(import_result)
+					},
+				};
 			let round_number = self.round_number;
 
 			match multiplicity {
 				VoteMultiplicity::Single(single_vote, _) => {
 					let vote = Vote::new(&info, Phase::Prevote);
 
-					self.graph.insert(
+					if let Err(e) = self.graph.insert(
 						single_vote.target_hash.clone(),
 						single_vote.target_number,
 						vote,
 						chain,
-					)?;
+					) {
+						self.prevote.undo_vote(&signer, weight);
+						return Err(e)
+					}
 
 					// Push the vote into HistoricalVotes.
 					let message = Message::Prevote(prevote);
@@ -307,6 +430,19 @@ where
 					None
 				},
 				VoteMultiplicity::Equivocated(ref first, ref second) => {
+					// the weight of an equivocator counts on every block anyway, but
+					// the second vote is added to the graph so that both forks are
+					// considered for the prevote-GHOST whichever was seen first.
+					if let Err(e) = self.graph.insert(
+						second.0.target_hash.clone(),
+						second.0.target_number,
+						Vote::new(&info, Phase::Prevote),
+						chain,
+					) {
+						self.prevote.undo_vote(&signer, weight);
+						return Err(e)
+					}
+
 					// mark the equivocator as such. no need to "undo" the first vote.
 					self.context.equivocated(&info, Phase::Prevote);
 
@@ -325,15 +461,24 @@ where
 			}
 		};
 
-		// update prevote-GHOST
+		// update prevote-GHOST. without equivocations it can only move to a
+		// descendent, so we search from the current one. equivocations can make
+		// several forks heavy enough, in which case we search from the base so
+		// that the tie-break doesn't depend on the order votes were imported in.
 		let threshold = self.threshold();
 		if self.prevote.current_weight >= threshold {
-			self.prevote_ghost = self.graph.find_ghost(self.prevote_ghost.take(), |v| {
-				self.context.weight(v, Phase::Prevote) >= threshold
-			});
+			let current_best = if self.context.equivocation_weight(Phase::Prevote) > VoteWeight(0) {
+				None
+			} else {
+				self.prevote_ghost.take()
+			};
+
+			self.prevote_ghost = self
+				.graph
+				.find_ghost(current_best, |v| self.context.weight(v, Phase::Prevote) >= threshold);
 		}
 
-		self.update();
+		self.update_and_note_finalized(&mut import_result);
 		import_result.equivocation = equivocation;
 		Ok(import_result)
 	}
@@ -341,14 +486,16 @@ where
 	/// Import a precommit. Returns an equivocation proof, if the vote is an
 	/// equivocation, and a bool indicating if the vote is duplicated (see `ImportResult`).
 	///
-	/// Ignores duplicate precommits (not equivocations).
+	/// Ignores duplicate precommits (not equivocations). If the precommit's target
+	/// can't be added to the vote-graph, an error is returned and the round is
+	/// left as if the precommit was never imported.
 	pub(crate) fn import_precommit<C: Chain<H, N>>(
 		&mut self,
 		chain: &C,
 		precommit: Precommit<H, N>,
 		signer: Id,
 		signature: Signature,
-	) -> Result<ImportResult<Id, Precommit<H, N>, Signature>, crate::Error> {
+	) -> Result<ImportResult<Id, H, N, Precommit<H, N>, Signature>, crate::Error> {
 		let mut import_result = ImportResult::default();
 
 		let info = match self.context.voters().get(&signer) {
@@ -362,7 +509,7 @@ where
 			let multiplicity = match self.precommit.add_vote(
 				signer.clone(),
 				precommit.clone(),
-				signature.clone(),
+				&signature,
 				weight,
 			) {
 				AddVoteResult { multiplicity: Some(m), .. } => m,
@@ -378,12 +525,15 @@ where
 				VoteMultiplicity::Single(single_vote, _) => {
 					let vote = Vote::new(&info, Phase::Precommit);
 
-					self.graph.insert(
+					if let Err(e) = self.graph.insert(
 						single_vote.target_hash.clone(),
 						single_vote.target_number,
 						vote,
 						chain,
-					)?;
+					) {
+						self.precommit.undo_vote(&signer, weight);
+						return Err(e)
+					}
 
 					let message = Message::Precommit(precommit);
 					let signed_message = SignedMessage { id: signer, signature, message };
@@ -410,7 +560,7 @@ where
 			}
 		};
 
-		self.update();
+		self.update_and_note_finalized(&mut import_result);
 		import_result.equivocation = equivocation;
 		Ok(import_result)
 	}
@@ -418,6 +568,7 @@ where
 	/// Return the current state.
 	pub fn state(&self) -> State<H, N> {
 		State {
+			round: self.round_number,
 			prevote_ghost: self.prevote_ghost.clone(),
 			finalized: self.finalized.clone(),
 			estimate: self.estimate.clone(),
@@ -504,6 +655,107 @@ where
 		Some(find_valid_precommits)
 	}
 
+	/// Returns a commit justifying the block finalized in this round.
+	///
+	/// Out of the `finalizing_precommits` only those of the voters with the
+	/// lowest ids are included, up to the threshold weight, and the commit is
+	/// canonicalized. Voters which have seen the same precommits therefore
+	/// produce identical commits.
+	///
+	/// Only returns `None` if no block has been finalized in this round.
+	pub fn commit<C: Chain<H, N>>(
+		&mut self,
+		chain: &C,
+	) -> Option<crate::Commit<H, N, Signature, Id>> {
+		let (target_hash, target_number) = self.finalized.clone()?;
+		let finalizing_precommits = self.finalizing_precommits(chain)?.collect::<Vec<_>>();
+
+		// the precommits are yielded in voter id order, with both votes of an
+		// equivocator next to each other.
+		let threshold = self.threshold();
+		let mut weight = VoteWeight(0);
+		let mut precommits = Vec::new();
+		for signed in finalizing_precommits {
+			let last_id =
+				precommits.last().map(|last: &crate::SignedPrecommit<_, _, _, _>| &last.id);
+
+			if last_id != Some(&signed.id) {
+				if weight >= threshold {
+					break
+				}
+
+				if let Some(info) = self.voters().get(&signed.id) {
+					weight = weight + info.weight();
+				}
+			}
+
+			precommits.push(signed);
+		}
+
+		let mut commit = crate::Commit { target_hash, target_number, precommits };
+		commit.canonicalize();
+
+		Some(commit)
+	}
+
+	/// Returns a catch-up proving the round completable with all imported
+	/// votes, for voters which are lagging behind. The catch-up is
+	/// canonicalized.
+	///
+	/// Only returns `None` if the round isn't completable.
+	pub fn catch_up(&self) -> Option<crate::CatchUp<H, N, Signature, Id>> {
+		if !self.completable {
+			return None
+		}
+
+		let (base_hash, base_number) = self.base();
+		let mut catch_up = crate::CatchUp {
+			round_number: self.number(),
+			prevotes: self
+				.prevotes()
+				.into_iter()
+				.map(|(id, prevote, signature)| crate::SignedPrevote { prevote, signature, id })
+				.collect(),
+			precommits: self
+				.precommits()
+				.into_iter()
+				.map(|(id, precommit, signature)| crate::SignedPrecommit {
+					precommit,
+					signature,
+					id,
+				})
+				.collect(),
+			base_hash,
+			base_number,
+		};
+		catch_up.canonicalize();
+
+		Some(catch_up)
+	}
+
+	// update the round state, noting in the import result if the finalized
+	// block has changed and in the historical votes when the round first had
+	// a prevote-GHOST and finalized a block.
+	fn update_and_note_finalized<P>(
+		&mut self,
+		import_result: &mut ImportResult<Id, H, N, P, Signature>,
+	) {
+		let finalized = self.finalized.clone();
+		self.update();
+
+		if self.finalized != finalized {
+			import_result.finalized_changed = self.finalized.clone();
+		}
+
+		if self.prevote_ghost.is_some() && self.historical_votes.prevote_ghost_idx().is_none() {
+			self.historical_votes.set_prevote_ghost_idx();
+		}
+
+		if self.finalized.is_some() && self.historical_votes.finalized_idx().is_none() {
+			self.historical_votes.set_finalized_idx();
+		}
+	}
+
 	// update the round-estimate and whether the round is completable.
 	fn update(&mut self) {
 		let threshold = self.threshold();
@@ -523,7 +775,12 @@ where
 		// 2/3+ prevote and precommit weight.
 		let current_precommits = self.precommit.current_weight;
 		if current_precommits >= self.threshold() {
-			self.finalized = self.graph.find_ancestor(g_hash.clone(), g_num, |v| {
+			let lowest = match self.finalized_floor {
+				Some((_, floor_number)) => floor_number,
+				None => self.graph.base().1,
+			};
+
+			self.finalized = self.graph.find_ancestor_above(g_hash.clone(), g_num, lowest, |v| {
 				ctx.weight(v, Phase::Precommit) >= threshold
 			});
 		};
@@ -545,7 +802,9 @@ where
 				ctx.voters().total_weight() - self.precommit.current_weight;
 
 			move |node: &VoteNode| {
-				// total precommits for this block, including equivocations.
+				// total precommits for this block, including equivocations. the
+				// weight of an equivocator counts towards every block since its
+				// precommit could be used to finalize any of them.
 				let precommitted_for = ctx.weight(node, Phase::Precommit);
 
 				// equivocations we could still get are out of those who
@@ -580,14 +839,14 @@ where
 			return
 		}
 
-		self.completable = self.estimate.clone().map_or(false, |(b_hash, b_num)| {
+		self.completable = self.estimate.clone().is_some_and(|(b_hash, b_num)| {
 			b_hash != g_hash || {
 				// round-estimate is the same as the prevote-ghost.
 				// this round is still completable if no further blocks
 				// could have commit-supermajority.
 				self.graph
 					.find_ghost(Some((b_hash, b_num)), possible_to_precommit)
-					.map_or(true, |x| x == (g_hash, g_num))
+					.is_none_or(|x| x == (g_hash, g_num))
 			}
 		})
 	}
@@ -601,6 +860,13 @@ where
 		self.estimate.as_ref()
 	}
 
+	/// Fetch the prevote-GHOST, the block with supermajority prevotes.
+	///
+	/// Returns `None` when no block has supermajority prevotes yet.
+	pub fn prevote_ghost(&self) -> Option<&(H, N)> {
+		self.prevote_ghost.as_ref()
+	}
+
 	/// Fetch the most recently finalized block.
 	pub fn finalized(&self) -> Option<&(H, N)> {
 		self.finalized.as_ref()
@@ -615,14 +881,68 @@ where
 		self.completable
 	}
 
+	/// Return the conditions which keep the round from being completed, given
+	/// the state of the previous round. The round can be completed once there
+	/// are none.
+	///
+	/// This is empty when the round is completable and the previous round's
+	/// estimate has been finalized.
+	pub fn completability_blockers(&self, previous: &State<H, N>) -> Vec<Blocker> {
+		let mut blockers = Vec::new();
+
+		if self.prevote_ghost.is_none() {
+			blockers.push(Blocker::NoPrevoteGhost);
+		}
+
+		if self.estimate.is_none() {
+			blockers.push(Blocker::NoEstimate);
+		} else if !self.completable {
+			blockers.push(Blocker::InsufficientPrecommits);
+		}
+
+		let previous_estimate_finalized = match *previous {
+			State {
+				estimate: Some((_, previous_estimate)),
+				finalized: Some((_, previous_finalized)),
+				..
+			} => {
+				// either it was already finalized in the previous round or it must
+				// be finalized in this one.
+				previous_estimate <= previous_finalized ||
+					self.finalized
+						.as_ref()
+						.is_some_and(|(_, finalized)| previous_estimate <= *finalized)
+			},
+			_ => false,
+		};
+
+		if !previous_estimate_finalized {
+			blockers.push(Blocker::PreviousEstimateNotFinalized);
+		}
+
+		blockers
+	}
+
 	/// Threshold weight for supermajority.
 	pub fn threshold(&self) -> VoterWeight {
 		self.context.voters().threshold()
 	}
 
+	/// Flag votes which are more than the given depth above the nearest block
+	/// voted on, see `VoteGraph::flag_sparse_votes`.
+	pub fn flag_sparse_votes(&mut self, depth: usize) {
+		self.graph.flag_sparse_votes(depth);
+	}
+
+	/// Take the longest span of blocks without votes below a vote flagged since
+	/// the last call, if any, see `flag_sparse_votes`.
+	pub fn take_sparse_votes(&mut self) -> Option<usize> {
+		self.graph.take_sparse_votes()
+	}
+
 	/// Return the round base.
 	pub fn base(&self) -> (H, N) {
-		self.graph.base()
+		self.base.clone()
 	}
 
 	/// Return the round voters and weights.
@@ -630,9 +950,10 @@ where
 		self.context.voters()
 	}
 
-	/// Return the primary voter of the round.
-	pub fn primary_voter(&self) -> (&Id, &VoterInfo) {
-		self.context.voters().nth_mod(self.round_number as usize)
+	/// Return the primary voter of the round, chosen by the given selector,
+	/// e.g. `Environment::primary_selector`.
+	pub fn primary_voter(&self, selector: &dyn PrimarySelector<Id>) -> (&Id, &VoterInfo) {
+		selector.primary_voter(self.round_number, self.context.voters())
 	}
 
 	/// Get the current weight and number of voters who have participated in prevoting.
@@ -655,6 +976,27 @@ where
 		self.precommit.votes()
 	}
 
+	/// Return proofs of all equivocations observed in the round, prevote
+	/// equivocations first and then precommit equivocations, each ordered by
+	/// the id of the equivocator.
+	pub fn equivocations(&self) -> Vec<Equivocation<Id, Message<H, N>, Signature>> {
+		let prevotes = self.prevote.equivocations(self.round_number).map(|e| Equivocation {
+			round_number: e.round_number,
+			identity: e.identity,
+			first: (Message::Prevote(e.first.0), e.first.1),
+			second: (Message::Prevote(e.second.0), e.second.1),
+		});
+
+		let precommits = self.precommit.equivocations(self.round_number).map(|e| Equivocation {
+			round_number: e.round_number,
+			identity: e.identity,
+			first: (Message::Precommit(e.first.0), e.first.1),
+			second: (Message::Precommit(e.second.0), e.second.1),
+		});
+
+		prevotes.chain(precommits).collect()
+	}
+
 	/// Return all votes for the round (prevotes and precommits), sorted by
 	/// imported order and indicating the indices where we voted. At most two
 	/// prevotes and two precommits per voter are present, further equivocations
@@ -700,6 +1042,97 @@ mod tests {
 	#[derive(PartialEq, Eq, Clone, Debug)]
 	struct Signature(&'static str);
 
+	#[test]
+	fn completability_blockers() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E", "F"]);
+
+		let mut round =
+			Round::new(RoundParams { round_number: 1, voters: voters(), base: (GENESIS_HASH, 1) });
+
+		let previous = State::genesis((GENESIS_HASH, 1));
+		assert_eq!(
+			round.completability_blockers(&previous),
+			vec![Blocker::NoPrevoteGhost, Blocker::NoEstimate],
+		);
+
+		// a supermajority prevotes for `E`, but nobody has precommitted.
+		round
+			.import_prevote(&chain, Prevote::new("E", 6), "Alice", Signature("Alice"))
+			.unwrap();
+		round
+			.import_prevote(&chain, Prevote::new("E", 6), "Bob", Signature("Bob"))
+			.unwrap();
+		assert_eq!(round.completability_blockers(&previous), vec![Blocker::InsufficientPrecommits]);
+
+		// the previous round's estimate is yet to be finalized.
+		let previous = State {
+			round: 0,
+			prevote_ghost: Some(("F", 7)),
+			finalized: Some(("C", 4)),
+			estimate: Some(("F", 7)),
+			completable: true,
+		};
+		assert_eq!(
+			round.completability_blockers(&previous),
+			vec![Blocker::InsufficientPrecommits, Blocker::PreviousEstimateNotFinalized],
+		);
+
+		// `E` is finalized and the round is completable, which isn't enough to
+		// finalize the previous round's estimate.
+		round
+			.import_precommit(&chain, Precommit::new("E", 6), "Alice", Signature("Alice"))
+			.unwrap();
+		round
+			.import_precommit(&chain, Precommit::new("E", 6), "Bob", Signature("Bob"))
+			.unwrap();
+		assert!(round.completable());
+		assert_eq!(
+			round.completability_blockers(&previous),
+			vec![Blocker::PreviousEstimateNotFinalized],
+		);
+
+		let previous = State { estimate: Some(("D", 5)), ..previous };
+		assert!(round.completability_blockers(&previous).is_empty());
+	}
+
+	#[test]
+	fn import_result_notes_finalized_change() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+
+		let mut round =
+			Round::new(RoundParams { round_number: 1, voters: voters(), base: (GENESIS_HASH, 1) });
+
+		for id in ["Alice", "Bob", "Eve"] {
+			let result =
+				round.import_prevote(&chain, Prevote::new("E", 6), id, Signature(id)).unwrap();
+			assert_eq!(result.finalized_changed, None);
+		}
+
+		// Alice and Eve together don't reach the threshold.
+		let result = round
+			.import_precommit(&chain, Precommit::new("D", 5), "Eve", Signature("Eve"))
+			.unwrap();
+		assert_eq!(result.finalized_changed, None);
+
+		let result = round
+			.import_precommit(&chain, Precommit::new("E", 6), "Alice", Signature("Alice"))
+			.unwrap();
+		assert_eq!(result.finalized_changed, None);
+
+		let result = round
+			.import_precommit(&chain, Precommit::new("E", 6), "Bob", Signature("Bob"))
+			.unwrap();
+		assert_eq!(result.finalized_changed, Some(("E", 6)));
+
+		// importing it again doesn't change anything.
+		let result = round
+			.import_precommit(&chain, Precommit::new("E", 6), "Bob", Signature("Bob"))
+			.unwrap();
+		assert_eq!(result.finalized_changed, None);
+	}
+
 	#[test]
 	fn estimate_is_valid() {
 		let mut chain = DummyChain::new();
@@ -772,6 +1205,59 @@ mod tests {
 			.unwrap();
 
 		assert_eq!(round.finalized, Some(("EA", 7)));
+
+		let state = round.state();
+		assert_eq!(round.prevote_ghost(), state.prevote_ghost.as_ref());
+		assert_eq!(round.estimate(), state.estimate.as_ref());
+		assert_eq!(round.finalized(), state.finalized.as_ref());
+		assert_eq!(round.completable(), state.completable);
+	}
+
+	#[test]
+	fn precommit_equivocations_are_counted_on_every_block_for_estimate() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E", "F"]);
+		chain.push_blocks("E", &["EA", "EB", "EC", "ED"]);
+		chain.push_blocks("F", &["FA", "FB", "FC"]);
+
+		let round_with_precommits = |precommits: &[(&'static str, u32, &'static str)]| {
+			let mut round =
+				Round::new(RoundParams { round_number: 1, voters: voters(), base: ("C", 4) });
+
+			for id in ["Alice", "Bob", "Eve"] {
+				round.import_prevote(&chain, Prevote::new("FC", 10), id, Signature(id)).unwrap();
+			}
+
+			for &(hash, number, id) in precommits {
+				round
+					.import_precommit(&chain, Precommit::new(hash, number), id, Signature(id))
+					.unwrap();
+			}
+
+			round
+		};
+
+		// Alice could still precommit for `FC`, together with a possible
+		// equivocation it could be finalized.
+		let round = round_with_precommits(&[("E", 6, "Bob"), ("FC", 10, "Eve")]);
+		assert_eq!(round.estimate(), Some(&("FC", 10)));
+
+		// Eve's weight counts towards both `FC` and `ED`, but it can't equivocate
+		// any further. the remaining weight of Alice isn't enough to finalize
+		// anything above `E` anymore.
+		let round = round_with_precommits(&[("E", 6, "Bob"), ("FC", 10, "Eve"), ("ED", 10, "Eve")]);
+		assert_eq!(round.estimate(), Some(&("E", 6)));
+		assert!(round.completable());
+
+		// with a supermajority on `FC` the equivocation doesn't matter.
+		let round = round_with_precommits(&[
+			("FC", 10, "Alice"),
+			("FC", 10, "Bob"),
+			("FC", 10, "Eve"),
+			("ED", 10, "Eve"),
+		]);
+		assert_eq!(round.estimate(), Some(&("FC", 10)));
+		assert_eq!(round.finalized(), Some(&("FC", 10)));
 	}
 
 	#[test]
@@ -865,7 +1351,7 @@ mod tests {
 			.unwrap();
 
 		round
-			.import_prevote(&chain, Prevote::new("EC", 10), "Alice", Signature("Alice"))
+			.import_prevote(&chain, Prevote::new("EC", 9), "Alice", Signature("Alice"))
 			.unwrap();
 
 		round.set_precommitted_index();
@@ -893,7 +1379,7 @@ mod tests {
 						id: "Eve"
 					},
 					SignedMessage {
-						message: Message::Prevote(Prevote { target_hash: "EC", target_number: 10 }),
+						message: Message::Prevote(Prevote { target_hash: "EC", target_number: 9 }),
 						signature: Signature("Alice"),
 						id: "Alice"
 					},
@@ -903,4 +1389,432 @@ mod tests {
 			)
 		);
 	}
+
+	#[test]
+	fn historical_votes_note_prevote_ghost_and_finalization() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B"]);
+
+		let mut round =
+			Round::new(RoundParams { round_number: 1, voters: voters(), base: (GENESIS_HASH, 1) });
+
+		round
+			.import_prevote(&chain, Prevote::new("B", 3), "Alice", Signature("Alice"))
+			.unwrap();
+		round
+			.import_precommit(&chain, Precommit::new("B", 3), "Alice", Signature("Alice"))
+			.unwrap();
+		round
+			.import_prevote(&chain, Prevote::new("B", 3), "Bob", Signature("Bob"))
+			.unwrap();
+
+		// `Alice` and `Bob` have supermajority prevotes.
+		assert_eq!(round.historical_votes().prevote_ghost_idx(), Some(3));
+		assert_eq!(round.historical_votes().finalized_idx(), None);
+
+		round
+			.import_precommit(&chain, Precommit::new("B", 3), "Bob", Signature("Bob"))
+			.unwrap();
+		round
+			.import_prevote(&chain, Prevote::new("B", 3), "Eve", Signature("Eve"))
+			.unwrap();
+		round
+			.import_precommit(&chain, Precommit::new("A", 2), "Eve", Signature("Eve"))
+			.unwrap();
+
+		// the markers aren't moved by later votes.
+		assert_eq!(round.historical_votes().prevote_ghost_idx(), Some(3));
+		assert_eq!(round.historical_votes().finalized_idx(), Some(4));
+		assert_eq!(round.historical_votes().seen().len(), 6);
+	}
+
+	#[test]
+	fn reports_all_equivocations() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E", "F"]);
+		chain.push_blocks("E", &["EA", "EB", "EC", "ED"]);
+		chain.push_blocks("F", &["FA", "FB", "FC"]);
+
+		let mut round =
+			Round::new(RoundParams { round_number: 1, voters: voters(), base: ("C", 4) });
+
+		round
+			.import_prevote(&chain, Prevote::new("FC", 10), "Eve", Signature("Eve-1"))
+			.unwrap();
+		round
+			.import_prevote(&chain, Prevote::new("ED", 10), "Eve", Signature("Eve-2"))
+			.unwrap();
+		// further equivocations are not reported.
+		round
+			.import_prevote(&chain, Prevote::new("EA", 7), "Eve", Signature("Eve-3"))
+			.unwrap();
+
+		round
+			.import_prevote(&chain, Prevote::new("EA", 7), "Alice", Signature("Alice-1"))
+			.unwrap();
+		round
+			.import_prevote(&chain, Prevote::new("FA", 8), "Alice", Signature("Alice-2"))
+			.unwrap();
+
+		// an honest voter.
+		round
+			.import_prevote(&chain, Prevote::new("FC", 10), "Bob", Signature("Bob"))
+			.unwrap();
+
+		round
+			.import_precommit(&chain, Precommit::new("E", 6), "Bob", Signature("Bob-1"))
+			.unwrap();
+		round
+			.import_precommit(&chain, Precommit::new("F", 7), "Bob", Signature("Bob-2"))
+			.unwrap();
+
+		let equivocation =
+			|identity, first: Message<_, _>, s1, second: Message<_, _>, s2| Equivocation {
+				round_number: 1,
+				identity,
+				first: (first, Signature(s1)),
+				second: (second, Signature(s2)),
+			};
+
+		assert_eq!(
+			round.equivocations(),
+			vec![
+				equivocation(
+					"Alice",
+					Message::Prevote(Prevote::new("EA", 7)),
+					"Alice-1",
+					Message::Prevote(Prevote::new("FA", 8)),
+					"Alice-2",
+				),
+				equivocation(
+					"Eve",
+					Message::Prevote(Prevote::new("FC", 10)),
+					"Eve-1",
+					Message::Prevote(Prevote::new("ED", 10)),
+					"Eve-2",
+				),
+				equivocation(
+					"Bob",
+					Message::Precommit(Precommit::new("E", 6)),
+					"Bob-1",
+					Message::Precommit(Precommit::new("F", 7)),
+					"Bob-2",
+				),
+			],
+		);
+	}
+
+	#[test]
+	fn prevote_ghost_is_independent_of_import_order() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B"]);
+		chain.push_blocks("B", &["C1", "D1"]);
+		chain.push_blocks("B", &["C2", "D2"]);
+
+		// `Bob` and `Eve` equivocate, giving both forks supermajority prevotes.
+		let prevotes = [
+			(Prevote::new("D1", 5), "Bob"),
+			(Prevote::new("D1", 5), "Eve"),
+			(Prevote::new("D2", 5), "Bob"),
+			(Prevote::new("D2", 5), "Eve"),
+			(Prevote::new("B", 3), "Alice"),
+		];
+
+		let ghost = |order: &[usize]| {
+			let mut round = Round::new(RoundParams {
+				round_number: 1,
+				voters: voters(),
+				base: (GENESIS_HASH, 1),
+			});
+
+			for &i in order {
+				let (ref prevote, id) = prevotes[i];
+				round.import_prevote(&chain, prevote.clone(), id, Signature(id)).unwrap();
+			}
+
+			round.state().prevote_ghost
+		};
+
+		// the tie between the forks is broken by the lowest hash.
+		let expected = ghost(&[0, 1, 2, 3, 4]);
+		assert_eq!(expected, Some(("D1", 5)));
+		assert_eq!(ghost(&[2, 3, 0, 1, 4]), expected);
+		assert_eq!(ghost(&[4, 3, 2, 1, 0]), expected);
+		assert_eq!(ghost(&[2, 0, 4, 1, 3]), expected);
+	}
+
+	#[test]
+	fn commits_are_minimal_and_independent_of_import_order() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C"]);
+
+		// five out of seven precommits are needed to finalize `C`.
+		let voters = VoterSet::new((1..=7).map(|id| (id, 1))).expect("nonempty");
+		let precommits = [
+			(Precommit::new("C", 4), 4),
+			(Precommit::new("A", 2), 7),
+			(Precommit::new("C", 4), 2),
+			(Precommit::new("C", 4), 6),
+			(Precommit::new("C", 4), 1),
+			(Precommit::new("C", 4), 5),
+			(Precommit::new("C", 4), 3),
+		];
+
+		let commit = |order: &[usize]| {
+			let mut round = Round::new(RoundParams {
+				round_number: 1,
+				voters: voters.clone(),
+				base: (GENESIS_HASH, 1),
+			});
+
+			for id in 1..=7 {
+				round.import_prevote(&chain, Prevote::new("C", 4), id, id).unwrap();
+			}
+
+			for &i in order {
+				let (ref precommit, id) = precommits[i];
+				round.import_precommit(&chain, precommit.clone(), id, id).unwrap();
+			}
+
+			round.commit(&chain).unwrap()
+		};
+
+		let expected = commit(&[0, 1, 2, 3, 4, 5, 6]);
+		assert_eq!(expected.target_hash, "C");
+		assert_eq!(expected.precommits.iter().map(|p| p.id).collect::<Vec<_>>(), [1, 2, 3, 4, 5]);
+		assert!(crate::validate_commit(&expected, &voters, &chain).unwrap().is_valid());
+
+		assert_eq!(commit(&[6, 5, 4, 3, 2, 1, 0]), expected);
+		assert_eq!(commit(&[3, 1, 6, 0, 5, 2, 4]), expected);
+	}
+
+	#[test]
+	fn finalization_in_small_sets() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A"]);
+
+		// with one voter offline, only the set of four still finalizes.
+		for (n, finalizes_without_one) in [(1, false), (2, false), (3, false), (4, true)] {
+			let round = |voting: u32| {
+				let voters = VoterSet::new((0..n).map(|id| (id, 1))).expect("nonempty");
+				let mut round =
+					Round::new(RoundParams { round_number: 1, voters, base: (GENESIS_HASH, 1) });
+
+				for id in 0..voting {
+					round.import_prevote(&chain, Prevote::new("A", 2), id, id).unwrap();
+					round.import_precommit(&chain, Precommit::new("A", 2), id, id).unwrap();
+				}
+
+				round.state().finalized
+			};
+
+			assert_eq!(round(n), Some(("A", 2)), "{} voters all voting", n);
+			assert_eq!(
+				round(n - 1) == Some(("A", 2)),
+				finalizes_without_one,
+				"{} voters with one offline",
+				n,
+			);
+		}
+	}
+
+	#[test]
+	fn finalization_respects_floor() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+		chain.push_blocks("B", &["F"]);
+
+		let mut round =
+			Round::new(RoundParams { round_number: 1, voters: voters(), base: ("C", 4) });
+
+		// the floor must not be below the base.
+		assert_eq!(round.set_finalized_floor(&chain, "B", 3), Err(crate::Error::NotDescendent));
+		assert_eq!(round.set_finalized_floor(&chain, "F", 4), Err(crate::Error::NotDescendent));
+
+		round.set_finalized_floor(&chain, "E", 6).unwrap();
+		assert_eq!(round.finalized_floor(), Some(&("E", 6)));
+
+		// supermajority precommits finalize `D`, which is below the floor.
+		round.import_prevote(&chain, Prevote::new("E", 6), "Alice", "Alice").unwrap();
+		round.import_prevote(&chain, Prevote::new("E", 6), "Bob", "Bob").unwrap();
+		round.import_prevote(&chain, Prevote::new("E", 6), "Eve", "Eve").unwrap();
+		round
+			.import_precommit(&chain, Precommit::new("D", 5), "Alice", "Alice")
+			.unwrap();
+		round.import_precommit(&chain, Precommit::new("D", 5), "Bob", "Bob").unwrap();
+		round.import_precommit(&chain, Precommit::new("E", 6), "Eve", "Eve").unwrap();
+
+		assert_eq!(round.finalized(), None);
+
+		// without the floor the votes finalize `D`.
+		let mut unbounded =
+			Round::new(RoundParams { round_number: 1, voters: voters(), base: ("C", 4) });
+		for (id, target) in [("Alice", ("D", 5)), ("Bob", ("D", 5)), ("Eve", ("E", 6))] {
+			unbounded.import_prevote(&chain, Prevote::new("E", 6), id, id).unwrap();
+			unbounded
+				.import_precommit(&chain, Precommit::new(target.0, target.1), id, id)
+				.unwrap();
+		}
+		assert_eq!(unbounded.finalized(), Some(&("D", 5)));
+	}
+
+	#[test]
+	fn pruning_keeps_round_state() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+
+		let mut round =
+			Round::new(RoundParams { round_number: 1, voters: voters(), base: (GENESIS_HASH, 1) });
+
+		for id in ["Alice", "Bob", "Eve"] {
+			round.import_prevote(&chain, Prevote::new("E", 6), id, id).unwrap();
+		}
+		round
+			.import_precommit(&chain, Precommit::new("C", 4), "Alice", "Alice")
+			.unwrap();
+		round.import_precommit(&chain, Precommit::new("D", 5), "Bob", "Bob").unwrap();
+
+		let state = round.state();
+		assert_eq!(state.finalized, Some(("C", 4)));
+		assert_eq!(state.estimate, Some(("D", 5)));
+
+		// the round only finalized `C`, so it isn't pruned to `D`.
+		assert_eq!(round.prune(&chain, "D", 5), Ok(false));
+		assert_eq!(round.finalized_floor(), None);
+
+		assert_eq!(round.prune(&chain, "C", 4), Ok(true));
+		assert_eq!(round.state(), state);
+		assert_eq!(round.base(), (GENESIS_HASH, 1));
+		assert_eq!(round.finalized_floor(), Some(&("C", 4)));
+
+		// votes below the pruned blocks can't be imported anymore.
+		assert_eq!(
+			round.import_precommit(&chain, Precommit::new("B", 3), "Eve", "Eve").err(),
+			Some(crate::Error::NotDescendent),
+		);
+		assert_eq!(round.state(), state);
+
+		round.import_precommit(&chain, Precommit::new("E", 6), "Eve", "Eve").unwrap();
+		assert_eq!(round.finalized(), Some(&("D", 5)));
+	}
+
+	#[test]
+	fn votes_with_invalid_targets_are_not_imported() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+
+		let mut round =
+			Round::new(RoundParams { round_number: 1, voters: voters(), base: (GENESIS_HASH, 1) });
+
+		// `E` is block 6, so its ancestry doesn't reach the base.
+		assert!(matches!(
+			round.import_prevote(&chain, Prevote::new("E", 9), "Alice", Signature("Alice")),
+			Err(crate::Error::InvalidAncestry),
+		));
+		assert!(round.prevotes().is_empty());
+		assert_eq!(round.prevote.current_weight, VoteWeight(0));
+
+		// the voter may still cast a valid prevote, which isn't an equivocation.
+		let result = round
+			.import_prevote(&chain, Prevote::new("E", 6), "Alice", Signature("Alice"))
+			.unwrap();
+		assert!(result.equivocation.is_none());
+
+		round
+			.import_prevote(&chain, Prevote::new("E", 6), "Bob", Signature("Bob"))
+			.unwrap();
+		assert_eq!(round.prevote.current_weight, VoteWeight(11));
+		assert_eq!(round.state().prevote_ghost, Some(("E", 6)));
+
+		// an invalid second vote isn't an equivocation either.
+		assert!(round
+			.import_prevote(&chain, Prevote::new("D", 7), "Bob", Signature("Bob-2"))
+			.is_err());
+		assert_eq!(round.prevotes().len(), 2);
+		assert_eq!(round.context.equivocation_weight(Phase::Prevote), VoteWeight(0));
+
+		let result = round
+			.import_prevote(&chain, Prevote::new("D", 5), "Bob", Signature("Bob-2"))
+			.unwrap();
+		assert_eq!(result.equivocation.map(|e| e.first.0), Some(Prevote::new("E", 6)));
+
+		assert!(round
+			.import_precommit(&chain, Precommit::new("C", 2), "Eve", Signature("Eve"))
+			.is_err());
+		assert!(round.precommits().is_empty());
+
+		let result = round
+			.import_precommit(&chain, Precommit::new("C", 4), "Eve", Signature("Eve"))
+			.unwrap();
+		assert!(!result.duplicated);
+		assert_eq!(round.precommit.current_weight, VoteWeight(3));
+	}
+
+	// a signature which counts how often it has been cloned.
+	#[derive(Debug)]
+	struct CountingSignature(&'static str, std::rc::Rc<std::cell::Cell<usize>>);
+
+	impl Clone for CountingSignature {
+		fn clone(&self) -> Self {
+			self.1.set(self.1.get() + 1);
+			CountingSignature(self.0, self.1.clone())
+		}
+	}
+
+	impl PartialEq for CountingSignature {
+		fn eq(&self, other: &Self) -> bool {
+			self.0 == other.0
+		}
+	}
+
+	impl Eq for CountingSignature {}
+
+	#[test]
+	fn signatures_are_cloned_once_on_import() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+
+		let clones = std::rc::Rc::new(std::cell::Cell::new(0));
+		let signature = |s| CountingSignature(s, clones.clone());
+
+		let mut round =
+			Round::new(RoundParams { round_number: 1, voters: voters(), base: (GENESIS_HASH, 1) });
+
+		// the vote is kept by the vote tracker and in the historical votes.
+		round
+			.import_prevote(&chain, Prevote::new("E", 6), "Alice", signature("Alice"))
+			.unwrap();
+		assert_eq!(clones.get(), 1);
+
+		round
+			.import_precommit(&chain, Precommit::new("E", 6), "Alice", signature("Alice"))
+			.unwrap();
+		assert_eq!(clones.get(), 2);
+
+		// duplicates and votes from unknown voters aren't kept at all.
+		round
+			.import_prevote(&chain, Prevote::new("E", 6), "Alice", signature("Alice"))
+			.unwrap();
+		round
+			.import_prevote(&chain, Prevote::new("E", 6), "Mallory", signature("Mallory"))
+			.unwrap();
+		assert_eq!(clones.get(), 2);
+
+		// and commits are validated without cloning any signatures.
+		let commit = crate::Commit {
+			target_hash: "E",
+			target_number: 6,
+			precommits: ["Alice", "Bob", "Eve"]
+				.iter()
+				.map(|&id| crate::SignedPrecommit {
+					precommit: Precommit::new("E", 6),
+					signature: signature(id),
+					id,
+				})
+				.collect(),
+		};
+
+		assert!(crate::validate_commit(&commit, &voters(), &chain).unwrap().is_valid());
+		assert_eq!(clones.get(), 2);
+	}
 }
@@ -17,7 +17,8 @@
 
 use crate::{
 	bitfield::{Bit1, Bitfield},
-	std::ops::AddAssign,
+	std::{collections::BTreeSet, ops::AddAssign},
+	vote_graph::Contributors,
 	voter_set::{VoterInfo, VoterSet},
 	weights::VoteWeight,
 };
@@ -128,6 +129,18 @@ impl AddAssign<&VoteNode> for VoteNode {
 	}
 }
 
+impl Contributors for VoteNode {
+	fn distinct_voters(&self) -> usize {
+		// each voter has one bit per phase.
+		self.bits
+			.iter1s_even()
+			.chain(self.bits.iter1s_odd())
+			.map(|bit| bit.position / 2)
+			.collect::<BTreeSet<_>>()
+			.len()
+	}
+}
+
 impl AddAssign<&Vote> for VoteNode {
 	fn add_assign(&mut self, rhs: &Vote) {
 		self.bits.set_bit(rhs.bit.position);
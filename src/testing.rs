@@ -32,6 +32,7 @@ pub mod chain {
 		inner: BTreeMap<&'static str, BlockRecord>,
 		leaves: Vec<&'static str>,
 		finalized: (&'static str, u32),
+		break_ties_by_arrival: bool,
 	}
 
 	impl DummyChain {
@@ -39,7 +40,18 @@ pub mod chain {
 			let mut inner = BTreeMap::new();
 			inner.insert(GENESIS_HASH, BlockRecord { number: 1, parent: NULL_HASH });
 
-			DummyChain { inner, leaves: vec![GENESIS_HASH], finalized: (GENESIS_HASH, 1) }
+			DummyChain {
+				inner,
+				leaves: vec![GENESIS_HASH],
+				finalized: (GENESIS_HASH, 1),
+				break_ties_by_arrival: false,
+			}
+		}
+
+		/// Break ties between leaves at the same height in favour of the leaf
+		/// pushed last, i.e. nondeterministically across nodes.
+		pub fn break_ties_by_arrival(&mut self) {
+			self.break_ties_by_arrival = true;
 		}
 
 		pub fn push_blocks(&mut self, mut parent: &'static str, blocks: &[&'static str]) {
@@ -73,38 +85,48 @@ pub mod chain {
 			self.leaves.insert(insertion_index, new_leaf);
 		}
 
+		pub fn is_known(&self, hash: &'static str) -> bool {
+			self.inner.contains_key(hash)
+		}
+
 		pub fn number(&self, hash: &'static str) -> u32 {
 			self.inner.get(hash).unwrap().number
 		}
 
 		pub fn last_finalized(&self) -> (&'static str, u32) {
-			self.finalized.clone()
+			self.finalized
 		}
 
 		pub fn set_last_finalized(&mut self, last_finalized: (&'static str, u32)) {
 			self.finalized = last_finalized;
 		}
 
+		/// Get the highest leaf containing the given block, breaking ties between
+		/// leaves at the same height by the lowest hash unless
+		/// `break_ties_by_arrival` is set.
 		pub fn best_chain_containing(&self, base: &'static str) -> Option<(&'static str, u32)> {
 			let base_number = self.inner.get(base)?.number;
 
+			let mut best: Option<(&'static str, u32)> = None;
 			for leaf in &self.leaves {
 				// leaves are in descending order.
 				let leaf_number = self.inner.get(leaf).unwrap().number;
-				if leaf_number < base_number {
+				if leaf_number < base_number || best.is_some_and(|(_, n)| leaf_number < n) {
 					break
 				}
 
-				if leaf == &base {
-					return Some((leaf, leaf_number))
+				if let Some((best_hash, _)) = best {
+					if self.break_ties_by_arrival || best_hash < *leaf {
+						continue
+					}
 				}
 
-				if let Ok(_) = self.ancestry(base, leaf) {
-					return Some((leaf, leaf_number))
+				if leaf == &base || self.ancestry(base, leaf).is_ok() {
+					best = Some((leaf, leaf_number));
 				}
 			}
 
-			None
+			best
 		}
 	}
 
@@ -144,13 +166,20 @@ pub mod environment {
 	use super::chain::*;
 	use crate::{
 		round::State as RoundState,
-		voter::{Callback, CommunicationIn, CommunicationOut, RoundData},
-		Chain, Commit, Equivocation, Error, HistoricalVotes, Message, Precommit, Prevote,
-		PrimaryPropose, SignedMessage,
+		voter::{
+			persistence::{InMemoryPersistence, Persistence},
+			voting_rule::VotingRule,
+			Callback, CommunicationIn, CommunicationOut, RoundData,
+		},
+		voter_set::{PrimarySelector, RoundRobin},
+		Chain, Commit, Equivocation, Error, HistoricalVotes, InvariantLevel, Message, Precommit,
+		Prevote, PrimaryPropose, SignedMessage,
 	};
 	use futures::{
 		channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
+		executor::LocalPool,
 		prelude::*,
+		task::SpawnExt,
 	};
 	use futures_timer::Delay;
 	use parking_lot::Mutex;
@@ -176,6 +205,13 @@ pub mod environment {
 			Vec<UnboundedSender<(&'static str, u32, Commit<&'static str, u32, Signature, Id>)>>,
 		>,
 		last_completed_and_concluded: Mutex<(u64, u64)>,
+		persistence: Option<Arc<InMemoryPersistence<&'static str, u32, Signature, Id, Error>>>,
+		voting_rule: Option<Box<dyn VotingRule<&'static str, u32> + Send + Sync>>,
+		primary_selector: Option<Box<dyn PrimarySelector<Id> + Send + Sync>>,
+		invariant_level: Mutex<InvariantLevel>,
+		equivocations: Mutex<Option<Vec<(u64, Id)>>>,
+		noted_votes: Arc<Mutex<Vec<(u64, Message<&'static str, u32>)>>>,
+		unnoted_votes: Arc<Mutex<Vec<(u64, Message<&'static str, u32>)>>>,
 	}
 
 	impl Environment {
@@ -186,15 +222,76 @@ pub mod environment {
 				network,
 				listeners: Mutex::new(Vec::new()),
 				last_completed_and_concluded: Mutex::new((0, 0)),
+				persistence: None,
+				voting_rule: None,
+				primary_selector: None,
+				invariant_level: Mutex::new(InvariantLevel::default()),
+				equivocations: Mutex::new(None),
+				noted_votes: Arc::new(Mutex::new(Vec::new())),
+				unnoted_votes: Arc::new(Mutex::new(Vec::new())),
 			}
 		}
 
+		/// Persist votes and completed rounds to the given storage.
+		pub fn with_persistence(
+			mut self,
+			persistence: Arc<InMemoryPersistence<&'static str, u32, Signature, Id, Error>>,
+		) -> Self {
+			self.persistence = Some(persistence);
+			self
+		}
+
+		/// Restrict the target of prevotes with the given rule.
+		pub fn with_voting_rule<R>(mut self, voting_rule: R) -> Self
+		where
+			R: VotingRule<&'static str, u32> + Send + Sync + 'static,
+		{
+			self.voting_rule = Some(Box::new(voting_rule));
+			self
+		}
+
+		/// Select the primary voters with the given strategy.
+		pub fn with_primary_selector<S>(mut self, primary_selector: S) -> Self
+		where
+			S: PrimarySelector<Id> + Send + Sync + 'static,
+		{
+			self.primary_selector = Some(Box::new(primary_selector));
+			self
+		}
+
+		/// Check consensus invariants at the given level.
+		pub fn set_invariant_level(&self, level: InvariantLevel) {
+			*self.invariant_level.lock() = level;
+		}
+
+		/// Record equivocations instead of panicking on them, e.g. when voting
+		/// along with byzantine voters.
+		pub fn record_equivocations(&self) {
+			*self.equivocations.lock() = Some(Vec::new());
+		}
+
+		/// The rounds and voters of the recorded equivocations.
+		pub fn equivocations(&self) -> Vec<(u64, Id)> {
+			self.equivocations.lock().clone().unwrap_or_default()
+		}
+
+		/// The votes the voter noted with `proposed`, `prevoted` and
+		/// `precommitted`, in order.
+		pub fn noted_votes(&self) -> Vec<(u64, Message<&'static str, u32>)> {
+			self.noted_votes.lock().clone()
+		}
+
+		/// The votes which were sent before being noted.
+		pub fn unnoted_votes(&self) -> Vec<(u64, Message<&'static str, u32>)> {
+			self.unnoted_votes.lock().clone()
+		}
+
 		pub fn with_chain<F, U>(&self, f: F) -> U
 		where
 			F: FnOnce(&mut DummyChain) -> U,
 		{
 			let mut chain = self.chain.lock();
-			f(&mut *chain)
+			f(&mut chain)
 		}
 
 		/// Stream of finalized blocks.
@@ -208,7 +305,7 @@ pub mod environment {
 
 		/// Get the last completed and concluded rounds.
 		pub fn last_completed_and_concluded(&self) -> (u64, u64) {
-			self.last_completed_and_concluded.lock().clone()
+			*self.last_completed_and_concluded.lock()
 		}
 	}
 
@@ -240,16 +337,38 @@ pub mod environment {
 			Box::new(future::ok(self.chain.lock().best_chain_containing(base)))
 		}
 
+		fn voting_rule(&self) -> Option<&dyn VotingRule<&'static str, u32>> {
+			self.voting_rule.as_deref().map(|r| r as _)
+		}
+
+		fn primary_selector(&self) -> &dyn PrimarySelector<Id> {
+			match self.primary_selector {
+				Some(ref primary_selector) => &**primary_selector,
+				None => &RoundRobin,
+			}
+		}
+
 		fn round_data(&self, round: u64) -> RoundData<Self::Id, Self::Timer, Self::In, Self::Out> {
 			const GOSSIP_DURATION: Duration = Duration::from_millis(500);
 
 			let (incoming, outgoing) = self.network.make_round_comms(round, self.local_id);
+
+			// check the votes being sent have been noted to the environment first.
+			let (noted, unnoted) = (self.noted_votes.clone(), self.unnoted_votes.clone());
+			let outgoing = Box::pin(outgoing.with(move |message: Message<&'static str, u32>| {
+				let vote = (round, message);
+				if !noted.lock().contains(&vote) {
+					unnoted.lock().push(vote.clone());
+				}
+				future::ok(vote.1)
+			}));
+
 			RoundData {
 				voter_id: Some(self.local_id),
 				prevote_timer: Box::new(Delay::new(GOSSIP_DURATION).map(Ok)),
 				precommit_timer: Box::new(Delay::new(GOSSIP_DURATION + GOSSIP_DURATION).map(Ok)),
 				incoming: Box::new(incoming),
-				outgoing: Box::pin(outgoing),
+				outgoing,
 			}
 		}
 
@@ -295,7 +414,7 @@ pub mod environment {
 			let mut chain = self.chain.lock();
 
 			let last_finalized = chain.last_finalized();
-			if number as u32 <= last_finalized.1 {
+			if number <= last_finalized.1 {
 				panic!("Attempted to finalize backwards")
 			}
 
@@ -314,34 +433,62 @@ pub mod environment {
 
 		fn proposed(
 			&self,
-			_round: u64,
-			_propose: PrimaryPropose<&'static str, u32>,
+			round: u64,
+			propose: PrimaryPropose<&'static str, u32>,
 		) -> Result<(), Self::Error> {
+			self.noted_votes.lock().push((round, Message::PrimaryPropose(propose)));
 			Ok(())
 		}
 
 		fn prevoted(
 			&self,
-			_round: u64,
-			_prevote: Prevote<&'static str, u32>,
+			round: u64,
+			prevote: Prevote<&'static str, u32>,
 		) -> Result<(), Self::Error> {
+			self.noted_votes.lock().push((round, Message::Prevote(prevote)));
 			Ok(())
 		}
 
 		fn precommitted(
 			&self,
-			_round: u64,
-			_precommit: Precommit<&'static str, u32>,
+			round: u64,
+			precommit: Precommit<&'static str, u32>,
 		) -> Result<(), Self::Error> {
+			self.noted_votes.lock().push((round, Message::Precommit(precommit)));
 			Ok(())
 		}
 
+		fn persistence(
+			&self,
+		) -> Option<&dyn Persistence<&'static str, u32, Signature, Id, Error = Error>> {
+			self.persistence.as_deref().map(|p| p as _)
+		}
+
+		fn invariant_level(&self) -> InvariantLevel {
+			*self.invariant_level.lock()
+		}
+
+		fn verify_persisted_vote(
+			&self,
+			_round: u64,
+			vote: &SignedMessage<&'static str, u32, Signature, Id>,
+		) -> bool {
+			vote.signature.0 == vote.id.0
+		}
+
+		fn is_block_known(&self, block: &&'static str) -> bool {
+			self.chain.lock().is_known(block)
+		}
+
 		fn prevote_equivocation(
 			&self,
 			round: u64,
 			equivocation: Equivocation<Id, Prevote<&'static str, u32>, Signature>,
 		) {
-			panic!("Encountered equivocation in round {}: {:?}", round, equivocation);
+			match *self.equivocations.lock() {
+				Some(ref mut equivocations) => equivocations.push((round, equivocation.identity)),
+				None => panic!("Encountered equivocation in round {}: {:?}", round, equivocation),
+			}
 		}
 
 		fn precommit_equivocation(
@@ -349,10 +496,234 @@ pub mod environment {
 			round: u64,
 			equivocation: Equivocation<Id, Precommit<&'static str, u32>, Signature>,
 		) {
-			panic!("Encountered equivocation in round {}: {:?}", round, equivocation);
+			match *self.equivocations.lock() {
+				Some(ref mut equivocations) => equivocations.push((round, equivocation.identity)),
+				None => panic!("Encountered equivocation in round {}: {:?}", round, equivocation),
+			}
 		}
 	}
 
+	/// Implements `Chain` and the voter `Environment` for a wrapper around the
+	/// testing `Environment` in its field `$inner`, forwarding the methods the
+	/// testing environment implements and adding the given hooks.
+	///
+	/// Wrappers overriding one of the forwarded methods implement the traits
+	/// themselves, forwarding the remaining methods with
+	/// `wrap_environment!(@forward $inner: ...)`, or all of the `Environment`
+	/// ones with `wrap_environment!(@environment $inner)`.
+	macro_rules! wrap_environment {
+		($wrapper:ty, $inner:tt, { $($hook:tt)* }) => {
+			impl $crate::Chain<&'static str, u32> for $wrapper {
+				$crate::testing::environment::wrap_environment!(@forward $inner: ancestry);
+			}
+
+			impl $crate::voter::Environment<&'static str, u32> for $wrapper {
+				$crate::testing::environment::wrap_environment!(@environment $inner);
+
+				$($hook)*
+			}
+		};
+		(@environment $inner:tt) => {
+			$crate::testing::environment::wrap_environment!(@forward $inner:
+				types,
+				best_chain_containing,
+				voting_rule,
+				primary_selector,
+				round_data,
+				round_commit_timer,
+				completed,
+				concluded,
+				finalize_block,
+				proposed,
+				prevoted,
+				precommitted,
+				persistence,
+				invariant_level,
+				verify_persisted_vote,
+				is_block_known,
+				prevote_equivocation,
+				precommit_equivocation,
+			);
+		};
+		(@forward $inner:tt: $($method:ident),* $(,)?) => {
+			$($crate::testing::environment::wrap_environment!(@$method $inner);)*
+		};
+		(@ancestry $inner:tt) => {
+			fn ancestry(
+				&self,
+				base: &'static str,
+				block: &'static str,
+			) -> Result<Vec<&'static str>, $crate::Error> {
+				$crate::Chain::ancestry(&self.$inner, base, block)
+			}
+		};
+		(@types $inner:tt) => {
+			type Timer = <$crate::testing::environment::Environment as
+				$crate::voter::Environment<&'static str, u32>>::Timer;
+			type BestChain = <$crate::testing::environment::Environment as
+				$crate::voter::Environment<&'static str, u32>>::BestChain;
+			type Id = $crate::testing::environment::Id;
+			type Signature = $crate::testing::environment::Signature;
+			type In = <$crate::testing::environment::Environment as
+				$crate::voter::Environment<&'static str, u32>>::In;
+			type Out = <$crate::testing::environment::Environment as
+				$crate::voter::Environment<&'static str, u32>>::Out;
+			type Error = $crate::Error;
+		};
+		(@best_chain_containing $inner:tt) => {
+			fn best_chain_containing(&self, base: &'static str) -> Self::BestChain {
+				$crate::voter::Environment::best_chain_containing(&self.$inner, base)
+			}
+		};
+		(@voting_rule $inner:tt) => {
+			fn voting_rule(
+				&self,
+			) -> Option<&dyn $crate::voter::voting_rule::VotingRule<&'static str, u32>> {
+				$crate::voter::Environment::voting_rule(&self.$inner)
+			}
+		};
+		(@primary_selector $inner:tt) => {
+			fn primary_selector(&self) -> &dyn $crate::voter_set::PrimarySelector<Self::Id> {
+				$crate::voter::Environment::primary_selector(&self.$inner)
+			}
+		};
+		(@round_data $inner:tt) => {
+			fn round_data(
+				&self,
+				round: u64,
+			) -> $crate::voter::RoundData<Self::Id, Self::Timer, Self::In, Self::Out> {
+				$crate::voter::Environment::round_data(&self.$inner, round)
+			}
+		};
+		(@round_commit_timer $inner:tt) => {
+			fn round_commit_timer(&self) -> Self::Timer {
+				$crate::voter::Environment::round_commit_timer(&self.$inner)
+			}
+		};
+		(@completed $inner:tt) => {
+			fn completed(
+				&self,
+				round: u64,
+				state: $crate::round::State<&'static str, u32>,
+				base: (&'static str, u32),
+				votes: &$crate::HistoricalVotes<&'static str, u32, Self::Signature, Self::Id>,
+			) -> Result<(), Self::Error> {
+				$crate::voter::Environment::completed(&self.$inner, round, state, base, votes)
+			}
+		};
+		(@concluded $inner:tt) => {
+			fn concluded(
+				&self,
+				round: u64,
+				state: $crate::round::State<&'static str, u32>,
+				base: (&'static str, u32),
+				votes: &$crate::HistoricalVotes<&'static str, u32, Self::Signature, Self::Id>,
+			) -> Result<(), Self::Error> {
+				$crate::voter::Environment::concluded(&self.$inner, round, state, base, votes)
+			}
+		};
+		(@finalize_block $inner:tt) => {
+			fn finalize_block(
+				&self,
+				hash: &'static str,
+				number: u32,
+				round: u64,
+				commit: $crate::Commit<&'static str, u32, Self::Signature, Self::Id>,
+			) -> Result<(), Self::Error> {
+				$crate::voter::Environment::finalize_block(&self.$inner, hash, number, round, commit)
+			}
+		};
+		(@proposed $inner:tt) => {
+			fn proposed(
+				&self,
+				round: u64,
+				propose: $crate::PrimaryPropose<&'static str, u32>,
+			) -> Result<(), Self::Error> {
+				$crate::voter::Environment::proposed(&self.$inner, round, propose)
+			}
+		};
+		(@prevoted $inner:tt) => {
+			fn prevoted(
+				&self,
+				round: u64,
+				prevote: $crate::Prevote<&'static str, u32>,
+			) -> Result<(), Self::Error> {
+				$crate::voter::Environment::prevoted(&self.$inner, round, prevote)
+			}
+		};
+		(@precommitted $inner:tt) => {
+			fn precommitted(
+				&self,
+				round: u64,
+				precommit: $crate::Precommit<&'static str, u32>,
+			) -> Result<(), Self::Error> {
+				$crate::voter::Environment::precommitted(&self.$inner, round, precommit)
+			}
+		};
+		(@persistence $inner:tt) => {
+			fn persistence(
+				&self,
+			) -> Option<
+				&dyn $crate::voter::persistence::Persistence<
+					&'static str,
+					u32,
+					Self::Signature,
+					Self::Id,
+					Error = Self::Error,
+				>,
+			> {
+				$crate::voter::Environment::persistence(&self.$inner)
+			}
+		};
+		(@invariant_level $inner:tt) => {
+			fn invariant_level(&self) -> $crate::InvariantLevel {
+				$crate::voter::Environment::invariant_level(&self.$inner)
+			}
+		};
+		(@verify_persisted_vote $inner:tt) => {
+			fn verify_persisted_vote(
+				&self,
+				round: u64,
+				vote: &$crate::SignedMessage<&'static str, u32, Self::Signature, Self::Id>,
+			) -> bool {
+				$crate::voter::Environment::verify_persisted_vote(&self.$inner, round, vote)
+			}
+		};
+		(@is_block_known $inner:tt) => {
+			fn is_block_known(&self, block: &&'static str) -> bool {
+				$crate::voter::Environment::is_block_known(&self.$inner, block)
+			}
+		};
+		(@prevote_equivocation $inner:tt) => {
+			fn prevote_equivocation(
+				&self,
+				round: u64,
+				equivocation: $crate::Equivocation<
+					Self::Id,
+					$crate::Prevote<&'static str, u32>,
+					Self::Signature,
+				>,
+			) {
+				$crate::voter::Environment::prevote_equivocation(&self.$inner, round, equivocation)
+			}
+		};
+		(@precommit_equivocation $inner:tt) => {
+			fn precommit_equivocation(
+				&self,
+				round: u64,
+				equivocation: $crate::Equivocation<
+					Self::Id,
+					$crate::Precommit<&'static str, u32>,
+					Self::Signature,
+				>,
+			) {
+				$crate::voter::Environment::precommit_equivocation(&self.$inner, round, equivocation)
+			}
+		};
+	}
+
+	pub(crate) use wrap_environment;
+
 	// p2p network data for a round.
 	struct BroadcastNetwork<M> {
 		receiver: UnboundedReceiver<M>,
@@ -427,6 +798,22 @@ pub mod environment {
 		)
 	}
 
+	/// Make the environment of the given voter on a chain with the given blocks
+	/// on top of genesis, along with its network and a pool routing it.
+	pub fn make_environment(
+		local_id: Id,
+		blocks: &[&'static str],
+	) -> (Environment, Network, LocalPool) {
+		let (network, routing_task) = make_network();
+		let env = Environment::new(network.clone(), local_id);
+		env.with_chain(|chain| chain.push_blocks(GENESIS_HASH, blocks));
+
+		let pool = LocalPool::new();
+		pool.spawner().spawn(routing_task).unwrap();
+
+		(env, network, pool)
+	}
+
 	type RoundNetwork = BroadcastNetwork<SignedMessage<&'static str, u32, Signature, Id>>;
 	type GlobalMessageNetwork = BroadcastNetwork<CommunicationIn<&'static str, u32, Signature, Id>>;
 
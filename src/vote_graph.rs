@@ -17,21 +17,43 @@
 //! See docs on `VoteGraph` for more information.
 
 use crate::std::{
+	boxed::Box,
 	collections::{BTreeMap, BTreeSet},
-	fmt::Debug,
-	ops::AddAssign,
+	fmt::{self, Debug, Display, Write},
+	ops::{AddAssign, SubAssign},
 	vec::Vec,
 };
 
 use super::{BlockNumberOps, Chain, Error};
 
+/// A vote accumulator which tracks the set of voters that contributed to it.
+pub trait Contributors {
+	/// The number of distinct voters which contributed to this value.
+	///
+	/// A voter which contributed more than once, e.g. by equivocating or by
+	/// voting in several phases accumulated into the same value, is counted once.
+	fn distinct_voters(&self) -> usize;
+}
+
 #[cfg_attr(any(feature = "std", test), derive(Debug))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+	feature = "serde",
+	serde(bound(
+		deserialize = "H: serde::Deserialize<'de>, N: serde::Deserialize<'de>, V: serde::Deserialize<'de>"
+	))
+)]
 struct Entry<H, N, V> {
 	number: N,
 	// ancestor hashes in reverse order, e.g. ancestors[0] is the parent
-	// and the last entry is the hash of the parent vote-node.
+	// and the last entry is the hash of the parent vote-node. this only spans
+	// the edge to the parent vote-node, so we make sure not to keep any spare
+	// capacity around after splitting it or fetching a longer ancestry.
 	ancestors: Vec<H>,
-	descendents: Vec<H>, // descendent vote-nodes
+	// descendent vote-nodes. these follow from the ancestors of the other
+	// vote-nodes, so they aren't serialized but rebuilt, see `from_entries`.
+	#[cfg_attr(feature = "serde", serde(skip))]
+	descendents: Vec<H>,
 	cumulative_vote: V,
 }
 
@@ -50,7 +72,7 @@ impl<H: Ord + PartialEq + Clone, N: BlockNumberOps, V> Entry<H, N, V> {
 		}
 		let offset = self.number - number - N::one();
 
-		self.ancestors.get(offset.as_())
+		self.ancestors.get(offset.try_into().ok()?)
 	}
 
 	// get ancestor vote-node.
@@ -71,15 +93,132 @@ impl<H: Clone, N: Copy + BlockNumberOps> Subchain<H, N> {
 	}
 }
 
+// a callback noting the growth of the graph, see `VoteGraph::on_growth`.
+struct GrowthCallback {
+	next_milestone: usize,
+	callback: Box<dyn FnMut(usize, usize) + Send>,
+}
+
 /// Maintains a DAG of blocks in the chain which have votes attached to them,
 /// and vote data which is accumulated along edges.
-pub struct VoteGraph<H: Ord + Eq, N, V> {
+///
+/// With the `serde` feature the graph can be serialized, e.g. to snapshot the
+/// votes of a round. Growth callbacks and sparse vote flagging aren't
+/// serialized, and a deserialized graph is checked as in `from_parts`.
+///
+/// Graphs created with `with_voter_ids` remember which voters' votes were
+/// inserted with `insert_once`, identified by `Id`. These aren't serialized.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct VoteGraph<H: Ord + Eq, N, V, Id = ()> {
+	entries: BTreeMap<H, Entry<H, N, V>>,
+	heads: BTreeSet<H>,
+	base: H,
+	base_number: N,
+	#[cfg_attr(feature = "serde", serde(skip))]
+	growth_callback: Option<GrowthCallback>,
+	// ancestor edges longer than this are flagged, see `flag_sparse_votes`.
+	#[cfg_attr(feature = "serde", serde(skip))]
+	sparse_votes_depth: Option<usize>,
+	// the longest edge flagged since the last `take_sparse_votes`.
+	#[cfg_attr(feature = "serde", serde(skip))]
+	sparse_votes: Option<usize>,
+	// the targets and voters of the votes inserted with `insert_once`.
+	#[cfg_attr(feature = "serde", serde(skip))]
+	voted: BTreeSet<(H, Id)>,
+}
+
+// the serialized fields of a `VoteGraph`, which are checked before the graph
+// is constructed from them.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SerializedVoteGraph<H: Ord, N, V> {
 	entries: BTreeMap<H, Entry<H, N, V>>,
 	heads: BTreeSet<H>,
 	base: H,
 	base_number: N,
 }
 
+#[cfg(feature = "serde")]
+impl<'de, H, N, V, Id> serde::Deserialize<'de> for VoteGraph<H, N, V, Id>
+where
+	H: Eq + Clone + Ord + Debug + serde::Deserialize<'de>,
+	V: for<'a> AddAssign<&'a V> + Default + Clone + Debug + serde::Deserialize<'de>,
+	N: Copy + Debug + BlockNumberOps + serde::Deserialize<'de>,
+{
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let SerializedVoteGraph { entries, heads, base, base_number } =
+			SerializedVoteGraph::deserialize(deserializer)?;
+
+		VoteGraph::from_entries(base, base_number, entries, heads)
+			.map_err(|_| serde::de::Error::custom("inconsistent vote-graph"))
+	}
+}
+
+/// A `VoteGraph` rendered in the DOT language, see `VoteGraph::dot`.
+pub struct Dot<'a, H: Ord + Eq, N, V, Id>(&'a VoteGraph<H, N, V, Id>);
+
+impl<H, N, V, Id> Display for Dot<'_, H, N, V, Id>
+where
+	H: Ord + Eq + Display,
+	N: BlockNumberOps + core::fmt::Debug, // the crate's `Debug` is a marker without `std`.
+	V: Display,
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		// vote-nodes are identified by their position, so that hashes only
+		// need to be escaped in labels.
+		let ids = self
+			.0
+			.entries
+			.keys()
+			.enumerate()
+			.map(|(i, h)| (h, i))
+			.collect::<BTreeMap<_, _>>();
+
+		writeln!(f, "digraph {{")?;
+		for (hash, entry) in &self.0.entries {
+			write!(f, "\tn{} [label=\"", ids[hash])?;
+			write!(Escaped(f), "{} #{:?}", hash, entry.number)?;
+			f.write_str("\\n")?;
+			write!(Escaped(f), "{}", entry.cumulative_vote)?;
+			if hash == &self.0.base {
+				writeln!(f, "\", shape=box];")?;
+			} else {
+				writeln!(f, "\"];")?;
+			}
+		}
+
+		for (hash, entry) in &self.0.entries {
+			for descendent in &entry.descendents {
+				let blocks = self.0.entries[descendent].ancestors.len();
+				let plural = if blocks == 1 { "" } else { "s" };
+				writeln!(
+					f,
+					"\tn{} -> n{} [label=\"{} block{}\"];",
+					ids[hash], ids[descendent], blocks, plural,
+				)?;
+			}
+		}
+
+		writeln!(f, "}}")
+	}
+}
+
+// escapes the characters which can't appear as such in quoted DOT strings.
+struct Escaped<'a, 'b>(&'a mut fmt::Formatter<'b>);
+
+impl fmt::Write for Escaped<'_, '_> {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		for c in s.chars() {
+			if c == '"' || c == '\\' {
+				self.0.write_char('\\')?;
+			}
+			self.0.write_char(c)?;
+		}
+
+		Ok(())
+	}
+}
+
 impl<H, N, V> VoteGraph<H, N, V>
 where
 	H: Eq + Clone + Ord + Debug,
@@ -88,6 +227,48 @@ where
 {
 	/// Create a new `VoteGraph` with base node as given.
 	pub fn new(base_hash: H, base_number: N, base_node: V) -> Self {
+		Self::with_voter_ids(base_hash, base_number, base_node)
+	}
+
+	/// Reconstruct a graph from its base and vote-nodes, e.g. as returned by
+	/// `entries` and `heads`. Every vote-node is given by its hash, number,
+	/// ancestry up to and including its parent vote-node, and cumulative vote.
+	///
+	/// Fails with `Error::InvalidVoteGraph` unless the base is the only
+	/// vote-node without ancestry, the ancestry of every other vote-node
+	/// matches its number and ends at another vote-node, and the heads are
+	/// exactly the vote-nodes without descendents. The cumulative votes are
+	/// taken as given.
+	pub fn from_parts<I>(
+		base: H,
+		base_number: N,
+		entries: I,
+		heads: BTreeSet<H>,
+	) -> Result<Self, Error>
+	where
+		I: IntoIterator<Item = (H, N, Vec<H>, V)>,
+	{
+		let mut map = BTreeMap::new();
+		for (hash, number, ancestors, cumulative_vote) in entries {
+			let entry = Entry { number, ancestors, descendents: Vec::new(), cumulative_vote };
+			if map.insert(hash, entry).is_some() {
+				return Err(Error::InvalidVoteGraph)
+			}
+		}
+
+		Self::from_entries(base, base_number, map, heads)
+	}
+}
+
+impl<H, N, V, Id> VoteGraph<H, N, V, Id>
+where
+	H: Eq + Clone + Ord + Debug,
+	V: for<'a> AddAssign<&'a V> + Default + Clone + Debug,
+	N: Copy + Debug + BlockNumberOps,
+{
+	/// Create a new `VoteGraph` with base node as given, which remembers the
+	/// voters of the votes inserted with `insert_once`.
+	pub fn with_voter_ids(base_hash: H, base_number: N, base_node: V) -> Self {
 		let mut entries = BTreeMap::new();
 		entries.insert(
 			base_hash.clone(),
@@ -102,7 +283,151 @@ where
 		let mut heads = BTreeSet::new();
 		heads.insert(base_hash.clone());
 
-		VoteGraph { entries, heads, base: base_hash, base_number }
+		VoteGraph {
+			entries,
+			heads,
+			base: base_hash,
+			base_number,
+			growth_callback: None,
+			sparse_votes_depth: None,
+			sparse_votes: None,
+			voted: BTreeSet::new(),
+		}
+	}
+
+	// check the given vote-nodes, ignoring their descendents, and rebuild the
+	// descendents from their ancestry.
+	fn from_entries(
+		base: H,
+		base_number: N,
+		mut entries: BTreeMap<H, Entry<H, N, V>>,
+		heads: BTreeSet<H>,
+	) -> Result<Self, Error> {
+		match entries.get(&base) {
+			Some(entry) if entry.number == base_number && entry.ancestors.is_empty() => {},
+			_ => return Err(Error::InvalidVoteGraph),
+		}
+
+		let mut edges = Vec::with_capacity(entries.len());
+		for (hash, entry) in &entries {
+			if hash == &base {
+				continue
+			}
+
+			// every other vote-node must have a shorter ancestry ending at a
+			// vote-node, which must be the only vote-node in the ancestry. since
+			// the numbers only decrease along the ancestry, it reaches the base.
+			let (parent, between) = entry.ancestors.split_last().ok_or(Error::InvalidVoteGraph)?;
+			let parent_number =
+				entries.get(parent).map(|p| p.number).ok_or(Error::InvalidVoteGraph)?;
+
+			if parent_number >= entry.number ||
+				(entry.number - parent_number).try_into().ok() != Some(entry.ancestors.len()) ||
+				between.iter().any(|h| entries.contains_key(h))
+			{
+				return Err(Error::InvalidVoteGraph)
+			}
+
+			edges.push((parent.clone(), hash.clone()));
+		}
+
+		for entry in entries.values_mut() {
+			entry.descendents.clear();
+		}
+
+		for (parent, hash) in edges {
+			entries
+				.get_mut(&parent)
+				.expect("parents were checked to be vote-nodes; qed")
+				.descendents
+				.push(hash);
+		}
+
+		let mut graph = VoteGraph {
+			entries,
+			heads: BTreeSet::new(),
+			base,
+			base_number,
+			growth_callback: None,
+			sparse_votes_depth: None,
+			sparse_votes: None,
+			voted: BTreeSet::new(),
+		};
+
+		graph.recompute_heads();
+		if graph.heads != heads {
+			return Err(Error::InvalidVoteGraph)
+		}
+
+		Ok(graph)
+	}
+
+	/// The vote-nodes in the graph, ordered by hash. Every vote-node is given
+	/// by its hash, number, ancestry up to and including its parent vote-node
+	/// in reverse order, and cumulative vote, see `from_parts`.
+	pub fn entries(&self) -> impl Iterator<Item = (&H, N, &[H], &V)> {
+		self.entries
+			.iter()
+			.map(|(hash, entry)| (hash, entry.number, &entry.ancestors[..], &entry.cumulative_vote))
+	}
+
+	/// The vote-nodes without descendents.
+	pub fn heads(&self) -> &BTreeSet<H> {
+		&self.heads
+	}
+
+	/// Render the graph in the DOT language, e.g. to inspect the forks and
+	/// where votes concentrated with Graphviz when finality stalls. Every
+	/// vote-node is labeled with its hash, number and cumulative vote, and every
+	/// edge with the number of blocks it spans.
+	pub fn dot(&self) -> Dot<'_, H, N, V, Id> {
+		Dot(self)
+	}
+
+	/// Register a callback which is called with the number of entries and heads
+	/// in the graph whenever the number of entries reaches a milestone. The first
+	/// milestone is given and each subsequent one doubles the previous one.
+	///
+	/// This replaces any previously registered callback.
+	pub fn on_growth<F>(&mut self, first_milestone: usize, callback: F)
+	where
+		F: FnMut(usize, usize) + Send + 'static,
+	{
+		self.growth_callback = Some(GrowthCallback {
+			next_milestone: first_milestone.max(1),
+			callback: Box::new(callback),
+		});
+		self.note_growth();
+	}
+
+	// call the growth callback if the number of entries reached the next milestone.
+	fn note_growth(&mut self) {
+		let entries = self.entries.len();
+		if let Some(ref mut growth) = self.growth_callback {
+			if entries < growth.next_milestone {
+				return
+			}
+
+			while growth.next_milestone <= entries {
+				growth.next_milestone = growth.next_milestone.saturating_mul(2);
+			}
+
+			(growth.callback)(entries, self.heads.len());
+		}
+	}
+
+	/// Flag the ancestor edges of vote-nodes appended to the graph which are
+	/// longer than the given depth, i.e. when none of the blocks in between
+	/// have been voted on, see `take_sparse_votes`. This is only diagnostic,
+	/// the graph is otherwise unaffected.
+	pub fn flag_sparse_votes(&mut self, depth: usize) {
+		self.sparse_votes_depth = Some(depth);
+	}
+
+	/// Take the length of the longest ancestor edge flagged since the last
+	/// call, if any, see `flag_sparse_votes`.
+	pub fn take_sparse_votes(&mut self) -> Option<usize> {
+		self.sparse_votes.take()
 	}
 
 	/// Get the base block.
@@ -110,6 +435,18 @@ where
 		(self.base.clone(), self.base_number)
 	}
 
+	/// Whether no votes are in the graph, i.e. the base is the only vote-node
+	/// and it carries no vote.
+	pub fn is_empty(&self) -> bool
+	where
+		V: PartialEq,
+	{
+		self.entries.len() == 1 &&
+			self.entries
+				.get(&self.base)
+				.is_some_and(|base| base.cumulative_vote == V::default())
+	}
+
 	/// Adjust the base of the graph. The new base must be an ancestor of the
 	/// old base.
 	///
@@ -121,11 +458,6 @@ where
 			Some(h) => h,
 		};
 
-		// not a valid ancestry proof. TODO: error?
-		if ancestry_proof.len() > self.base_number.as_() {
-			return
-		}
-
 		// hack because we can't convert usize -> N, only vice-versa.
 		// hopefully LLVM can optimize.
 		//
@@ -133,6 +465,11 @@ where
 		let new_number = {
 			let mut new_number = self.base_number;
 			for _ in 0..ancestry_proof.len() {
+				// not a valid ancestry proof. TODO: error?
+				if new_number.is_zero() {
+					return
+				}
+
 				new_number = new_number - N::one();
 			}
 			new_number
@@ -155,6 +492,56 @@ where
 		self.entries.insert(new_hash.clone(), entry);
 		self.base = new_hash.clone();
 		self.base_number = new_number;
+		self.debug_check_heads();
+		self.note_growth();
+	}
+
+	/// Move the base of the graph up to the given block, e.g. once it is
+	/// finalized, dropping the vote-nodes which aren't equal to or descendents
+	/// of it along with their votes. This is the counterpart of `adjust_base`.
+	///
+	/// The cumulative votes of the blocks kept are unchanged, since they only
+	/// include the votes of their descendents. Returns `false` and leaves the
+	/// graph unchanged if the block isn't in the graph, i.e. neither a
+	/// vote-node nor in the ancestry of one.
+	pub fn prune(&mut self, hash: H, number: N) -> bool
+	where
+		Id: Ord,
+	{
+		if hash == self.base {
+			return true
+		}
+
+		if number <= self.base_number {
+			return false
+		}
+
+		if let Some(containing) = self.find_containing_nodes(hash.clone(), number) {
+			if containing.is_empty() {
+				return false
+			}
+
+			self.introduce_branch(containing, hash.clone(), number);
+		}
+
+		let mut kept = BTreeMap::new();
+		let mut pending = vec![hash.clone()];
+		while let Some(node) = pending.pop() {
+			let entry = self.entries.remove(&node).expect("descendents are vote-nodes; qed");
+			pending.extend(entry.descendents.iter().cloned());
+			kept.insert(node, entry);
+		}
+
+		let base = kept.get_mut(&hash).expect("the new base was inserted first; qed");
+		base.ancestors = Vec::new();
+
+		self.entries = kept;
+		self.voted.retain(|(voted_hash, _)| self.entries.contains_key(voted_hash));
+		self.base = hash;
+		self.base_number = number;
+		self.recompute_heads();
+
+		true
 	}
 
 	/// Insert a vote with given value into the graph at given hash and number.
@@ -174,6 +561,9 @@ where
 			} else {
 				self.introduce_branch(containing, hash.clone(), number);
 			}
+
+			self.debug_check_heads();
+			self.note_growth();
 		} else {
 			// this entry already exists
 		}
@@ -198,16 +588,201 @@ where
 		Ok(())
 	}
 
+	/// Merge the votes of another graph with the same base into this one, e.g.
+	/// when the graphs were built from different batches of votes.
+	///
+	/// The cumulative votes of the other graph include the votes of all
+	/// descendents, so each of its vote-nodes is inserted with the vote it adds
+	/// over its descendents only. The voters remembered by `insert_once` are
+	/// merged as well. On error, the votes of the other graph may have been
+	/// merged in part.
+	///
+	/// This function panics if the graphs don't have the same base.
+	pub fn merge<C: Chain<H, N>>(
+		&mut self,
+		other: VoteGraph<H, N, V, Id>,
+		chain: &C,
+	) -> Result<(), Error>
+	where
+		Id: Ord,
+		V: for<'a> SubAssign<&'a V> + PartialEq,
+	{
+		assert!(
+			self.base == other.base && self.base_number == other.base_number,
+			"only graphs with the same base can be merged",
+		);
+
+		for (hash, entry) in &other.entries {
+			let mut vote = entry.cumulative_vote.clone();
+			for descendent in &entry.descendents {
+				vote -= &other.entries[descendent].cumulative_vote;
+			}
+
+			if vote != V::default() {
+				self.insert(hash.clone(), entry.number, vote, chain)?;
+			}
+		}
+
+		self.voted.extend(other.voted);
+		Ok(())
+	}
+
+	/// Remove a vote with given value from the graph at given hash and number.
+	/// The vote must have been inserted at the same block before.
+	///
+	/// Vote-nodes which are left without votes and without descendents are
+	/// pruned from the graph. Returns `false` and leaves the graph unchanged if
+	/// there is no vote-node at the given block.
+	pub fn remove<W>(&mut self, hash: H, number: N, vote: W) -> bool
+	where
+		V: for<'a> SubAssign<&'a W> + PartialEq,
+	{
+		if self.entries.get(&hash).is_none_or(|entry| entry.number != number) {
+			return false
+		}
+
+		let mut inspecting_hash = hash.clone();
+		loop {
+			let active_entry = self
+				.entries
+				.get_mut(&inspecting_hash)
+				.expect("vote-node and its ancestry always exist; qed");
+
+			active_entry.cumulative_vote -= &vote;
+
+			match active_entry.ancestor_node() {
+				Some(parent) => inspecting_hash = parent,
+				None => break,
+			}
+		}
+
+		// walk back from the target while vote-nodes are dead, the base is
+		// always kept.
+		let mut inspecting_hash = hash;
+		while inspecting_hash != self.base {
+			let entry = &self.entries[&inspecting_hash];
+			if !entry.descendents.is_empty() || entry.cumulative_vote != V::default() {
+				break
+			}
+
+			let entry =
+				self.entries.remove(&inspecting_hash).expect("entry was just inspected; qed");
+			let parent_hash =
+				entry.ancestor_node().expect("only the base vote-node has no ancestor; qed");
+			let parent = self
+				.entries
+				.get_mut(&parent_hash)
+				.expect("Prior ancestor is referenced from a node; qed");
+
+			parent.descendents.retain(|h| h != &inspecting_hash);
+			if parent.descendents.is_empty() {
+				self.heads.insert(parent_hash.clone());
+			}
+
+			self.heads.remove(&inspecting_hash);
+			inspecting_hash = parent_hash;
+		}
+
+		self.debug_check_heads();
+		true
+	}
+
+	/// Insert the vote of the given voter, unless a vote of the voter for the
+	/// same block was inserted before, e.g. when a vote is received again.
+	/// Returns whether the vote was inserted.
+	///
+	/// Votes for different blocks are all inserted, so an equivocating voter
+	/// is counted for each of its targets.
+	pub fn insert_once<C: Chain<H, N>, W>(
+		&mut self,
+		id: Id,
+		hash: H,
+		number: N,
+		vote: W,
+		chain: &C,
+	) -> Result<bool, Error>
+	where
+		Id: Ord,
+		V: for<'a> AddAssign<&'a W>,
+	{
+		let key = (hash, id);
+		if self.voted.contains(&key) {
+			return Ok(false)
+		}
+
+		self.insert(key.0.clone(), number, vote, chain)?;
+		self.voted.insert(key);
+		Ok(true)
+	}
+
+	/// Remove the vote of the given voter inserted with `insert_once`, see
+	/// `remove`. Returns `false` and leaves the graph unchanged if there is no
+	/// such vote.
+	pub fn remove_once<W>(&mut self, id: Id, hash: H, number: N, vote: W) -> bool
+	where
+		Id: Ord,
+		V: for<'a> SubAssign<&'a W> + PartialEq,
+	{
+		let key = (hash, id);
+		if !self.voted.contains(&key) || !self.remove(key.0.clone(), number, vote) {
+			return false
+		}
+
+		self.voted.remove(&key);
+		true
+	}
+
+	/// Rebuild the set of heads from scratch, i.e. the vote-nodes without any
+	/// descendents.
+	///
+	/// The heads are kept up to date whenever the graph is changed, so this is
+	/// only useful to check that they haven't drifted.
+	pub fn recompute_heads(&mut self) {
+		self.heads = self.compute_heads();
+	}
+
+	fn compute_heads(&self) -> BTreeSet<H> {
+		self.entries
+			.iter()
+			.filter(|(_, entry)| entry.descendents.is_empty())
+			.map(|(hash, _)| hash.clone())
+			.collect()
+	}
+
+	// check the incrementally updated heads against recomputing them.
+	fn debug_check_heads(&self) {
+		debug_assert!(self.heads == self.compute_heads(), "vote-graph heads drifted");
+	}
+
 	/// Find the block with the highest block number in the chain with the given head
 	/// which fulfills the given condition.
 	///
 	/// Returns `None` if the given head is not in the graph or no node fulfills the
 	/// given condition.
-	pub fn find_ancestor<F>(&self, mut hash: H, mut number: N, condition: F) -> Option<(H, N)>
+	pub fn find_ancestor<F>(&self, hash: H, number: N, condition: F) -> Option<(H, N)>
+	where
+		F: Fn(&V) -> bool,
+	{
+		self.find_ancestor_above(hash, number, self.base_number, condition)
+	}
+
+	/// Like `find_ancestor`, but only considers blocks with a number of at least
+	/// `lowest`, without visiting any of the blocks below it.
+	pub fn find_ancestor_above<F>(
+		&self,
+		mut hash: H,
+		mut number: N,
+		lowest: N,
+		condition: F,
+	) -> Option<(H, N)>
 	where
 		F: Fn(&V) -> bool,
 	{
 		loop {
+			if number < lowest {
+				return None
+			}
+
 			match self.find_containing_nodes(hash.clone(), number) {
 				None => {
 					// The block has a vote-node in the graph.
@@ -218,7 +793,7 @@ where
 						return Some((hash, number))
 					}
 					// Not enough weight, check the parent block.
-					match node.ancestors.get(0) {
+					match node.ancestors.first() {
 						None => return None,
 						Some(a) => {
 							hash = a.clone();
@@ -247,7 +822,7 @@ where
 					// Not enough weight, check the parent block.
 					let child = children.last().expect("children not empty; qed");
 					let entry = self.entries.get(child).expect("all children in graph; qed");
-					let offset = (entry.number - number).as_();
+					let offset = (entry.number - number).try_into().ok()?;
 					match entry.ancestors.get(offset) {
 						None => return None, // Reached base without sufficient weight.
 						Some(parent) => {
@@ -260,8 +835,12 @@ where
 		}
 	}
 
-	/// Find the total vote on a given block.
-	pub fn cumulative_vote<'a>(&'a self, hash: H, number: N) -> V {
+	/// Find the total vote on a given block, i.e. the vote for it and all of its
+	/// descendents.
+	///
+	/// Returns `None` if the block is not known to the graph, that is, it isn't
+	/// a vote-node or in the ancestry of one.
+	pub fn cumulative_vote<'a>(&'a self, hash: &H, number: N) -> Option<V> {
 		let entries = &self.entries;
 		let get_node = |hash: &_| -> &'a _ {
 			entries
@@ -270,18 +849,54 @@ where
 		};
 
 		match self.find_containing_nodes(hash.clone(), number) {
-			None => get_node(&hash).cumulative_vote.clone(),
+			None => Some(get_node(hash).cumulative_vote.clone()),
+			Some(nodes) if nodes.is_empty() => None,
 			Some(nodes) => {
-				let mut v = Default::default();
+				let mut v = V::default();
 				for node in nodes {
 					v += &get_node(&node).cumulative_vote;
 				}
 
-				v
+				Some(v)
 			},
 		}
 	}
 
+	/// Find the number of distinct voters whose votes accumulate at the given
+	/// vote-node, i.e. those who voted for the block or any of its descendents.
+	///
+	/// Returns `None` if the block is not a vote-node in the graph. The count is
+	/// only as precise as the accumulator `V`, see `Contributors`.
+	pub fn distinct_voters_at(&self, hash: &H) -> Option<usize>
+	where
+		V: Contributors,
+	{
+		self.entries.get(hash).map(|entry| entry.cumulative_vote.distinct_voters())
+	}
+
+	/// Find the lowest common ancestor of two blocks in the graph, i.e. the
+	/// block with the highest number which is equal to or an ancestor of both.
+	///
+	/// Returns `None` if either block is not known to the graph, that is, it
+	/// isn't a vote-node or in the ancestry of one.
+	pub fn common_ancestor(&self, a: (H, N), b: (H, N)) -> Option<(H, N)> {
+		let (high, low) = if a.1 >= b.1 { (a, b) } else { (b, a) };
+		let mut number = low.1;
+
+		let high = self.ancestry_iter(high.0, high.1)?.skip((high.1 - low.1).try_into().ok()?);
+		let low = self.ancestry_iter(low.0, low.1)?;
+
+		for (a, b) in high.zip(low) {
+			if a == b {
+				return Some((a, number))
+			}
+
+			number = number - N::one();
+		}
+
+		None
+	}
+
 	/// Find the best GHOST descendent of the given block.
 	/// Pass a closure used to evaluate the cumulative vote value.
 	///
@@ -290,7 +905,9 @@ where
 	///
 	/// This assumes that the evaluation closure is one which returns true for at most a single
 	/// descendent of a block, in that only one fork of a block can be "heavy"
-	/// enough to trigger the threshold.
+	/// enough to trigger the threshold. When that doesn't hold, e.g. because of equivocations,
+	/// the fork with the lowest hash is followed, so that the result doesn't depend on the
+	/// order in which votes were inserted.
 	///
 	/// Returns `None` when the given `current_best` does not fulfill the condition.
 	pub fn find_ghost<'a, F>(&'a self, current_best: Option<(H, N)>, condition: F) -> Option<(H, N)>
@@ -339,7 +956,8 @@ where
 						true
 					}
 				})
-				.find(|&(_, node)| condition(&node.cumulative_vote));
+				.filter(|&(_, node)| condition(&node.cumulative_vote))
+				.min_by(|(a, _), (b, _)| a.cmp(b));
 
 			match next_descendent {
 				Some((key, node)) => {
@@ -402,23 +1020,22 @@ where
 		loop {
 			offset = offset + N::one();
 
-			let mut new_best = None;
 			for d_node in &descendent_nodes {
 				if let Some(d_block) = d_node.ancestor_block(base_number + offset) {
-					match descendent_blocks.binary_search_by_key(&d_block, |&(ref x, _)| x) {
-						Ok(idx) => {
-							descendent_blocks[idx].1 += &d_node.cumulative_vote;
-							if condition(&descendent_blocks[idx].1) {
-								new_best = Some(d_block.clone());
-								break
-							}
-						},
+					match descendent_blocks.binary_search_by_key(&d_block, |(x, _)| x) {
+						Ok(idx) => descendent_blocks[idx].1 += &d_node.cumulative_vote,
 						Err(idx) => descendent_blocks
 							.insert(idx, (d_block.clone(), d_node.cumulative_vote.clone())),
 					}
 				}
 			}
 
+			// the blocks are sorted, so on a tie the lowest hash wins.
+			let new_best = descendent_blocks
+				.iter()
+				.find(|(_, vote)| condition(vote))
+				.map(|(block, _)| block.clone());
+
 			match new_best {
 				Some(new_best) => {
 					best_number = best_number + N::one();
@@ -436,6 +1053,42 @@ where
 		Subchain { hashes, best_number }
 	}
 
+	// iterate the given block and its ancestors down to the base, following the
+	// ancestor-edges of the graph. returns `None` if the block isn't in the graph.
+	fn ancestry_iter(&self, hash: H, number: N) -> Option<impl Iterator<Item = H> + '_> {
+		// the node whose ancestor-edge we are walking, and the position in it.
+		let (first, mut node, mut index) = match self.find_containing_nodes(hash.clone(), number) {
+			None => (None, hash, 0),
+			Some(nodes) => {
+				let node = nodes.into_iter().next()?;
+				let offset = self.entries[&node].number - number - N::one();
+				(Some(hash), node, offset.try_into().ok()? + 1)
+			},
+		};
+
+		let mut first = first.or_else(|| Some(node.clone()));
+		Some(crate::std::iter::from_fn(move || {
+			if let Some(first) = first.take() {
+				return Some(first)
+			}
+
+			loop {
+				let entry = self.entries.get(&node)?;
+				match entry.ancestors.get(index) {
+					Some(ancestor) => {
+						index += 1;
+						return Some(ancestor.clone())
+					},
+					None => {
+						// move on to the edge of the parent vote-node.
+						node = entry.ancestor_node()?;
+						index = 0;
+					},
+				}
+			}
+		}))
+	}
+
 	// attempts to find the containing node keys for the given hash and number.
 	//
 	// returns `None` if there is a node by that key already, and a vector
@@ -513,12 +1166,13 @@ where
 				} else {
 					(entry.number - ancestor_number).as_()
 				};
-				let new_ancestors = entry.ancestors.drain(offset_usize..);
+				let new_ancestors = entry.ancestors.split_off(offset_usize);
+				entry.ancestors.shrink_to_fit();
 
 				let &mut (ref mut new_entry, _) = maybe_entry.get_or_insert_with(move || {
 					let new_entry = Entry {
 						number: ancestor_number,
-						ancestors: new_ancestors.collect(),
+						ancestors: new_ancestors,
 						descendents: vec![],
 						cumulative_vote: V::default(),
 					};
@@ -554,25 +1208,60 @@ where
 	// append a vote-node onto the chain-tree. This should only be called if
 	// no node in the tree keeps the target anyway.
 	fn append<C: Chain<H, N>>(&mut self, hash: H, number: N, chain: &C) -> Result<(), Error> {
-		let mut ancestry = chain.ancestry(self.base.clone(), hash.clone())?;
-		ancestry.push(self.base.clone()); // ancestry doesn't include base.
+		// we fetch the ancestry in spans ending at the number of the closest
+		// vote-node which could be an ancestor, since none of the blocks in
+		// between can be vote-nodes.
+		let mut ancestry = Vec::new();
+		let (mut cursor, mut cursor_number) = (hash.clone(), number);
+
+		// the numbers of the vote-nodes below the block, nearest first.
+		let mut node_numbers: Vec<N> = self
+			.entries
+			.values()
+			.map(|entry| entry.number)
+			.filter(|n| *n < number)
+			.collect();
+		node_numbers.sort_unstable_by(|a, b| b.cmp(a));
+		node_numbers.dedup();
+		let mut node_numbers = node_numbers.into_iter();
+
+		let ancestor_hash = loop {
+			let nearest = node_numbers.next().ok_or(Error::NotDescendent)?;
+
+			let depth = (cursor_number - nearest).try_into().map_err(|_| Error::InvalidAncestry)?;
+			let mut span = chain.ancestry_bounded(self.base.clone(), cursor, depth)?;
+			if span.len() < depth {
+				// a short span must end right above the base, anything else is a
+				// bug in the chain which would corrupt the numbers of the blocks.
+				if (cursor_number - self.base_number).try_into().ok() != Some(span.len() + 1) {
+					return Err(Error::InvalidAncestry)
+				}
+
+				span.push(self.base.clone()); // ancestry doesn't include base.
+			} else if span.len() > depth {
+				return Err(Error::InvalidAncestry)
+			}
+
+			let last = span
+				.last()
+				.cloned()
+				.expect("depth is non-zero; base is pushed if the span is short; qed");
+			ancestry.append(&mut span);
 
-		let mut ancestor_index = None;
-		for (i, ancestor) in ancestry.iter().enumerate() {
-			if let Some(entry) = self.entries.get_mut(ancestor) {
+			if let Some(entry) = self.entries.get_mut(&last) {
 				entry.descendents.push(hash.clone());
-				ancestor_index = Some(i);
-				break
+				break last
 			}
-		}
 
-		let ancestor_index = ancestor_index.expect(
-			"base is kept; \
-			chain returns ancestry only if the block is a descendent of base; qed",
-		);
+			cursor = last;
+			cursor_number = nearest;
+		};
 
-		let ancestor_hash = ancestry[ancestor_index].clone();
-		ancestry.truncate(ancestor_index + 1);
+		ancestry.shrink_to_fit();
+
+		if self.sparse_votes_depth.is_some_and(|depth| ancestry.len() > depth) {
+			self.sparse_votes = self.sparse_votes.max(Some(ancestry.len()));
+		}
 
 		self.entries.insert(
 			hash.clone(),
@@ -596,13 +1285,277 @@ mod tests {
 	use super::*;
 	use crate::testing::chain::{DummyChain, GENESIS_HASH};
 
-	#[test]
-	fn graph_fork_not_at_node() {
-		let mut chain = DummyChain::new();
-		let mut tracker = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+	// an accumulator tracking the ids of the voters.
+	#[derive(Clone, Debug, Default)]
+	struct Voters(BTreeSet<u32>);
 
-		chain.push_blocks(GENESIS_HASH, &["A", "B", "C"]);
-		chain.push_blocks("C", &["D1", "E1", "F1"]);
+	impl AddAssign<&Voters> for Voters {
+		fn add_assign(&mut self, rhs: &Voters) {
+			self.0.extend(rhs.0.iter().cloned());
+		}
+	}
+
+	impl Contributors for Voters {
+		fn distinct_voters(&self) -> usize {
+			self.0.len()
+		}
+	}
+
+	fn voter(id: u32) -> Voters {
+		Voters(std::iter::once(id).collect())
+	}
+
+	// a chain which records the length of the bounded ancestries it returns.
+	struct BoundedChain {
+		inner: DummyChain,
+		fetched: std::cell::RefCell<Vec<usize>>,
+	}
+
+	impl Chain<&'static str, u32> for BoundedChain {
+		fn ancestry(
+			&self,
+			_base: &'static str,
+			_block: &'static str,
+		) -> Result<Vec<&'static str>, Error> {
+			panic!("only bounded ancestry should be requested");
+		}
+
+		fn ancestry_bounded(
+			&self,
+			base: &'static str,
+			block: &'static str,
+			max: usize,
+		) -> Result<Vec<&'static str>, Error> {
+			let ancestry = self.inner.ancestry_bounded(base, block, max)?;
+			self.fetched.borrow_mut().push(ancestry.len());
+			Ok(ancestry)
+		}
+	}
+
+	#[test]
+	fn distinct_voters_at_ancestors() {
+		let mut chain = DummyChain::new();
+		let mut tracker = VoteGraph::new(GENESIS_HASH, 1, Voters::default());
+
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C"]);
+		chain.push_blocks("C", &["D1", "E1"]);
+		chain.push_blocks("C", &["D2", "E2"]);
+
+		tracker.insert("A", 2, voter(4), &chain).unwrap();
+		tracker.insert("C", 4, voter(1), &chain).unwrap();
+		tracker.insert("E1", 6, voter(1), &chain).unwrap();
+		tracker.insert("E1", 6, voter(2), &chain).unwrap();
+		tracker.insert("E2", 6, voter(2), &chain).unwrap();
+		tracker.insert("E2", 6, voter(3), &chain).unwrap();
+
+		assert_eq!(tracker.distinct_voters_at(&"E1"), Some(2));
+		assert_eq!(tracker.distinct_voters_at(&"E2"), Some(2));
+
+		// voters 1 and 2 voted more than once, but are only counted once.
+		assert_eq!(tracker.distinct_voters_at(&"C"), Some(3));
+		assert_eq!(tracker.distinct_voters_at(&"A"), Some(4));
+		assert_eq!(tracker.distinct_voters_at(&GENESIS_HASH), Some(4));
+
+		// not a vote-node.
+		assert_eq!(tracker.distinct_voters_at(&"B"), None);
+	}
+
+	#[test]
+	fn bounded_ancestry_is_truncated() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D"]);
+
+		assert_eq!(chain.ancestry_bounded(GENESIS_HASH, "D", 2), Ok(vec!["C", "B"]));
+		assert_eq!(chain.ancestry_bounded(GENESIS_HASH, "D", 10), Ok(vec!["C", "B", "A"]));
+		assert_eq!(chain.ancestry_bounded("A", "D", 0), Ok(vec![]));
+		assert_eq!(chain.ancestry_bounded("D", "A", 2), Err(Error::NotDescendent));
+	}
+
+	#[test]
+	fn truncated_ancestry_is_an_error() {
+		// a chain which drops the oldest blocks of every ancestry.
+		struct TruncatingChain(DummyChain);
+
+		impl Chain<&'static str, u32> for TruncatingChain {
+			fn ancestry(
+				&self,
+				base: &'static str,
+				block: &'static str,
+			) -> Result<Vec<&'static str>, Error> {
+				let mut ancestry = self.0.ancestry(base, block)?;
+				ancestry.truncate(ancestry.len().saturating_sub(2));
+				Ok(ancestry)
+			}
+		}
+
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+		let chain = TruncatingChain(chain);
+
+		let mut tracker = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+		assert_eq!(tracker.insert("E", 6, 100, &chain), Err(Error::InvalidAncestry));
+		assert!(tracker.find_containing_nodes("E", 6).is_some());
+		assert_eq!(tracker.cumulative_vote(&GENESIS_HASH, 1), Some(0));
+
+		// a block right above the base has an empty ancestry either way.
+		tracker.insert("A", 2, 100, &chain).unwrap();
+		assert_eq!(tracker.cumulative_vote(&GENESIS_HASH, 1), Some(100));
+	}
+
+	#[test]
+	fn append_only_fetches_ancestry_to_nearest_node() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E", "F", "G", "H"]);
+		chain.push_blocks("B", &["C2", "D2", "E2", "F2", "G2"]);
+
+		let chain = BoundedChain { inner: chain, fetched: Default::default() };
+		let mut tracker = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+
+		// there are no vote-nodes between `F` and the base.
+		tracker.insert("F", 7, 100, &chain).unwrap();
+		assert_eq!(*chain.fetched.borrow(), vec![5]);
+
+		// only the blocks up to `F` are needed.
+		chain.fetched.borrow_mut().clear();
+		tracker.insert("H", 9, 100, &chain).unwrap();
+		assert_eq!(*chain.fetched.borrow(), vec![2]);
+		assert_eq!(tracker.entries.get("H").unwrap().ancestors, vec!["G", "F"]);
+
+		// `F2` is at the number of `F` but isn't a vote-node, so we keep going.
+		chain.fetched.borrow_mut().clear();
+		tracker.insert("G2", 8, 100, &chain).unwrap();
+		assert_eq!(*chain.fetched.borrow(), vec![1, 5]);
+		assert_eq!(
+			tracker.entries.get("G2").unwrap().ancestors,
+			vec!["F2", "E2", "D2", "C2", "B", "A", GENESIS_HASH],
+		);
+		assert_eq!(tracker.cumulative_vote(&GENESIS_HASH, 1), Some(300));
+	}
+
+	#[test]
+	fn common_ancestor_of_forks() {
+		let mut chain = DummyChain::new();
+		let mut tracker = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C"]);
+		chain.push_blocks("C", &["D1", "E1", "F1"]);
+		chain.push_blocks("C", &["D2", "E2", "F2"]);
+		chain.push_blocks("E1", &["FA", "GA"]);
+
+		tracker.insert("A", 2, 100, &chain).unwrap();
+		tracker.insert("F1", 7, 100, &chain).unwrap();
+		tracker.insert("F2", 7, 100, &chain).unwrap();
+		tracker.insert("GA", 8, 100, &chain).unwrap();
+
+		// heads of forks which diverge at interior blocks.
+		assert_eq!(tracker.common_ancestor(("F1", 7), ("F2", 7)), Some(("C", 4)));
+		assert_eq!(tracker.common_ancestor(("GA", 8), ("F2", 7)), Some(("C", 4)));
+		assert_eq!(tracker.common_ancestor(("GA", 8), ("F1", 7)), Some(("E1", 6)));
+
+		// interior blocks and ancestors.
+		assert_eq!(tracker.common_ancestor(("D1", 5), ("E2", 6)), Some(("C", 4)));
+		assert_eq!(tracker.common_ancestor(("B", 3), ("F2", 7)), Some(("B", 3)));
+		assert_eq!(tracker.common_ancestor(("F2", 7), ("A", 2)), Some(("A", 2)));
+		assert_eq!(tracker.common_ancestor(("F2", 7), (GENESIS_HASH, 1)), Some((GENESIS_HASH, 1)));
+
+		// equal blocks.
+		assert_eq!(tracker.common_ancestor(("F1", 7), ("F1", 7)), Some(("F1", 7)));
+		assert_eq!(tracker.common_ancestor(("E2", 6), ("E2", 6)), Some(("E2", 6)));
+
+		// unknown blocks.
+		assert_eq!(tracker.common_ancestor(("X", 7), ("F1", 7)), None);
+		assert_eq!(tracker.common_ancestor(("F1", 7), ("E2", 5)), None);
+	}
+
+	#[test]
+	fn growth_callback_fires_at_milestones() {
+		use std::sync::{Arc, Mutex};
+
+		let mut chain = DummyChain::new();
+		let mut tracker = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E", "F", "G", "H"]);
+		chain.push_blocks("A", &["B1", "C1"]);
+		chain.push_blocks("B", &["C2", "D2"]);
+
+		let milestones = Arc::new(Mutex::new(Vec::new()));
+		let noted = milestones.clone();
+		tracker.on_growth(2, move |entries, heads| noted.lock().unwrap().push((entries, heads)));
+
+		// only the base.
+		assert!(milestones.lock().unwrap().is_empty());
+
+		tracker.insert("B", 3, 1, &chain).unwrap();
+		assert_eq!(*milestones.lock().unwrap(), vec![(2, 1)]);
+
+		// votes on existing nodes don't grow the graph.
+		tracker.insert("B", 3, 1, &chain).unwrap();
+		tracker.insert("D", 5, 1, &chain).unwrap();
+		assert_eq!(*milestones.lock().unwrap(), vec![(2, 1)]);
+
+		tracker.insert("C1", 4, 1, &chain).unwrap();
+		assert_eq!(*milestones.lock().unwrap(), vec![(2, 1), (4, 2)]);
+
+		tracker.insert("H", 9, 1, &chain).unwrap();
+		tracker.insert("D2", 5, 1, &chain).unwrap();
+		tracker.insert("G", 8, 1, &chain).unwrap();
+		assert_eq!(*milestones.lock().unwrap(), vec![(2, 1), (4, 2)]);
+
+		tracker.insert("E", 6, 1, &chain).unwrap();
+		assert_eq!(*milestones.lock().unwrap(), vec![(2, 1), (4, 2), (8, 3)]);
+	}
+
+	#[test]
+	fn graph_over_wider_block_numbers() {
+		// the dummy chain's ancestry doesn't depend on the block number type.
+		struct WideChain(DummyChain);
+
+		impl<N: Copy + BlockNumberOps> Chain<&'static str, N> for WideChain {
+			fn ancestry(
+				&self,
+				base: &'static str,
+				block: &'static str,
+			) -> Result<Vec<&'static str>, Error> {
+				Chain::<_, u32>::ancestry(&self.0, base, block)
+			}
+		}
+
+		fn check<N: Copy + Debug + BlockNumberOps + From<u16>>() {
+			let mut chain = DummyChain::new();
+			chain.push_blocks(GENESIS_HASH, &["A", "B", "C"]);
+			chain.push_blocks("C", &["D1", "E1", "F1"]);
+			chain.push_blocks("C", &["D2", "E2", "F2"]);
+			let chain = WideChain(chain);
+
+			let mut tracker = VoteGraph::new(GENESIS_HASH, N::from(1), 0u32);
+			tracker.insert("A", N::from(2), 100, &chain).unwrap();
+			tracker.insert("E1", N::from(6), 100, &chain).unwrap();
+			tracker.insert("F2", N::from(7), 100, &chain).unwrap();
+
+			assert_eq!(tracker.cumulative_vote(&"C", N::from(4)), Some(200));
+			assert_eq!(tracker.find_ghost(None, |&x| x >= 200), Some(("C", N::from(4))));
+			assert_eq!(
+				tracker.find_ancestor("F2", N::from(7), |&x| x > 100),
+				Some(("C", N::from(4))),
+			);
+
+			assert!(matches!(
+				tracker.insert("E2", N::from(9), 100, &chain),
+				Err(Error::InvalidAncestry),
+			));
+		}
+
+		check::<usize>();
+		check::<u64>();
+	}
+
+	#[test]
+	fn graph_fork_not_at_node() {
+		let mut chain = DummyChain::new();
+		let mut tracker = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C"]);
+		chain.push_blocks("C", &["D1", "E1", "F1"]);
 		chain.push_blocks("C", &["D2", "E2", "F2"]);
 
 		tracker.insert("A", 2, 100, &chain).unwrap();
@@ -626,6 +1579,402 @@ mod tests {
 		assert_eq!(f_entry.cumulative_vote, 100);
 	}
 
+	#[test]
+	fn removing_votes() {
+		let mut chain = DummyChain::new();
+		let mut tracker = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C"]);
+		chain.push_blocks("C", &["D1", "E1", "F1"]);
+		chain.push_blocks("C", &["D2", "E2", "F2"]);
+
+		tracker.insert("A", 2, 100, &chain).unwrap();
+		tracker.insert("E1", 6, 100, &chain).unwrap();
+		tracker.insert("F2", 7, 100, &chain).unwrap();
+		tracker.insert("F2", 7, 50, &chain).unwrap();
+
+		// only blocks with vote-nodes hold votes to remove.
+		assert!(!tracker.remove("D2", 5, 50));
+		assert!(!tracker.remove("F2", 6, 50));
+
+		assert!(tracker.remove("F2", 7, 50));
+		assert_eq!(tracker.cumulative_vote(&"F2", 7), Some(100));
+		assert_eq!(tracker.entries.get("A").unwrap().cumulative_vote, 300);
+		assert_eq!(tracker.find_ghost(None, |&x| x >= 200), Some(("C", 4)));
+
+		// the last vote on F2 is removed along with its vote-node.
+		assert!(tracker.remove("F2", 7, 100));
+		assert!(!tracker.entries.contains_key("F2"));
+		assert!(!tracker.heads.contains("F2"));
+		assert_eq!(tracker.entries.get("A").unwrap().descendents, vec!["E1"]);
+		assert_eq!(tracker.find_ghost(None, |&x| x >= 100), Some(("E1", 6)));
+
+		// A still holds a vote of its own after E1 is gone, so it becomes a head.
+		assert!(tracker.remove("E1", 6, 100));
+		assert_eq!(tracker.heads.iter().collect::<Vec<_>>(), vec![&"A"]);
+		assert_eq!(tracker.entries.get("A").unwrap().cumulative_vote, 100);
+
+		// and the base is kept when every vote is gone.
+		assert!(tracker.remove("A", 2, 100));
+		assert_eq!(tracker.entries.len(), 1);
+		assert_eq!(tracker.heads.iter().collect::<Vec<_>>(), vec![&GENESIS_HASH]);
+		assert_eq!(tracker.cumulative_vote(&GENESIS_HASH, 1), Some(0));
+	}
+
+	#[test]
+	fn removing_votes_prunes_dead_ancestors() {
+		let mut chain = DummyChain::new();
+		let mut tracker = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C"]);
+		chain.push_blocks("C", &["D1", "E1"]);
+		chain.push_blocks("C", &["D2", "E2"]);
+
+		// the vote on C introduces a branch, which the second fork is appended to.
+		tracker.insert("E1", 6, 100, &chain).unwrap();
+		tracker.insert("C", 4, 50, &chain).unwrap();
+		tracker.insert("E2", 6, 100, &chain).unwrap();
+		assert_eq!(tracker.entries.get("C").unwrap().descendents, vec!["E1", "E2"]);
+
+		// C is kept while E2 descends from it, even without votes of its own.
+		assert!(tracker.remove("E1", 6, 100));
+		assert!(tracker.remove("C", 4, 50));
+		assert_eq!(tracker.entries.get("C").unwrap().descendents, vec!["E2"]);
+		assert_eq!(tracker.cumulative_vote(&"C", 4), Some(100));
+
+		assert!(tracker.remove("E2", 6, 100));
+		assert_eq!(tracker.entries.len(), 1);
+		assert_eq!(tracker.heads.iter().collect::<Vec<_>>(), vec![&GENESIS_HASH]);
+
+		// votes can be inserted again afterwards.
+		tracker.insert("E2", 6, 100, &chain).unwrap();
+		assert_eq!(tracker.find_ghost(None, |&x| x >= 100), Some(("E2", 6)));
+	}
+
+	#[test]
+	fn reinserted_votes_are_counted_once() {
+		let mut chain = DummyChain::new();
+		let mut tracker = VoteGraph::with_voter_ids(GENESIS_HASH, 1, 0u32);
+
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C"]);
+
+		assert!(tracker.insert_once(1u32, "C", 4, 5u32, &chain).unwrap());
+		assert!(!tracker.insert_once(1u32, "C", 4, 5u32, &chain).unwrap());
+		assert_eq!(tracker.cumulative_vote(&"C", 4), Some(5));
+
+		// votes of other voters, and of the same voter for other blocks, count.
+		assert!(tracker.insert_once(2u32, "C", 4, 3u32, &chain).unwrap());
+		assert!(tracker.insert_once(1u32, "B", 3, 5u32, &chain).unwrap());
+		assert_eq!(tracker.cumulative_vote(&"C", 4), Some(8));
+		assert_eq!(tracker.cumulative_vote(&"B", 3), Some(13));
+
+		// a vote which couldn't be inserted isn't remembered.
+		assert!(tracker.insert_once(3u32, "D", 5, 1u32, &chain).is_err());
+		chain.push_blocks("C", &["D"]);
+		assert!(tracker.insert_once(3u32, "D", 5, 1u32, &chain).unwrap());
+
+		// once removed, a vote can be inserted again.
+		assert!(tracker.remove_once(1u32, "C", 4, 5u32));
+		assert!(!tracker.remove_once(1u32, "C", 4, 5u32));
+		assert_eq!(tracker.cumulative_vote(&"C", 4), Some(4));
+
+		assert!(tracker.insert_once(1u32, "C", 4, 5u32, &chain).unwrap());
+		assert_eq!(tracker.cumulative_vote(&"C", 4), Some(9));
+	}
+
+	#[test]
+	fn rendering_as_dot() {
+		let mut chain = DummyChain::new();
+		let mut tracker = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C"]);
+		chain.push_blocks("C", &["D1", "E1", "F1"]);
+		chain.push_blocks("C", &["D2", "E2", "F2"]);
+		chain.push_blocks("B", &["C\"3"]);
+
+		tracker.insert("F1", 7, 5u32, &chain).unwrap();
+		tracker.insert("F2", 7, 4u32, &chain).unwrap();
+		tracker.insert("C\"3", 4, 1u32, &chain).unwrap();
+
+		// vote-nodes are numbered in the order of their hashes.
+		let expected = [
+			"digraph {",
+			"\tn0 [label=\"C\\\"3 #4\\n1\"];",
+			"\tn1 [label=\"F1 #7\\n5\"];",
+			"\tn2 [label=\"F2 #7\\n4\"];",
+			"\tn3 [label=\"genesis #1\\n10\", shape=box];",
+			"\tn3 -> n1 [label=\"6 blocks\"];",
+			"\tn3 -> n2 [label=\"6 blocks\"];",
+			"\tn3 -> n0 [label=\"3 blocks\"];",
+			"}",
+			"",
+		];
+
+		assert_eq!(format!("{}", tracker.dot()), expected.join("\n"));
+	}
+
+	#[test]
+	fn merging_graphs_counts_each_vote_once() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C"]);
+		chain.push_blocks("C", &["D1", "E1", "F1"]);
+		chain.push_blocks("C", &["D2", "E2", "F2"]);
+		chain.push_blocks("B", &["X"]);
+
+		let votes = [
+			("F1", 7, 5u32),
+			("C", 4, 2),
+			("E2", 6, 4),
+			(GENESIS_HASH, 1, 1),
+			("F1", 7, 3),
+			("X", 4, 6),
+			("D2", 5, 1),
+		];
+
+		let mut all = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+		let mut first = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+		let mut second = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+		for (i, &(hash, number, vote)) in votes.iter().enumerate() {
+			all.insert(hash, number, vote, &chain).unwrap();
+			let batch = if i % 2 == 0 { &mut first } else { &mut second };
+			batch.insert(hash, number, vote, &chain).unwrap();
+		}
+
+		first.merge(second, &chain).unwrap();
+
+		let blocks = [
+			(GENESIS_HASH, 1),
+			("A", 2),
+			("B", 3),
+			("C", 4),
+			("X", 4),
+			("D1", 5),
+			("E1", 6),
+			("F1", 7),
+			("D2", 5),
+			("E2", 6),
+			("F2", 7),
+		];
+		for (hash, number) in blocks {
+			assert_eq!(first.cumulative_vote(&hash, number), all.cumulative_vote(&hash, number));
+		}
+
+		assert_eq!(first.cumulative_vote(&GENESIS_HASH, 1), Some(22));
+		assert_eq!(first.heads(), all.heads());
+		for threshold in [5, 10, 15] {
+			assert_eq!(
+				first.find_ghost(None, |&v| v >= threshold),
+				all.find_ghost(None, |&v| v >= threshold),
+			);
+		}
+	}
+
+	#[test]
+	#[should_panic(expected = "only graphs with the same base can be merged")]
+	fn merging_graphs_with_different_bases() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B"]);
+
+		let mut graph = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+		graph.merge(VoteGraph::new("A", 2, 0u32), &chain).unwrap();
+	}
+
+	#[test]
+	fn querying_cumulative_votes() {
+		let mut chain = DummyChain::new();
+		let mut tracker = VoteGraph::new("A", 2, 0u32);
+
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C"]);
+		chain.push_blocks("C", &["D1", "E1"]);
+		chain.push_blocks("C", &["D2", "E2"]);
+		chain.push_blocks("B", &["X"]);
+
+		assert!(tracker.is_empty());
+		assert_eq!(tracker.cumulative_vote(&"A", 2), Some(0));
+
+		tracker.insert("E1", 6, 3u32, &chain).unwrap();
+		tracker.insert("E2", 6, 4u32, &chain).unwrap();
+		assert!(!tracker.is_empty());
+
+		// blocks within edges are summed over the vote-nodes containing them.
+		assert_eq!(tracker.cumulative_vote(&"B", 3), Some(7));
+		assert_eq!(tracker.cumulative_vote(&"C", 4), Some(7));
+		assert_eq!(tracker.cumulative_vote(&"D1", 5), Some(3));
+		assert_eq!(tracker.cumulative_vote(&"E2", 6), Some(4));
+
+		// blocks off the voted-on chains or below the base aren't known.
+		assert_eq!(tracker.cumulative_vote(&"X", 4), None);
+		assert_eq!(tracker.cumulative_vote(&GENESIS_HASH, 1), None);
+
+		assert_eq!(tracker.base(), ("A", 2));
+
+		assert!(tracker.remove("E1", 6, 3u32));
+		assert!(tracker.remove("E2", 6, 4u32));
+		assert!(tracker.is_empty());
+	}
+
+	#[test]
+	fn incremental_heads_match_recomputed() {
+		let mut chain = DummyChain::new();
+		let mut tracker = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C"]);
+		chain.push_blocks("C", &["D1", "E1", "F1"]);
+		chain.push_blocks("C", &["D2", "E2", "F2"]);
+		chain.push_blocks("E2", &["F3", "G3"]);
+
+		let check = |tracker: &mut VoteGraph<_, _, _>| {
+			let incremental = tracker.heads.clone();
+			tracker.recompute_heads();
+			assert_eq!(tracker.heads, incremental);
+		};
+
+		enum Op {
+			Insert(&'static str, u32),
+			Remove(&'static str, u32),
+		}
+
+		let ops = [
+			Op::Insert("F1", 7),
+			Op::Insert("F2", 7),
+			Op::Insert("C", 4),
+			Op::Insert("G3", 8),
+			Op::Insert("E2", 6),
+			Op::Remove("F2", 7),
+			Op::Insert("A", 2),
+			Op::Remove("G3", 8),
+			Op::Remove("E2", 6),
+			Op::Insert("D1", 5),
+			Op::Remove("F1", 7),
+			Op::Remove("C", 4),
+			Op::Remove("D1", 5),
+			Op::Insert("F3", 7),
+			Op::Remove("A", 2),
+			Op::Remove("F3", 7),
+		];
+
+		check(&mut tracker);
+		for op in ops {
+			match op {
+				Op::Insert(hash, number) => tracker.insert(hash, number, 1, &chain).unwrap(),
+				Op::Remove(hash, number) => assert!(tracker.remove(hash, number, 1)),
+			}
+			check(&mut tracker);
+		}
+
+		assert_eq!(tracker.entries.len(), 1);
+
+		// drifted heads are repaired by recomputing them.
+		tracker.heads.insert("A");
+		tracker.recompute_heads();
+		assert_eq!(tracker.heads.iter().collect::<Vec<_>>(), vec![&GENESIS_HASH]);
+	}
+
+	#[test]
+	fn reconstructed_from_parts() {
+		let mut chain = DummyChain::new();
+		let mut tracker = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C"]);
+		chain.push_blocks("C", &["D1", "E1", "F1"]);
+		chain.push_blocks("C", &["D2", "E2", "F2"]);
+
+		tracker.insert("F1", 7, 5u32, &chain).unwrap();
+		tracker.insert("F2", 7, 4u32, &chain).unwrap();
+		tracker.insert("B", 3, 2u32, &chain).unwrap();
+
+		let parts = |tracker: &VoteGraph<_, _, u32>| {
+			tracker
+				.entries()
+				.map(|(h, n, ancestors, v)| (*h, n, ancestors.to_vec(), *v))
+				.collect::<Vec<_>>()
+		};
+
+		let rebuilt =
+			VoteGraph::from_parts(GENESIS_HASH, 1, parts(&tracker), tracker.heads().clone())
+				.unwrap();
+
+		assert_eq!(parts(&rebuilt), parts(&tracker));
+		assert_eq!(rebuilt.heads(), tracker.heads());
+		for threshold in [4, 9, 11] {
+			assert_eq!(
+				rebuilt.find_ghost(None, |&v| v >= threshold),
+				tracker.find_ghost(None, |&v| v >= threshold),
+			);
+		}
+	}
+
+	#[test]
+	fn inconsistent_parts_are_rejected() {
+		let parts = || {
+			vec![
+				(GENESIS_HASH, 1, vec![], 9u32),
+				("C", 4, vec!["B", "A", GENESIS_HASH], 9),
+				("F1", 7, vec!["E1", "D1", "C"], 5),
+				("F2", 7, vec!["E2", "D2", "C"], 4),
+			]
+		};
+		let heads = || ["F1", "F2"].iter().cloned().collect::<BTreeSet<_>>();
+		let from_parts = |parts, heads| VoteGraph::from_parts(GENESIS_HASH, 1, parts, heads);
+
+		assert!(from_parts(parts(), heads()).is_ok());
+
+		// the parent vote-node of F2 is missing.
+		let mut missing_parent = parts();
+		missing_parent.remove(1);
+		assert!(matches!(from_parts(missing_parent, heads()), Err(Error::InvalidVoteGraph)));
+
+		// the ancestry of F1 doesn't match its number.
+		let mut wrong_edge = parts();
+		wrong_edge[2].1 = 8;
+		assert!(matches!(from_parts(wrong_edge, heads()), Err(Error::InvalidVoteGraph)));
+
+		// the ancestry of F1 passes through another vote-node.
+		let mut skipped_node = parts();
+		skipped_node[2].2 = vec!["E1", "D1", "C", "B", "A", GENESIS_HASH];
+		assert!(matches!(from_parts(skipped_node, heads()), Err(Error::InvalidVoteGraph)));
+
+		// C has descendents so it can't be a head.
+		let mut not_leaf = heads();
+		not_leaf.insert("C");
+		assert!(matches!(from_parts(parts(), not_leaf), Err(Error::InvalidVoteGraph)));
+
+		// the base must be the first vote-node.
+		let mut wrong_base = parts();
+		wrong_base[0].2 = vec!["X"];
+		assert!(matches!(from_parts(wrong_base, heads()), Err(Error::InvalidVoteGraph)));
+
+		// vote-nodes are unique.
+		let mut duplicate = parts();
+		duplicate.push(("F2", 7, vec!["E2", "D2", "C"], 4));
+		assert!(matches!(from_parts(duplicate, heads()), Err(Error::InvalidVoteGraph)));
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn serialization_round_trip() {
+		let mut chain = DummyChain::new();
+		let mut tracker = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C"]);
+		chain.push_blocks("C", &["D1", "E1", "F1"]);
+		chain.push_blocks("C", &["D2", "E2", "F2"]);
+
+		tracker.insert("F1", 7, 5u32, &chain).unwrap();
+		tracker.insert("F2", 7, 4u32, &chain).unwrap();
+
+		let json = serde_json::to_string(&tracker).unwrap();
+		let rebuilt: VoteGraph<String, u32, u32> = serde_json::from_str(&json).unwrap();
+
+		assert_eq!(rebuilt.heads().len(), 2);
+		assert_eq!(rebuilt.find_ghost(None, |&v| v >= 9), Some(("C".to_string(), 4)));
+
+		// F2 is dropped from the heads.
+		let corrupted = json.replace("\"F1\",\"F2\"]", "\"F1\"]");
+		assert_ne!(corrupted, json);
+		assert!(serde_json::from_str::<VoteGraph<String, u32, u32>>(&corrupted).is_err());
+	}
+
 	#[test]
 	fn graph_fork_at_node() {
 		let mut chain = DummyChain::new();
@@ -684,6 +2033,33 @@ mod tests {
 		assert_eq!(tracker.find_ghost(Some(("B", 3)), |&x| x >= 250), Some(("C", 4)));
 	}
 
+	#[test]
+	fn ghost_ties_and_unqualified_descendents() {
+		let mut chain = DummyChain::new();
+		chain.push_blocks(GENESIS_HASH, &["A", "B"]);
+		chain.push_blocks("B", &["C2", "D2"]);
+		chain.push_blocks("B", &["C1", "D1"]);
+
+		// the forks are equally heavy, whichever is inserted first.
+		for forks in [["D1", "D2"], ["D2", "D1"]] {
+			let mut tracker = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+			tracker.insert("A", 2, 50, &chain).unwrap();
+			for fork in forks {
+				tracker.insert(fork, 5, 100, &chain).unwrap();
+			}
+
+			// ties between sibling subtrees go to the lowest hash.
+			assert_eq!(tracker.find_ghost(None, |&x| x >= 100), Some(("D1", 5)));
+
+			// no descendent qualifies, so the starting block is the GHOST.
+			assert_eq!(tracker.find_ghost(None, |&x| x >= 150), Some(("B", 3)));
+			assert_eq!(tracker.find_ghost(Some(("A", 2)), |&x| x >= 250), Some(("A", 2)));
+
+			// the starting block doesn't qualify either.
+			assert_eq!(tracker.find_ghost(Some(("A", 2)), |&x| x >= 300), None);
+		}
+	}
+
 	#[test]
 	fn ghost_merge_not_at_node_one_side_weighted() {
 		let mut chain = DummyChain::new();
@@ -798,6 +2174,21 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn walk_back_from_unknown_block() {
+		let mut chain = DummyChain::new();
+		let mut tracker = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D"]);
+		tracker.insert("C", 4, 10, &chain).unwrap();
+
+		// blocks which aren't in the ancestry of any vote-node aren't in the graph,
+		// even if they are descendents of one.
+		assert_eq!(tracker.find_ancestor("X", 3, |&x| x > 5), None);
+		assert_eq!(tracker.find_ancestor("D", 5, |&x| x > 5), None);
+		assert_eq!(tracker.find_ancestor("B", 3, |&x| x > 5), Some(("B", 3)));
+	}
+
 	#[test]
 	fn adjust_base() {
 		let mut chain = DummyChain::new();
@@ -828,6 +2219,140 @@ mod tests {
 		assert_eq!(tracker.entries.get(GENESIS_HASH).unwrap().cumulative_vote, 15);
 	}
 
+	#[test]
+	fn prune() {
+		let mut chain = DummyChain::new();
+		let mut tracker = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E", "F"]);
+		chain.push_blocks("E", &["EA", "EB", "EC", "ED"]);
+		chain.push_blocks("C", &["D2", "E2"]);
+		chain.push_blocks("B", &["C3"]);
+
+		tracker.insert("A", 2, 1, &chain).unwrap();
+		tracker.insert("F", 7, 5, &chain).unwrap();
+		tracker.insert("ED", 10, 7, &chain).unwrap();
+		tracker.insert("E2", 6, 3, &chain).unwrap();
+
+		// blocks off the graph or below the base can't become the base.
+		assert!(!tracker.prune("C3", 4));
+		assert!(!tracker.prune("X", 1));
+		assert_eq!(tracker.base(), (GENESIS_HASH, 1));
+
+		// `D` is in the ancestry of the vote-nodes at `F` and `ED`.
+		assert!(tracker.prune("D", 5));
+		assert_eq!(tracker.base(), ("D", 5));
+		assert_eq!(tracker.entries.get("D").unwrap().ancestors, Vec::<&str>::new());
+		assert_eq!(tracker.entries.get("F").unwrap().ancestors, vec!["E", "D"]);
+		assert_eq!(tracker.heads().iter().cloned().collect::<Vec<_>>(), vec!["ED", "F"]);
+
+		// votes off the chain of `D` are dropped, the rest is unchanged.
+		assert_eq!(tracker.cumulative_vote(&"D", 5), Some(12));
+		assert_eq!(tracker.cumulative_vote(&"E", 6), Some(12));
+		assert_eq!(tracker.cumulative_vote(&"EB", 8), Some(7));
+		assert_eq!(tracker.cumulative_vote(&"A", 2), None);
+		assert_eq!(tracker.cumulative_vote(&"E2", 6), None);
+
+		// votes for blocks below the base are no longer accepted.
+		assert_eq!(tracker.insert("C", 4, 1, &chain), Err(Error::NotDescendent));
+		tracker.insert("EB", 8, 1, &chain).unwrap();
+		assert_eq!(tracker.cumulative_vote(&"D", 5), Some(13));
+	}
+
+	#[test]
+	fn block_numbers_beyond_usize() {
+		// the blocks of a chain, numbered from `OFFSET`.
+		struct OffsetChain(&'static [&'static str]);
+
+		const OFFSET: u128 = u64::MAX as u128 + 10;
+
+		impl Chain<&'static str, u128> for OffsetChain {
+			fn ancestry(
+				&self,
+				base: &'static str,
+				block: &'static str,
+			) -> Result<Vec<&'static str>, Error> {
+				let position = |hash| self.0.iter().position(|h| *h == hash);
+				match (position(base), position(block)) {
+					(Some(base), Some(block)) if base < block =>
+						Ok(self.0[base + 1..block].iter().rev().cloned().collect()),
+					_ => Err(Error::NotDescendent),
+				}
+			}
+		}
+
+		let chain = OffsetChain(&["G", "A", "B", "C", "D"]);
+		let mut tracker = VoteGraph::new("A", OFFSET + 1, 0u32);
+
+		tracker.insert("D", OFFSET + 4, 5, &chain).unwrap();
+		tracker.insert("C", OFFSET + 3, 3, &chain).unwrap();
+
+		assert_eq!(tracker.find_ghost(None, |&v| v >= 8), Some(("C", OFFSET + 3)));
+		assert_eq!(tracker.find_ancestor("D", OFFSET + 4, |&v| v >= 8), Some(("C", OFFSET + 3)));
+
+		tracker.adjust_base(&["G"]);
+		assert_eq!(tracker.base(), ("G", OFFSET));
+		assert_eq!(tracker.cumulative_vote(&"A", OFFSET + 1), Some(8));
+
+		// an ancestry proof can't reach below block zero.
+		let mut tracker = VoteGraph::new("A", 1u128, 0u32);
+		tracker.adjust_base(&["G", "H"]);
+		assert_eq!(tracker.base(), ("A", 1));
+	}
+
+	#[test]
+	fn ancestor_offsets_beyond_usize() {
+		// the ancestors of a block numbered far beyond `usize`.
+		let number = usize::MAX as u128 + 4;
+		let entry = Entry {
+			number,
+			ancestors: vec!["C", "B", "A"],
+			descendents: Vec::new(),
+			cumulative_vote: 0u32,
+		};
+
+		assert_eq!(entry.ancestor_block(number - 1), Some(&"C"));
+		assert_eq!(entry.ancestor_block(number - 3), Some(&"A"));
+		assert_eq!(entry.ancestor_block(number - 4), None);
+
+		// the offset of block 1 is `usize::MAX + 2`, which an `as` cast would
+		// truncate to the index of `B`.
+		assert_eq!(entry.ancestor_block(1), None);
+		assert_eq!(entry.in_direct_ancestry(&"B", 1), None);
+	}
+
+	#[test]
+	fn adjusting_base_preserves_votes_and_heads() {
+		let mut chain = DummyChain::new();
+		let mut tracker = VoteGraph::new("C", 4, 0u32);
+
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D1", "E1", "F1"]);
+		chain.push_blocks("C", &["D2", "E2"]);
+
+		tracker.insert("F1", 7, 5u32, &chain).unwrap();
+		tracker.insert("E2", 6, 4u32, &chain).unwrap();
+		tracker.insert("C", 4, 1u32, &chain).unwrap();
+
+		let voted_on = [("C", 4), ("D1", 5), ("E1", 6), ("F1", 7), ("D2", 5), ("E2", 6)];
+		let votes = |tracker: &VoteGraph<_, _, u32>| {
+			voted_on.iter().map(|(h, n)| tracker.cumulative_vote(h, *n)).collect::<Vec<_>>()
+		};
+
+		let before = votes(&tracker);
+		let heads = tracker.heads().clone();
+		let ghost = tracker.find_ghost(None, |&v| v >= 5);
+
+		tracker.adjust_base(&["B", "A"]);
+
+		assert_eq!(votes(&tracker), before);
+		assert_eq!(tracker.heads(), &heads);
+		assert_eq!(tracker.find_ghost(None, |&v| v >= 5), ghost);
+
+		// the blocks below the old base carry all votes.
+		assert_eq!(tracker.cumulative_vote(&"B", 3), Some(10));
+		assert_eq!(tracker.cumulative_vote(&"A", 2), Some(10));
+	}
+
 	#[test]
 	fn find_ancestor_is_largest() {
 		let mut chain = DummyChain::new();
@@ -850,4 +2375,102 @@ mod tests {
 		// `actual` used to (incorrectly) be (genesis, 0)
 		assert_eq!(actual, ("A", 1));
 	}
+
+	#[test]
+	fn deep_ancestry_keeps_no_spare_capacity() {
+		let mut chain = DummyChain::new();
+		let mut tracker = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+
+		let blocks: Vec<&'static str> =
+			(0..10_000).map(|i| &*Box::leak(format!("{}", i).into_boxed_str())).collect();
+		chain.push_blocks(GENESIS_HASH, &blocks);
+
+		// a vote far above base fetches the whole ancestry from base.
+		tracker.insert("9999", 10_001, 1, &chain).unwrap();
+		tracker.insert("9998", 10_000, 1, &chain).unwrap();
+
+		// and one which splits the long edge in the middle.
+		tracker.insert("4999", 5001, 1, &chain).unwrap();
+
+		for (hash, entry) in &tracker.entries {
+			if hash != &GENESIS_HASH {
+				let ancestor_number = tracker.entries[&entry.ancestor_node().unwrap()].number;
+				assert_eq!(entry.ancestors.len() as u32, entry.number - ancestor_number);
+			}
+
+			assert_eq!(entry.ancestors.len(), entry.ancestors.capacity());
+		}
+
+		assert_eq!(tracker.entries["9999"].ancestors.len(), 1);
+		assert_eq!(tracker.entries["4999"].ancestors.len(), 5000);
+		assert_eq!(tracker.find_ghost(None, |x| x >= &3), Some(("4999", 5001)));
+		assert_eq!(tracker.find_ghost(None, |x| x >= &2), Some(("9998", 10_000)));
+	}
+
+	#[test]
+	fn branch_introduced_below_many_heads() {
+		let mut chain = DummyChain::new();
+		let mut tracker = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C"]);
+		chain.push_blocks("C", &["D1", "E1"]);
+		chain.push_blocks("C", &["D2", "E2"]);
+		chain.push_blocks("C", &["D3", "E3"]);
+
+		// no node at the fork point, so all three heads share their edge
+		// down to genesis.
+		tracker.insert("E1", 6, 100, &chain).unwrap();
+		tracker.insert("E2", 6, 200, &chain).unwrap();
+		tracker.insert("E3", 6, 300, &chain).unwrap();
+		assert!(!tracker.entries.contains_key("C"));
+
+		// a vote below the fork point splits the edge of every head at once.
+		tracker.insert("A", 2, 10, &chain).unwrap();
+
+		let a_entry = tracker.entries.get("A").unwrap();
+		let mut descendents = a_entry.descendents.clone();
+		descendents.sort();
+		assert_eq!(descendents, vec!["E1", "E2", "E3"]);
+		assert_eq!(a_entry.ancestors, vec![GENESIS_HASH]);
+		assert_eq!(a_entry.cumulative_vote, 610);
+
+		for (head, c) in &[("E1", "D1"), ("E2", "D2"), ("E3", "D3")] {
+			let entry = tracker.entries.get(head).unwrap();
+			assert_eq!(entry.ancestors, vec![*c, "C", "B", "A"]);
+			assert_eq!(entry.ancestor_node().unwrap(), "A");
+		}
+
+		assert_eq!(tracker.entries[GENESIS_HASH].descendents, vec!["A"]);
+		assert_eq!(tracker.entries[GENESIS_HASH].cumulative_vote, 610);
+
+		// further votes keep cumulative votes consistent across the split.
+		tracker.insert("E2", 6, 5, &chain).unwrap();
+		assert_eq!(tracker.entries["E2"].cumulative_vote, 205);
+		assert_eq!(tracker.entries["A"].cumulative_vote, 615);
+		assert_eq!(tracker.entries[GENESIS_HASH].cumulative_vote, 615);
+		assert_eq!(tracker.find_ghost(None, |x| x >= &400), Some(("C", 4)));
+	}
+
+	#[test]
+	fn long_ancestor_edges_are_flagged() {
+		let mut chain = DummyChain::new();
+		let mut tracker = VoteGraph::new(GENESIS_HASH, 1, 0u32);
+		tracker.flag_sparse_votes(3);
+
+		chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E", "F", "G"]);
+
+		tracker.insert("C", 4, 1, &chain).unwrap();
+		assert_eq!(tracker.take_sparse_votes(), None);
+
+		// `G` is four blocks above `C`, the nearest vote-node.
+		tracker.insert("G", 8, 1, &chain).unwrap();
+		assert_eq!(tracker.take_sparse_votes(), Some(4));
+		assert_eq!(tracker.take_sparse_votes(), None);
+
+		// splitting the edge isn't flagged and the graph is unaffected.
+		tracker.insert("E", 6, 1, &chain).unwrap();
+		assert_eq!(tracker.take_sparse_votes(), None);
+		assert_eq!(tracker.entries["G"].ancestors, vec!["F", "E"]);
+		assert_eq!(tracker.find_ghost(None, |x| x >= &2), Some(("E", 6)));
+	}
 }
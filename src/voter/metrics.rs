@@ -0,0 +1,350 @@
+// Copyright 2018-2019 Parity Technologies (UK) Ltd
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Metrics of the voter in the Prometheus text format, available with the
+//! `prometheus` feature.
+//!
+//! `Metrics` only consumes the hooks of the `Environment`: call its methods of
+//! the same name from the corresponding hooks and serve the output of `render`
+//! on the metrics endpoint, or read the values with the getters to export them
+//! to a registry of the host.
+
+use std::{
+	fmt::Write,
+	sync::atomic::{AtomicU64, Ordering},
+	time::Duration,
+};
+
+use super::VoteDropReason;
+
+const DROP_REASONS: [(VoteDropReason, &str); 5] = [
+	(VoteDropReason::BelowBase, "below_base"),
+	(VoteDropReason::UnknownBlock, "unknown_block"),
+	(VoteDropReason::UnknownVoter, "unknown_voter"),
+	(VoteDropReason::Duplicate, "duplicate"),
+	(VoteDropReason::InvalidTarget, "invalid_target"),
+];
+
+/// Counters and gauges of the voter.
+#[derive(Debug, Default)]
+pub struct Metrics {
+	rounds_completed: AtomicU64,
+	round_completable_millis: AtomicU64,
+	finality_lag: AtomicU64,
+	votes_dropped: [AtomicU64; DROP_REASONS.len()],
+	prevote_equivocations: AtomicU64,
+	precommit_equivocations: AtomicU64,
+}
+
+impl Metrics {
+	/// Create metrics with all counters at zero.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Note that a round became completable, call from
+	/// `Environment::round_completable`.
+	pub fn round_completable(&self, _round: u64, elapsed: Duration) {
+		let millis = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+		self.round_completable_millis.store(millis, Ordering::Relaxed);
+	}
+
+	/// Note that a round was completed, call from `Environment::completed`.
+	pub fn completed(&self, _round: u64) {
+		self.rounds_completed.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// Note that a block was finalized, call from `Environment::finalize_block`
+	/// with the number of the best block to track the finality lag.
+	pub fn finalize_block(&self, number: u64, best_number: u64) {
+		self.finality_lag.store(best_number.saturating_sub(number), Ordering::Relaxed);
+	}
+
+	/// Note that a vote was dropped, call from `Environment::vote_dropped`.
+	pub fn vote_dropped(&self, _round: u64, reason: VoteDropReason) {
+		self.votes_dropped[Self::reason_index(reason)].fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// Note a prevote equivocation, call from `Environment::prevote_equivocation`.
+	pub fn prevote_equivocation(&self, _round: u64) {
+		self.prevote_equivocations.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// Note a precommit equivocation, call from
+	/// `Environment::precommit_equivocation`.
+	pub fn precommit_equivocation(&self, _round: u64) {
+		self.precommit_equivocations.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// The number of rounds completed.
+	pub fn rounds_completed(&self) -> u64 {
+		self.rounds_completed.load(Ordering::Relaxed)
+	}
+
+	/// The time it took the last completable round to become completable, in
+	/// milliseconds.
+	pub fn round_completable_millis(&self) -> u64 {
+		self.round_completable_millis.load(Ordering::Relaxed)
+	}
+
+	/// The number of blocks between the best block and the last finalized one,
+	/// as of the last finalized block.
+	pub fn finality_lag(&self) -> u64 {
+		self.finality_lag.load(Ordering::Relaxed)
+	}
+
+	/// The number of votes dropped for the given reason.
+	pub fn votes_dropped(&self, reason: VoteDropReason) -> u64 {
+		self.votes_dropped[Self::reason_index(reason)].load(Ordering::Relaxed)
+	}
+
+	/// The number of prevote and precommit equivocations.
+	pub fn equivocations(&self) -> (u64, u64) {
+		(
+			self.prevote_equivocations.load(Ordering::Relaxed),
+			self.precommit_equivocations.load(Ordering::Relaxed),
+		)
+	}
+
+	/// Render the metrics in the Prometheus text exposition format.
+	pub fn render(&self) -> String {
+		let mut out = String::new();
+		let mut metric = |name: &str, kind: &str, help: &str, values: &[(&str, u64)]| {
+			let _ = writeln!(out, "# HELP {} {}", name, help);
+			let _ = writeln!(out, "# TYPE {} {}", name, kind);
+			for (labels, value) in values {
+				let _ = writeln!(out, "{}{} {}", name, labels, value);
+			}
+		};
+
+		metric(
+			"grandpa_rounds_completed_total",
+			"counter",
+			"Number of rounds completed.",
+			&[("", self.rounds_completed())],
+		);
+		metric(
+			"grandpa_round_completable_milliseconds",
+			"gauge",
+			"Time from the start of the last completable round until it became completable.",
+			&[("", self.round_completable_millis())],
+		);
+		metric(
+			"grandpa_finality_lag_blocks",
+			"gauge",
+			"Number of blocks between the best and the last finalized block.",
+			&[("", self.finality_lag())],
+		);
+
+		let dropped = DROP_REASONS
+			.iter()
+			.map(|&(reason, label)| {
+				(format!("{{reason=\"{}\"}}", label), self.votes_dropped(reason))
+			})
+			.collect::<Vec<_>>();
+		metric(
+			"grandpa_votes_dropped_total",
+			"counter",
+			"Number of votes dropped, by reason.",
+			&dropped.iter().map(|(labels, value)| (&labels[..], *value)).collect::<Vec<_>>(),
+		);
+
+		let (prevotes, precommits) = self.equivocations();
+		metric(
+			"grandpa_equivocations_total",
+			"counter",
+			"Number of equivocations, by phase.",
+			&[("{phase=\"prevote\"}", prevotes), ("{phase=\"precommit\"}", precommits)],
+		);
+
+		out
+	}
+
+	fn reason_index(reason: VoteDropReason) -> usize {
+		DROP_REASONS
+			.iter()
+			.position(|&(r, _)| r == reason)
+			.expect("all reasons are listed; qed")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		testing::{
+			self,
+			chain::GENESIS_HASH,
+			environment::{Environment, Id, Signature},
+		},
+		voter::{RoundData, Voter},
+		voter_set::VoterSet,
+		Chain, Commit, Message, Precommit, Prevote, PrimaryPropose,
+	};
+	use futures::{executor::LocalPool, prelude::*, task::SpawnExt};
+	use std::sync::Arc;
+
+	// an environment feeding the metrics from its hooks.
+	struct MeteredEnvironment(Environment, Arc<Metrics>);
+
+	impl Chain<&'static str, u32> for MeteredEnvironment {
+		fn ancestry(
+			&self,
+			base: &'static str,
+			block: &'static str,
+		) -> Result<Vec<&'static str>, crate::Error> {
+			self.0.ancestry(base, block)
+		}
+	}
+
+	impl crate::voter::Environment<&'static str, u32> for MeteredEnvironment {
+		type Timer = <Environment as crate::voter::Environment<&'static str, u32>>::Timer;
+		type BestChain = <Environment as crate::voter::Environment<&'static str, u32>>::BestChain;
+		type Id = Id;
+		type Signature = Signature;
+		type In = <Environment as crate::voter::Environment<&'static str, u32>>::In;
+		type Out = <Environment as crate::voter::Environment<&'static str, u32>>::Out;
+		type Error = crate::Error;
+
+		fn best_chain_containing(&self, base: &'static str) -> Self::BestChain {
+			self.0.best_chain_containing(base)
+		}
+
+		fn round_data(&self, round: u64) -> RoundData<Self::Id, Self::Timer, Self::In, Self::Out> {
+			self.0.round_data(round)
+		}
+
+		fn round_commit_timer(&self) -> Self::Timer {
+			self.0.round_commit_timer()
+		}
+
+		fn proposed(
+			&self,
+			round: u64,
+			propose: PrimaryPropose<&'static str, u32>,
+		) -> Result<(), crate::Error> {
+			self.0.proposed(round, propose)
+		}
+
+		fn prevoted(
+			&self,
+			round: u64,
+			prevote: Prevote<&'static str, u32>,
+		) -> Result<(), crate::Error> {
+			self.0.prevoted(round, prevote)
+		}
+
+		fn precommitted(
+			&self,
+			round: u64,
+			precommit: Precommit<&'static str, u32>,
+		) -> Result<(), crate::Error> {
+			self.0.precommitted(round, precommit)
+		}
+
+		fn round_completable(&self, round: u64, elapsed: std::time::Duration) {
+			self.1.round_completable(round, elapsed);
+		}
+
+		fn completed(
+			&self,
+			round: u64,
+			state: crate::round::State<&'static str, u32>,
+			base: (&'static str, u32),
+			votes: &crate::HistoricalVotes<&'static str, u32, Signature, Id>,
+		) -> Result<(), crate::Error> {
+			self.1.completed(round);
+			self.0.completed(round, state, base, votes)
+		}
+
+		fn concluded(
+			&self,
+			round: u64,
+			state: crate::round::State<&'static str, u32>,
+			base: (&'static str, u32),
+			votes: &crate::HistoricalVotes<&'static str, u32, Signature, Id>,
+		) -> Result<(), crate::Error> {
+			self.0.concluded(round, state, base, votes)
+		}
+
+		fn finalize_block(
+			&self,
+			hash: &'static str,
+			number: u32,
+			round: u64,
+			commit: Commit<&'static str, u32, Signature, Id>,
+		) -> Result<(), crate::Error> {
+			let best = self.0.with_chain(|chain| chain.best_chain_containing(GENESIS_HASH));
+			self.1.finalize_block(number as u64, best.map_or(0, |(_, n)| n as u64));
+			self.0.finalize_block(hash, number, round, commit)
+		}
+
+		fn vote_dropped(&self, round: u64, reason: VoteDropReason) {
+			self.1.vote_dropped(round, reason);
+		}
+	}
+
+	#[test]
+	fn counts_voter_events() {
+		let local_id = Id(5);
+		let voters = VoterSet::new(std::iter::once((local_id, 100))).unwrap();
+
+		let (network, routing_task) = testing::environment::make_network();
+		let env = Environment::new(network.clone(), local_id);
+		let last_finalized = env.with_chain(|chain| {
+			chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+			chain.last_finalized()
+		});
+
+		let metrics = Arc::new(Metrics::new());
+		let finalized = env.finalized_stream();
+		let voter = Voter::new(
+			Arc::new(MeteredEnvironment(env, metrics.clone())),
+			voters,
+			network.make_global_comms(),
+			0,
+			Vec::new(),
+			last_finalized,
+			last_finalized,
+		);
+
+		// a voter outside of the set prevotes in the first round.
+		let (_, outgoing) = network.make_round_comms(1, Id(9));
+		let prevote = Message::Prevote(Prevote::new("E", 6));
+		let mut pool = LocalPool::new();
+		pool.run_until(Box::pin(outgoing).send(prevote)).unwrap();
+
+		pool.spawner().spawn(voter.map(|v| v.expect("Error voting"))).unwrap();
+		pool.spawner().spawn(routing_task).unwrap();
+
+		pool.run_until(
+			finalized
+				.take_while(|&(_, n, _)| future::ready(n < 6))
+				.for_each(|_| future::ready(())),
+		);
+		pool.run_until_stalled();
+
+		assert!(metrics.rounds_completed() >= 1);
+		assert_eq!(metrics.finality_lag(), 0);
+		assert_eq!(metrics.votes_dropped(VoteDropReason::UnknownVoter), 1);
+		assert_eq!(metrics.votes_dropped(VoteDropReason::Duplicate), 0);
+		assert_eq!(metrics.equivocations(), (0, 0));
+
+		let rendered = metrics.render();
+		assert!(rendered.contains("# TYPE grandpa_rounds_completed_total counter\n"));
+		assert!(rendered.contains("# TYPE grandpa_round_completable_milliseconds gauge\n"));
+		assert!(rendered.contains("grandpa_votes_dropped_total{reason=\"unknown_voter\"} 1\n"));
+		assert!(rendered.contains("grandpa_equivocations_total{phase=\"prevote\"} 0\n"));
+	}
+}
@@ -31,7 +31,7 @@ use futures::{
 	ready,
 };
 #[cfg(feature = "std")]
-use log::trace;
+use log::{debug, trace, warn};
 
 use parking_lot::Mutex;
 
@@ -40,19 +40,33 @@ use std::{
 	hash::Hash,
 	pin::Pin,
 	sync::Arc,
-	task::{Context, Poll},
+	task::{Context, Poll, Waker},
+	time::Duration,
 };
 
 use crate::{
-	round::State as RoundState, validate_commit, voter_set::VoterSet, weights::VoteWeight,
+	round::State as RoundState,
+	validate_commit,
+	voter_set::{PrimarySelector, RoundRobin, VoterSet},
+	weights::VoteWeight,
 	BlockNumberOps, CatchUp, Chain, Commit, CommitValidationResult, CompactCommit, Equivocation,
-	HistoricalVotes, Message, Precommit, Prevote, PrimaryPropose, SignedMessage,
+	HistoricalVotes, InvariantLevel, Message, Precommit, Prevote, PrimaryPropose, SignedMessage,
 };
 use past_rounds::PastRounds;
-use voting_round::{State as VotingRoundState, VotingRound};
+use persistence::{CompletedRound, Persistence, VoterSnapshot};
+use voting_round::{BestChainCache, State as VotingRoundState, VotingRound};
+use voting_rule::VotingRule;
 
+// the number of finalized blocks for which the finalizing round is kept, see
+// `Voter::finalization_round`.
+const FINALIZATION_ROUNDS_KEPT: usize = 1024;
+
+#[cfg(feature = "prometheus")]
+pub mod metrics;
 mod past_rounds;
+pub mod persistence;
 mod voting_round;
+pub mod voting_rule;
 
 /// Necessary environment for a voter.
 ///
@@ -79,9 +93,35 @@ pub trait Environment<H: Eq, N: BlockNumberOps>: Chain<H, N> {
 	/// Return a future that will resolve to the hash of the best block whose chain
 	/// contains the given block hash, even if that block is `base` itself.
 	///
-	/// If `base` is unknown the future outputs `None`.
+	/// If `base` is unknown the future outputs `None`, see
+	/// [`Self::best_chain_retry_timer`].
+	///
+	/// Ties between equally good chains must be broken deterministically, e.g.
+	/// by the lowest hash, so that all voters with the same view of the chain
+	/// prevote for the same block. Voters which pick among them arbitrarily
+	/// split their prevotes, which can stall finality at the fork point.
 	fn best_chain_containing(&self, base: H) -> Self::BestChain;
 
+	/// A counter which changes whenever the result of `best_chain_containing`
+	/// may have changed, e.g. when a new best block is imported or a block is
+	/// finalized.
+	///
+	/// When this returns `Some`, the voter caches the result of
+	/// `best_chain_containing` and reuses it while the generation is unchanged.
+	/// By default `None` is returned and nothing is cached.
+	fn best_block_generation(&self) -> Option<u64> {
+		None
+	}
+
+	/// Return the rule restricting the target of our prevotes, which is
+	/// otherwise the head of the chain returned by `best_chain_containing`,
+	/// see [`VotingRule`].
+	///
+	/// By default the target isn't restricted.
+	fn voting_rule(&self) -> Option<&dyn VotingRule<H, N>> {
+		None
+	}
+
 	/// Produce data necessary to start a round of voting. This may also be called
 	/// with the round number of the most recently completed round, in which case
 	/// it should yield a valid input stream.
@@ -103,6 +143,53 @@ pub trait Environment<H: Eq, N: BlockNumberOps>: Chain<H, N> {
 	/// signatures is flexible and can be maintained outside this crate.
 	fn round_data(&self, round: u64) -> RoundData<Self::Id, Self::Timer, Self::In, Self::Out>;
 
+	/// Return a timer to wait for before prevoting in the given round, when the
+	/// prevote timer fired but the round isn't completable yet. The number of
+	/// times the timer was already re-armed in the round is given.
+	///
+	/// This allows to spread out the prevotes of large voter sets with a short
+	/// jittered delay. It only applies before we prevote, so we never prevote
+	/// twice. By default the timer isn't re-armed and we prevote right away.
+	fn rearm_prevote_timer(&self, _round: u64, _rearmed: usize) -> Option<Self::Timer> {
+		None
+	}
+
+	/// Return a timer to wait for before querying `best_chain_containing`
+	/// again, when it output `None` for the block we must prevote on in the
+	/// given round. The number of times the query was already retried in the
+	/// round is given.
+	///
+	/// This allows to wait for a block which isn't synced yet instead of
+	/// abstaining from the round. By default the query isn't retried and we
+	/// neither prevote nor precommit in the round.
+	fn best_chain_retry_timer(
+		&self,
+		_round: u64,
+		_base: (H, N),
+		_retries: usize,
+	) -> Option<Self::Timer> {
+		None
+	}
+
+	/// Return a timer bounding the wait for `best_chain_containing` when
+	/// prevoting on the given block in the given round. Once it fires, the
+	/// best chain is considered unknown, see [`Self::best_chain_retry_timer`].
+	///
+	/// The wait is always bounded by the precommit timer of the round, so that
+	/// a chain backend which hangs doesn't make the voter miss the precommit
+	/// phase. This allows to give up earlier, e.g. to leave time for a retry.
+	/// By default there is no earlier deadline.
+	fn best_chain_deadline(&self, _round: u64, _base: (H, N)) -> Option<Self::Timer> {
+		None
+	}
+
+	/// Return the strategy selecting the primary voter of each round, which
+	/// must be the same for all voters. By default voters are selected
+	/// round-robin.
+	fn primary_selector(&self) -> &dyn PrimarySelector<Self::Id> {
+		&RoundRobin
+	}
+
 	/// Return a timer that will be used to delay the broadcast of a commit
 	/// message. This delay should not be static to minimize the amount of
 	/// commit messages that are sent (e.g. random value in [0, 1] seconds).
@@ -117,6 +204,15 @@ pub trait Environment<H: Eq, N: BlockNumberOps>: Chain<H, N> {
 	/// Note that we have precommitted in the given round.
 	fn precommitted(&self, round: u64, precommit: Precommit<H, N>) -> Result<(), Self::Error>;
 
+	/// Note that a round has become completable, the given time after it was
+	/// started. This is called at most once per round, before `completed`.
+	/// Rounds which are skipped by catching up aren't noted.
+	///
+	/// The round can only be completed once the previous round's estimate is
+	/// finalized too, so together with `completed` this allows to tell slow
+	/// voting apart from slow finality of the previous round.
+	fn round_completable(&self, _round: u64, _elapsed: Duration) {}
+
 	/// Note that a round is completed. This is called when a round has been
 	/// voted in and the next round can start. The round may continue to be run
 	/// in the background until _concluded_.
@@ -153,18 +249,227 @@ pub trait Environment<H: Eq, N: BlockNumberOps>: Chain<H, N> {
 		commit: Commit<H, N, Self::Signature, Self::Id>,
 	) -> Result<(), Self::Error>;
 
-	/// Note that an equivocation in prevotes has occurred.
+	/// Return the storage used to persist the votes cast by the local voter and
+	/// the rounds it has completed, see [`Persistence`]. Without it, a voter
+	/// restarted in the middle of a round may equivocate.
+	///
+	/// By default nothing is persisted.
+	fn persistence(
+		&self,
+	) -> Option<&dyn Persistence<H, N, Self::Signature, Self::Id, Error = Self::Error>> {
+		None
+	}
+
+	/// The number of consecutive rounds which finalize nothing beyond their
+	/// base, e.g. because the voters are partitioned on different forks, after
+	/// which `view_change` is called. By default it is never called.
+	fn view_change_threshold(&self) -> Option<usize> {
+		None
+	}
+
+	/// Note that the given round was the last of `view_change_threshold`
+	/// consecutive rounds which only finalized their base. This is only called
+	/// by voters, observers don't request view changes since they don't vote.
+	///
+	/// This should prompt the voters to re-sync their views before the next
+	/// round, e.g. by broadcasting a request for peers to exchange their best
+	/// blocks. The next round is started right after this returns, voting on
+	/// the best chain as it is then. The count restarts afterwards, so this is
+	/// called again if the stall persists. Finality still requires
+	/// supermajority precommits, so this doesn't affect safety.
+	fn view_change(&self, _round: u64) {}
+
+	/// Whether the local voter can sign votes in the given round, e.g. `false`
+	/// after its key was rotated out or its signing device became unavailable.
+	///
+	/// This is checked before casting each vote. When signing is unavailable
+	/// the voter casts no further votes in the round, as if it were an
+	/// observer, and `signing_unavailable` is called. By default signing is
+	/// always available.
+	fn can_sign(&self, _round: u64) -> bool {
+		true
+	}
+
+	/// Note that we stopped voting in the given round since signing became
+	/// unavailable, see `can_sign`. Since we only stop casting votes, this
+	/// can't cause an equivocation. Signing is checked again in the next round.
+	fn signing_unavailable(&self, _round: u64) {}
+
+	/// Whether an error of the outgoing sink of a round is a failure to sign a
+	/// vote, e.g. `Error::SigningFailed`. `can_sign` is only checked before
+	/// casting a vote, so signing can still fail when the sink signs it. The
+	/// vote is then skipped and the voter stops voting in the round as if
+	/// signing was unavailable, rather than failing. By default no error is a
+	/// signing failure.
+	fn is_signing_failure(&self, _error: &Self::Error) -> bool {
+		false
+	}
+
+	/// The number of consecutive blocks without votes below a vote, above the
+	/// nearest block voted on in the round, after which `sparse_votes` is
+	/// called. By default it is never called.
+	fn sparse_votes_depth(&self) -> Option<usize> {
+		None
+	}
+
+	/// Note that a vote was imported in the given round for a block the given
+	/// depth above the nearest block voted on, see `sparse_votes_depth`.
+	///
+	/// Long voteless spans indicate that intermediate votes never arrived,
+	/// e.g. because of a partial network partition. This is only diagnostic,
+	/// the vote is counted as usual.
+	fn sparse_votes(&self, _round: u64, _depth: usize) {}
+
+	/// Whether the given block has been imported.
+	///
+	/// Votes for blocks which haven't been imported yet are kept until
+	/// `Voter::block_imported` is called for them instead of being dropped, up
+	/// to one prevote and one precommit per voter in each round and at most
+	/// `max_pending_votes` in total. By default all blocks are considered
+	/// imported, so votes for unknown blocks are dropped.
+	fn is_block_known(&self, _block: &H) -> bool {
+		true
+	}
+
+	/// The number of votes for blocks which haven't been imported yet that are
+	/// kept in each round, see `is_block_known`. When the limit is reached the
+	/// oldest kept vote is dropped to make room for a new one, since votes for
+	/// blocks which are never imported, e.g. garbage hashes, are kept longest.
+	///
+	/// By default the votes kept are only limited per voter.
+	fn max_pending_votes(&self) -> Option<usize> {
+		None
+	}
+
+	/// Whether the signatures of the votes loaded from `persistence` should be
+	/// verified again with `verify_persisted_vote` when resuming the voter with
+	/// `Voter::resume`. This protects against acting on corrupted storage and
+	/// can be disabled when the storage is trusted.
+	///
+	/// By default persisted votes are trusted.
+	fn reverify_persisted_votes(&self) -> bool {
+		false
+	}
+
+	/// Verify the signature of a vote cast in the given round which was loaded
+	/// from `persistence`, see `reverify_persisted_votes`.
+	fn verify_persisted_vote(
+		&self,
+		_round: u64,
+		_vote: &SignedMessage<H, N, Self::Signature, Self::Id>,
+	) -> bool {
+		true
+	}
+
+	/// Note that we precommitted in the given round after waiting for the
+	/// prevote-GHOST to include the previous round's estimate, as required by
+	/// the protocol, for the given duration.
+	///
+	/// On well-connected networks this wait is rare. If it is regularly close
+	/// to or longer than the precommit timer returned by `round_data`, that timer
+	/// can be shortened since it rarely determines when we precommit, and vice
+	/// versa. The number of polls held back by the wait is reported in
+	/// `report::RoundState::precommit_gated`.
+	fn precommit_waited(&self, _round: u64, _waited: Duration) {}
+
+	/// Whether the voter should stop with `Error::MassiveEquivocation` when the
+	/// previous round's prevote-GHOST is not a descendent of its estimate, which
+	/// is only possible if a massive equivocation has taken place.
+	///
+	/// By default the voter logs a warning and prevotes for the best chain
+	/// containing the previous round's estimate.
+	fn halt_on_massive_equivocation(&self) -> bool {
+		false
+	}
+
+	/// The number of times a round may be polled without casting a prevote,
+	/// after the previous round is known, before it is considered stuck and
+	/// `round_stuck` is called.
+	///
+	/// A round may be legitimately polled several times while waiting for the
+	/// prevote timer, so this should be set well above the number of votes
+	/// expected in a round. By default rounds are never considered stuck.
+	fn round_stuck_threshold(&self) -> Option<usize> {
+		None
+	}
+
+	/// Note that the given round is stuck waiting to prevote, see
+	/// `round_stuck_threshold`. This is called at most once per round.
+	///
+	/// Returning `true` makes the voter immediately prevote for the base of the
+	/// round, which is always safe but doesn't help make progress beyond it.
+	fn round_stuck(&self, _round: u64, _state: RoundState<H, N>) -> bool {
+		false
+	}
+
+	/// Note that the voter paused before starting the given round, see
+	/// `Voter::pause_at`. This is called once per pause.
+	fn paused_at(&self, _round: u64) {}
+
+	/// Return a timer after which `voter_silent` is called for the given voter
+	/// in the given round, unless the voter sent a vote in it meanwhile. It's
+	/// requested at the start of the round and again with each of the voter's
+	/// votes. Voters which already precommitted, the local voter, and rounds
+	/// which are completable aren't watched. By default voters are never
+	/// considered silent.
+	fn voter_silence_timer(&self, _round: u64, _id: &Self::Id) -> Option<Self::Timer> {
+		None
+	}
+
+	/// Note that the given voter has been silent in the given round until its
+	/// timer fired, see `voter_silence_timer`. This is called at most once per
+	/// voter and round, and doesn't affect voting.
+	fn voter_silent(&self, _round: u64, _id: Self::Id) {}
+
+	/// Note that the given voter misbehaved in the given round, e.g. by sending
+	/// a primary proposal without being the primary. The message is ignored,
+	/// this only allows the host to down-rank the voter.
+	fn note_misbehaving_voter(&self, _round: u64, _id: Self::Id) {}
+
+	/// When the voter checks costly consensus invariants, see `InvariantLevel`.
+	/// Defaults to checking them only in debug builds.
+	fn invariant_level(&self) -> InvariantLevel {
+		InvariantLevel::default()
+	}
+
+	/// Note that a vote received in the given round was dropped for the given
+	/// reason. By default it is ignored.
+	fn vote_dropped(&self, _round: u64, _reason: VoteDropReason) {}
+
+	/// Note that an equivocation in prevotes has occurred. By default it is
+	/// ignored.
 	fn prevote_equivocation(
 		&self,
-		round: u64,
-		equivocation: Equivocation<Self::Id, Prevote<H, N>, Self::Signature>,
-	);
-	/// Note that an equivocation in precommits has occurred.
+		_round: u64,
+		_equivocation: Equivocation<Self::Id, Prevote<H, N>, Self::Signature>,
+	) {
+	}
+
+	/// Note that an equivocation in precommits has occurred. By default it is
+	/// ignored.
 	fn precommit_equivocation(
 		&self,
-		round: u64,
-		equivocation: Equivocation<Self::Id, Precommit<H, N>, Self::Signature>,
-	);
+		_round: u64,
+		_equivocation: Equivocation<Self::Id, Precommit<H, N>, Self::Signature>,
+	) {
+	}
+}
+
+/// The reason a vote was dropped, see `Environment::vote_dropped`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoteDropReason {
+	/// The vote targets a block which isn't a descendent of the round base.
+	BelowBase,
+	/// The vote targets a block which hasn't been imported and it can't be kept
+	/// until it is, see `Environment::is_block_known`.
+	UnknownBlock,
+	/// The vote was cast by a voter outside of the voter set.
+	UnknownVoter,
+	/// The vote was already received.
+	Duplicate,
+	/// The vote targets a block which couldn't be added to the vote-graph, e.g.
+	/// because the target number doesn't match the block.
+	InvalidTarget,
 }
 
 /// Communication between nodes that is not round-localized.
@@ -216,6 +521,7 @@ pub struct BadCommit {
 	num_duplicated_precommits: usize,
 	num_equivocations: usize,
 	num_invalid_voters: usize,
+	num_invalid_signatures: usize,
 }
 
 impl BadCommit {
@@ -238,6 +544,11 @@ impl BadCommit {
 	pub fn num_invalid_voters(&self) -> usize {
 		self.num_invalid_voters
 	}
+
+	/// Get the number of precommits with an invalid signature
+	pub fn num_invalid_signatures(&self) -> usize {
+		self.num_invalid_signatures
+	}
 }
 
 impl From<CommitValidationResult> for BadCommit {
@@ -247,6 +558,7 @@ impl From<CommitValidationResult> for BadCommit {
 			num_duplicated_precommits: r.num_duplicated_precommits,
 			num_equivocations: r.num_equivocations,
 			num_invalid_voters: r.num_invalid_voters,
+			num_invalid_signatures: r.num_invalid_signatures,
 			_priv: (),
 		}
 	}
@@ -346,6 +658,12 @@ pub struct RoundData<Id, Timer, Input, Output> {
 	pub voter_id: Option<Id>,
 	/// Timer before prevotes can be cast. This should be Start + 2T
 	/// where T is the gossip time estimate.
+	///
+	/// In large voter sets the timers can be jittered by a fraction of T, so
+	/// that the voters don't all broadcast their votes at the same instant,
+	/// see also `Environment::rearm_prevote_timer`. It isn't waited for when
+	/// we caught up to the previous round and its estimate is already
+	/// finalized.
 	pub prevote_timer: Timer,
 	/// Timer before precommits can be cast. This should be Start + 4T
 	pub precommit_timer: Timer,
@@ -355,6 +673,13 @@ pub struct RoundData<Id, Timer, Input, Output> {
 	pub outgoing: Output,
 }
 
+// a sink queueing the items pushed to it until the inner sink accepts them,
+// so that a congested network doesn't keep the voter from processing incoming
+// messages while its own votes and commits are waiting to be sent.
+//
+// the queue is unbounded, but a round only pushes the few votes it casts
+// itself, and votes are saved to the environment's `persistence` before
+// being pushed, so they are persisted even while they are still queued.
 struct Buffered<S, I> {
 	inner: S,
 	buffer: VecDeque<I>,
@@ -371,6 +696,11 @@ impl<S: Sink<I> + Unpin, I> Buffered<S, I> {
 		self.buffer.push_back(item);
 	}
 
+	// whether all items were passed to the sink.
+	fn is_empty(&self) -> bool {
+		self.buffer.is_empty()
+	}
+
 	// returns ready when the sink and the buffer are completely flushed.
 	fn poll(&mut self, cx: &mut Context) -> Poll<Result<(), S::Error>> {
 		let polled = self.schedule_all(cx)?;
@@ -435,7 +765,7 @@ where
 		last_round.handle_vote(vote).ok()?;
 	}
 
-	if last_round.round_state().completable {
+	if last_round.completable() {
 		Some(last_round)
 	} else {
 		None
@@ -463,12 +793,12 @@ where
 /// given block and includes a set of precommits as proof.
 ///
 /// - When a round is completable and we precommitted we start a commit timer
-/// and start accepting commit messages;
+///   and start accepting commit messages;
 /// - When we receive a commit message if it targets a block higher than what
-/// we've finalized we validate it and import its precommits if valid;
+///   we've finalized we validate it and import its precommits if valid;
 /// - When our commit timer triggers we check if we've received any commit
-/// message for a block equal to what we've finalized, if we haven't then we
-/// broadcast a commit.
+///   message for a block equal to what we've finalized, if we haven't then we
+///   broadcast a commit.
 ///
 /// Additionally, we also listen to commit messages from rounds that aren't
 /// currently running, we validate the commit and dispatch a finalization
@@ -484,13 +814,30 @@ where
 	voters: VoterSet<E::Id>,
 	inner: Arc<Mutex<InnerVoterState<H, N, E>>>,
 	finalized_notifications: UnboundedReceiver<FinalizedNotification<H, N, E>>,
-	last_finalized_number: N,
+	// the last block given to `Environment::finalize_block` or noted as
+	// finalized, whether by a round or an imported commit.
+	last_finalized: (H, N),
 	global_in: GlobalIn,
 	global_out: Buffered<GlobalOut, CommunicationOut<H, N, E::Signature, E::Id>>,
 	// the commit protocol might finalize further than the current round (if we're
 	// behind), we keep track of last finalized in round so we don't violate any
 	// assumptions from round-to-round.
 	last_finalized_in_rounds: (H, N),
+	// the result of the last best chain query, shared by all rounds.
+	best_chain_cache: Arc<BestChainCache<H, N>>,
+	// subscriber to the commits of finalized blocks, see `Voter::commits`.
+	commit_sender: Option<mpsc::Sender<Commit<H, N, E::Signature, E::Id>>>,
+	// consecutive completed rounds which didn't finalize beyond their base.
+	stalled_rounds: usize,
+	// the most recent blocks given to `Environment::finalize_block` with the
+	// rounds they were finalized in, in increasing order.
+	finalization_rounds: VecDeque<(H, u64)>,
+	// the round not to start until unpaused, see `Voter::pause_at`.
+	pause_at: Option<u64>,
+	// the task to wake on `Voter::unpause`, set once the voter has paused.
+	paused: Option<Waker>,
+	// whether we never vote, see `Voter::new_observer`.
+	observer: bool,
 }
 
 impl<'a, H: 'a, N, E: 'a, GlobalIn, GlobalOut> Voter<H, N, E, GlobalIn, GlobalOut>
@@ -505,7 +852,7 @@ where
 	pub fn voter_state(&self) -> Box<dyn VoterState<E::Id> + 'a + Send + Sync>
 	where
 		<E as Environment<H, N>>::Signature: Send,
-		<E as Environment<H, N>>::Id: Hash + Send,
+		<E as Environment<H, N>>::Id: Hash + Send + Sync,
 		<E as Environment<H, N>>::Timer: Send,
 		<E as Environment<H, N>>::Out: Send,
 		<E as Environment<H, N>>::In: Send,
@@ -528,6 +875,10 @@ where
 	/// should be provided. When available, all messages required to complete
 	/// the last round should be provided.
 	///
+	/// When starting from the genesis state the first round doesn't need a
+	/// previous round estimate, it votes on the best chain containing the given
+	/// base as if the base was finalized in round 0.
+	///
 	/// The input stream for commit messages should provide commits which
 	/// correspond to known blocks only (including all its precommits). It
 	/// is also responsible for validating the signature data in commit
@@ -540,16 +891,69 @@ where
 		last_round_votes: Vec<SignedMessage<H, N, E::Signature, E::Id>>,
 		last_round_base: (H, N),
 		last_finalized: (H, N),
+	) -> Self {
+		Voter::start(
+			env,
+			voters,
+			global_comms,
+			last_round_number,
+			last_round_votes,
+			last_round_base,
+			last_finalized,
+			false,
+		)
+	}
+
+	/// Create a new `Voter` which follows the rounds and the blocks they
+	/// finalize without ever voting, e.g. for a light client or an archive
+	/// node. The arguments are the same as for `Voter::new`.
+	///
+	/// Unlike a voter whose local voter id isn't part of the voter set, an
+	/// observer doesn't vote even if the id given in the `RoundData` is, e.g.
+	/// when it's misconfigured to share the key of a voter.
+	pub fn new_observer(
+		env: Arc<E>,
+		voters: VoterSet<E::Id>,
+		global_comms: (GlobalIn, GlobalOut),
+		last_round_number: u64,
+		last_round_votes: Vec<SignedMessage<H, N, E::Signature, E::Id>>,
+		last_round_base: (H, N),
+		last_finalized: (H, N),
+	) -> Self {
+		Voter::start(
+			env,
+			voters,
+			global_comms,
+			last_round_number,
+			last_round_votes,
+			last_round_base,
+			last_finalized,
+			true,
+		)
+	}
+
+	#[allow(clippy::too_many_arguments)]
+	fn start(
+		env: Arc<E>,
+		voters: VoterSet<E::Id>,
+		global_comms: (GlobalIn, GlobalOut),
+		last_round_number: u64,
+		last_round_votes: Vec<SignedMessage<H, N, E::Signature, E::Id>>,
+		last_round_base: (H, N),
+		last_finalized: (H, N),
+		observer: bool,
 	) -> Self {
 		let (finalized_sender, finalized_notifications) = mpsc::unbounded();
-		let last_finalized_number = last_finalized.1;
 
 		// re-start the last round and queue all messages to be processed on first poll.
 		// keep it in the background so we can push the estimate backwards until finalized
 		// by actually waiting for more messages.
 		let mut past_rounds = PastRounds::new();
-		let mut last_round_state =
-			crate::bridge_state::bridge_state(RoundState::genesis(last_round_base.clone())).1;
+		let mut last_round_state = crate::bridge_state::bridge_state(RoundState {
+			round: last_round_number,
+			..RoundState::genesis(last_round_base.clone())
+		})
+		.1;
 
 		if last_round_number > 0 {
 			let maybe_completed_last_round = instantiate_last_round(
@@ -572,12 +976,15 @@ where
 			// round of a set.
 		}
 
-		let best_round = VotingRound::new(
+		let best_chain_cache = Arc::new(BestChainCache::new());
+		let new_round = if observer { VotingRound::new_observer } else { VotingRound::new };
+		let best_round = new_round(
 			last_round_number + 1,
 			voters.clone(),
 			last_finalized.clone(),
 			Some(last_round_state),
 			finalized_sender,
+			best_chain_cache.clone(),
 			env.clone(),
 		);
 
@@ -590,10 +997,79 @@ where
 			voters,
 			inner,
 			finalized_notifications,
-			last_finalized_number,
+			last_finalized: last_finalized.clone(),
 			last_finalized_in_rounds: last_finalized,
 			global_in,
 			global_out: Buffered::new(global_out),
+			best_chain_cache,
+			commit_sender: None,
+			stalled_rounds: 0,
+			finalization_rounds: VecDeque::new(),
+			pause_at: None,
+			paused: None,
+			observer,
+		}
+	}
+
+	/// Resume a voter from the last completed round saved to the environment's
+	/// `persistence`, or start it from the given last finalized block if there
+	/// is none.
+	///
+	/// When `Environment::reverify_persisted_votes` is set, the signatures of
+	/// the persisted votes are verified and `Error::CorruptPersistence` is
+	/// returned if any of them is invalid.
+	pub fn resume(
+		env: Arc<E>,
+		voters: VoterSet<E::Id>,
+		global_comms: (GlobalIn, GlobalOut),
+		last_finalized: (H, N),
+	) -> Result<Self, E::Error> {
+		let (last_round_number, last_round_votes, last_round_base) =
+			Self::load_last_round(&*env, last_finalized.clone())?;
+
+		Ok(Voter::new(
+			env,
+			voters,
+			global_comms,
+			last_round_number,
+			last_round_votes,
+			last_round_base,
+			last_finalized,
+		))
+	}
+
+	// load the number, votes and base of the last completed round from the
+	// environment's `persistence`, see `Voter::resume`.
+	#[allow(clippy::type_complexity)]
+	fn load_last_round(
+		env: &E,
+		last_finalized: (H, N),
+	) -> Result<(u64, Vec<SignedMessage<H, N, E::Signature, E::Id>>, (H, N)), E::Error> {
+		let completed = match env.persistence() {
+			Some(persistence) => persistence.load_completed()?,
+			None => None,
+		};
+
+		match completed {
+			Some(completed) => {
+				let votes = completed.votes.seen().to_vec();
+				if env.reverify_persisted_votes() {
+					if let Some(vote) =
+						votes.iter().find(|vote| !env.verify_persisted_vote(completed.number, vote))
+					{
+						warn!(target: "afg", "Persisted vote for {:?} by {:?} in round {} has an invalid signature",
+							vote.target(),
+							vote.id,
+							completed.number,
+						);
+
+						return Err(crate::Error::CorruptPersistence.into())
+					}
+				}
+
+				Ok((completed.number, votes, completed.base))
+			},
+			None => Ok((0, Vec::new(), last_finalized)),
 		}
 	}
 
@@ -610,9 +1086,26 @@ where
 			}
 		}
 
-		while let Poll::Ready(res) =
-			Stream::poll_next(Pin::new(&mut self.finalized_notifications), cx)
-		{
+		self.process_finalized_notifications(cx)
+	}
+
+	// give the blocks finalized by rounds to the environment.
+	fn process_finalized_notifications(&mut self, cx: &mut Context) -> Result<(), E::Error> {
+		loop {
+			// don't take any notifications until there is room for the commit.
+			if let Some(ref mut sender) = self.commit_sender {
+				match sender.poll_ready(cx) {
+					Poll::Ready(Ok(())) => {},
+					Poll::Ready(Err(_)) => self.commit_sender = None,
+					Poll::Pending => break,
+				}
+			}
+
+			let res = match Stream::poll_next(Pin::new(&mut self.finalized_notifications), cx) {
+				Poll::Ready(res) => res,
+				Poll::Pending => break,
+			};
+
 			let inner = self.inner.clone();
 			let mut inner = inner.lock();
 
@@ -621,8 +1114,13 @@ where
 
 			inner.past_rounds.update_finalized(f_num);
 
-			if self.set_last_finalized_number(f_num) {
-				self.env.finalize_block(f_hash.clone(), f_num, round, commit)?;
+			if self.set_last_finalized(f_hash.clone(), f_num)? {
+				if let Some(ref mut sender) = self.commit_sender {
+					// the receiver may have been dropped since it was polled.
+					let _ = sender.start_send(commit.clone());
+				}
+
+				self.finalize_block(f_hash.clone(), f_num, round, commit)?;
 			}
 
 			if f_num > self.last_finalized_in_rounds.1 {
@@ -663,17 +1161,15 @@ where
 						let validation_result = validate_commit(&commit, &self.voters, &*self.env)?;
 
 						if validation_result.is_valid() {
-							// this can't be moved to a function because the compiler
-							// will complain about getting two mutable borrows to self
-							// (due to the call to `self.rounds.get_mut`).
-							let last_finalized_number = &mut self.last_finalized_number;
-
 							// clean up any background rounds
 							inner.past_rounds.update_finalized(commit.target_number);
+							drop(inner);
 
-							if commit.target_number > *last_finalized_number {
-								*last_finalized_number = commit.target_number;
-								self.env.finalize_block(
+							if self.set_last_finalized(
+								commit.target_hash.clone(),
+								commit.target_number,
+							)? {
+								self.finalize_block(
 									commit.target_hash.clone(),
 									commit.target_number,
 									round_number,
@@ -698,6 +1194,14 @@ where
 				CommunicationIn::CatchUp(catch_up, mut process_catch_up_outcome) => {
 					trace!(target: "afg", "Got catch-up message for round {}", catch_up.round_number);
 
+					// catching up would start the round after the one caught up to.
+					if self.pause_at.is_some_and(|round| catch_up.round_number + 1 >= round) {
+						trace!(target: "afg", "Ignoring catch-up to round {} while pausing", catch_up.round_number);
+
+						process_catch_up_outcome.run(CatchUpProcessingOutcome::Useless);
+						continue
+					}
+
 					let mut inner = self.inner.lock();
 
 					let round = if let Some(round) = validate_catch_up(
@@ -724,14 +1228,23 @@ where
 						self.env.clone(),
 					);
 
-					let new_best = VotingRound::new(
+					self.check_round_base(
+						&inner.best_round.dag_base(),
+						&self.last_finalized_in_rounds,
+					)?;
+
+					let new_round =
+						if self.observer { VotingRound::new_observer } else { VotingRound::new };
+					let mut new_best = new_round(
 						just_completed.round_number() + 1,
 						self.voters.clone(),
 						self.last_finalized_in_rounds.clone(),
 						Some(just_completed.bridge_state()),
 						inner.best_round.finalized_sender(),
+						self.best_chain_cache.clone(),
 						self.env.clone(),
 					);
+					new_best.note_caught_up();
 
 					// update last-finalized in rounds _after_ starting new round.
 					// otherwise the base could be too eagerly set forward.
@@ -741,12 +1254,7 @@ where
 						}
 					}
 
-					self.env.completed(
-						just_completed.round_number(),
-						just_completed.round_state(),
-						just_completed.dag_base(),
-						just_completed.historical_votes(),
-					)?;
+					just_completed.note_completed()?;
 
 					inner.past_rounds.push(&*self.env, just_completed);
 
@@ -786,6 +1294,17 @@ where
 				return Poll::Pending
 			}
 
+			let next_round = inner.best_round.round_number() + 1;
+			if self.pause_at == Some(next_round) {
+				if self.paused.is_none() {
+					debug!(target: "afg", "Pausing before starting round {}", next_round);
+					self.env.paused_at(next_round);
+				}
+
+				self.paused = Some(cx.waker().clone());
+				return Poll::Pending
+			}
+
 			trace!(target: "afg", "Best round at {} has become completable. Starting new best round at {}",
 				inner.best_round.round_number(),
 				inner.best_round.round_number() + 1,
@@ -801,21 +1320,36 @@ where
 	fn completed_best_round(&mut self) -> Result<(), E::Error> {
 		let mut inner = self.inner.lock();
 
-		self.env.completed(
-			inner.best_round.round_number(),
-			inner.best_round.round_state(),
-			inner.best_round.dag_base(),
-			inner.best_round.historical_votes(),
-		)?;
+		inner.best_round.note_completed()?;
 
 		let old_round_number = inner.best_round.round_number();
 
-		let next_round = VotingRound::new(
+		self.check_round_base(&inner.best_round.dag_base(), &self.last_finalized_in_rounds)?;
+
+		let base_number = inner.best_round.dag_base().1;
+		if inner.best_round.finalized().is_some_and(|f| f.1 > base_number) {
+			self.stalled_rounds = 0;
+		} else if inner.best_round.is_voting() {
+			self.stalled_rounds += 1;
+			if self.env.view_change_threshold().is_some_and(|t| self.stalled_rounds >= t) {
+				warn!(target: "afg", "Round {} is the {}th in a row to only finalize its base, requesting view change",
+					old_round_number,
+					self.stalled_rounds,
+				);
+
+				self.stalled_rounds = 0;
+				self.env.view_change(old_round_number);
+			}
+		}
+
+		let new_round = if self.observer { VotingRound::new_observer } else { VotingRound::new };
+		let next_round = new_round(
 			old_round_number + 1,
 			self.voters.clone(),
 			self.last_finalized_in_rounds.clone(),
 			Some(inner.best_round.bridge_state()),
 			inner.best_round.finalized_sender(),
+			self.best_chain_cache.clone(),
 			self.env.clone(),
 		);
 
@@ -824,89 +1358,590 @@ where
 		Ok(())
 	}
 
-	fn set_last_finalized_number(&mut self, finalized_number: N) -> bool {
-		let last_finalized_number = &mut self.last_finalized_number;
-		if finalized_number > *last_finalized_number {
-			*last_finalized_number = finalized_number;
-			return true
+	// the base of each round we start must be equal to or a descendent of the
+	// base of the round it replaces, otherwise it could never agree on finality
+	// with a correctly configured voter.
+	fn check_round_base(&self, previous: &(H, N), next: &(H, N)) -> Result<(), E::Error> {
+		if !self.env.invariant_level().is_enabled() {
+			return Ok(())
 		}
-		false
-	}
-}
 
-impl<H, N, E: Environment<H, N>, GlobalIn, GlobalOut> Future for Voter<H, N, E, GlobalIn, GlobalOut>
-where
-	H: Clone + Eq + Ord + ::std::fmt::Debug,
-	N: Copy + BlockNumberOps + ::std::fmt::Debug,
-	GlobalIn: Stream<Item = Result<CommunicationIn<H, N, E::Signature, E::Id>, E::Error>> + Unpin,
-	GlobalOut: Sink<CommunicationOut<H, N, E::Signature, E::Id>, Error = E::Error> + Unpin,
-{
-	type Output = Result<(), E::Error>;
+		if next.1 < previous.1 ||
+			!self.env.is_equal_or_descendent_of(previous.0.clone(), next.0.clone())
+		{
+			warn!(target: "afg", "Base of new round at {:?} regressed from previous round base at {:?}",
+				next,
+				previous,
+			);
 
-	fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), E::Error>> {
-		self.process_incoming(cx)?;
-		self.prune_background_rounds(cx)?;
-		let _ = self.global_out.poll(cx)?;
+			return Err(crate::Error::BaseRegression.into())
+		}
 
-		self.process_best_round(cx)
+		Ok(())
 	}
-}
-
-impl<H, N, E: Environment<H, N>, GlobalIn, GlobalOut> Unpin for Voter<H, N, E, GlobalIn, GlobalOut>
-where
-	H: Clone + Eq + Ord + ::std::fmt::Debug,
-	N: Copy + BlockNumberOps + ::std::fmt::Debug,
-	GlobalIn: Stream<Item = Result<CommunicationIn<H, N, E::Signature, E::Id>, E::Error>> + Unpin,
-	GlobalOut: Sink<CommunicationOut<H, N, E::Signature, E::Id>, Error = E::Error> + Unpin,
-{
-}
 
-/// Trait for querying the state of the voter. Used by `Voter` to return a queryable object
-/// without exposing too many data types.
-pub trait VoterState<Id: Eq + std::hash::Hash> {
-	/// Returns a plain data type, `report::VoterState`, describing the current state
-	/// of the voter relevant to the voting process.
-	fn get(&self) -> report::VoterState<Id>;
-}
+	/// Get a summary of the current voting round followed by the background
+	/// rounds in increasing round number.
+	pub fn rounds(&self) -> Vec<report::RoundSummary<H, N>> {
+		let summary = |round: &VotingRound<H, N, E>| report::RoundSummary {
+			number: round.round_number(),
+			phase: round.phase(),
+			finalized: round.finalized().cloned(),
+		};
 
-/// Contains a number of data transfer objects for reporting data to the outside world.
-pub mod report {
-	use crate::weights::{VoteWeight, VoterWeight};
-	use std::collections::{HashMap, HashSet};
+		let inner = self.inner.lock();
+		let mut background = inner.past_rounds.voting_rounds().map(summary).collect::<Vec<_>>();
+		background.sort_by_key(|round| round.number);
 
-	/// Basic data struct for the state of a round.
-	#[derive(PartialEq, Eq, Clone)]
-	#[cfg_attr(test, derive(Debug))]
-	pub struct RoundState<Id: Eq + std::hash::Hash> {
-		/// Total weight of all votes.
-		pub total_weight: VoterWeight,
-		/// The threshold voter weight.
-		pub threshold_weight: VoterWeight,
+		let mut rounds = vec![summary(&inner.best_round)];
+		rounds.extend(background);
+		rounds
+	}
 
-		/// Current weight of the prevotes.
-		pub prevote_current_weight: VoteWeight,
-		/// The identities of nodes that have cast prevotes so far.
-		pub prevote_ids: HashSet<Id>,
+	/// Get a catch-up proving the latest completable round the voter runs, to
+	/// answer the catch-up request of a voter which is lagging behind.
+	///
+	/// Returns `None` if none of the rounds is completable.
+	pub fn catch_up(&self) -> Option<CatchUp<H, N, E::Signature, E::Id>> {
+		let inner = self.inner.lock();
+
+		inner.best_round.catch_up().or_else(|| {
+			inner
+				.past_rounds
+				.voting_rounds()
+				.max_by_key(|round| round.round_number())
+				.and_then(|round| round.catch_up())
+		})
+	}
 
-		/// Current weight of the precommits.
-		pub precommit_current_weight: VoteWeight,
-		/// The identities of nodes that have cast precommits so far.
-		pub precommit_ids: HashSet<Id>,
+	/// Get a stream of the commits assembled by the voter, one for each block it
+	/// finalizes in increasing order.
+	///
+	/// The stream is backed by a channel with the given capacity. When it is
+	/// full the voter stops processing finalized blocks until the commits are
+	/// consumed, so none are dropped. Only the latest stream receives commits,
+	/// calling this again ends the previously returned stream.
+	pub fn commits(
+		&mut self,
+		capacity: usize,
+	) -> mpsc::Receiver<Commit<H, N, E::Signature, E::Id>> {
+		let (sender, receiver) = mpsc::channel(capacity);
+		self.commit_sender = Some(sender);
+		receiver
 	}
 
-	/// Basic data struct for the current state of the voter in a form suitable
-	/// for passing on to other systems.
-	#[derive(PartialEq, Eq)]
-	#[cfg_attr(test, derive(Debug))]
-	pub struct VoterState<Id: Eq + std::hash::Hash> {
-		/// Voting rounds running in the background.
-		pub background_rounds: HashMap<u64, RoundState<Id>>,
-		/// The current best voting round.
-		pub best_round: (u64, RoundState<Id>),
+	/// Shut the voter down, returning a snapshot of its state.
+	///
+	/// The voter stops processing incoming messages and casting votes. It gives
+	/// the blocks already finalized by rounds to the environment, sends the
+	/// votes and commits which are still queued and saves all completed rounds
+	/// to the environment's `persistence`, oldest first. Sending is abandoned
+	/// when the given `deadline` fires, so that an unresponsive network can't
+	/// hold up the shutdown.
+	///
+	/// Since votes are saved before they are sent, resuming the voter with
+	/// `Voter::resume` from the snapshot's last finalized block re-sends the
+	/// votes it cast instead of casting new ones. Without `persistence` the
+	/// votes of the snapshot's round aren't known after a restart, so the
+	/// voter may only be started again once that round is over.
+	#[allow(clippy::type_complexity)]
+	pub fn shutdown(
+		mut self,
+		mut deadline: E::Timer,
+	) -> impl Future<Output = Result<VoterSnapshot<H, N, E::Signature, E::Id>, E::Error>> {
+		future::poll_fn(move |cx| {
+			if self.poll_flush(cx)?.is_pending() {
+				match deadline.poll_unpin(cx) {
+					Poll::Ready(res) => {
+						res?;
+						warn!(target: "afg", "Giving up on sending queued messages after shutdown deadline");
+					},
+					Poll::Pending => return Poll::Pending,
+				}
+			}
+
+			Poll::Ready(self.checkpoint())
+		})
 	}
-}
 
-struct SharedVoterState<H, N, E>(Arc<Mutex<InnerVoterState<H, N, E>>>)
+	// finish the work which doesn't depend on incoming messages, ready once
+	// everything has been sent.
+	fn poll_flush(&mut self, cx: &mut Context) -> Result<Poll<()>, E::Error> {
+		self.process_finalized_notifications(cx)?;
+
+		let mut inner = self.inner.lock();
+		let best_round = inner.best_round.poll_flush(cx)?;
+		let past_rounds = inner.past_rounds.poll_flush(cx)?;
+		drop(inner);
+
+		let global_out = self.global_out.poll(cx)?;
+
+		Ok(if best_round.is_ready() && past_rounds.is_ready() && global_out.is_ready() {
+			Poll::Ready(())
+		} else {
+			Poll::Pending
+		})
+	}
+
+	// save the completed rounds to the persistence and take a snapshot.
+	fn checkpoint(&mut self) -> Result<VoterSnapshot<H, N, E::Signature, E::Id>, E::Error> {
+		let inner = self.inner.lock();
+
+		let mut completed = inner
+			.past_rounds
+			.voting_rounds()
+			.map(|round| CompletedRound {
+				number: round.round_number(),
+				state: round.round_state(),
+				base: round.dag_base(),
+				votes: round.historical_votes().clone(),
+			})
+			.collect::<Vec<_>>();
+		completed.sort_by_key(|round| round.number);
+
+		let round = inner.best_round.round_number();
+		let mut votes = Vec::new();
+		if let Some(persistence) = self.env.persistence() {
+			// background rounds keep collecting votes after they are completed.
+			for c in &completed {
+				persistence.save_completed(c.number, c.state.clone(), c.base.clone(), &c.votes)?;
+			}
+
+			votes = persistence.load_round(round)?;
+		}
+
+		Ok(VoterSnapshot {
+			last_finalized: self.last_finalized.clone(),
+			last_completed: completed.pop(),
+			round,
+			votes,
+		})
+	}
+
+	/// Note that a block has been finalized out-of-band, e.g. by importing a
+	/// justification during warp sync.
+	///
+	/// The block is used as the base of subsequent rounds and any background
+	/// rounds whose estimate it finalizes are discarded. The current round keeps
+	/// running on its existing base, but the blocks below the finalized one are
+	/// pruned from its vote-graph and those of the remaining background rounds,
+	/// see `Round::prune`. Returns `Error::NotDescendent` if the block is not
+	/// equal to or a descendent of the last block finalized in rounds, and
+	/// `Error::ConflictingFinality` if it conflicts with a higher block
+	/// finalized by a commit.
+	pub fn note_finalized(&mut self, hash: H, number: N) -> Result<(), E::Error> {
+		let (ref base_hash, base_number) = self.last_finalized_in_rounds;
+		if number < base_number ||
+			!self.env.is_equal_or_descendent_of(base_hash.clone(), hash.clone())
+		{
+			warn!(target: "afg", "Ignoring finalized block {:?} not descending from {:?}",
+				(&hash, number),
+				self.last_finalized_in_rounds,
+			);
+
+			return Err(crate::Error::NotDescendent.into())
+		}
+
+		self.set_last_finalized(hash.clone(), number)?;
+
+		{
+			let mut inner = self.inner.lock();
+			inner.best_round.prune(hash.clone(), number);
+			inner.past_rounds.prune(hash.clone(), number);
+			inner.past_rounds.update_finalized(number);
+		}
+
+		self.last_finalized_in_rounds = (hash, number);
+
+		Ok(())
+	}
+
+	/// Note that the given block has been imported, handling any votes for it
+	/// which were received before, see `Environment::is_block_known`.
+	pub fn block_imported(&mut self, hash: H) -> Result<(), E::Error> {
+		let mut inner = self.inner.lock();
+		inner.best_round.block_imported(&hash)?;
+		inner.past_rounds.block_imported(&hash)
+	}
+
+	/// Import a sequence of commits to finalize many blocks at once without
+	/// running the rounds in between, e.g. when warp syncing a new node.
+	///
+	/// Each commit is given with its round number and validated against the
+	/// voter set returned by `voters_by_round` for that round. Commits are
+	/// applied in order of their target number and each one must target a
+	/// descendent of the block finalized by the previous one, otherwise it is
+	/// skipped like any invalid commit. Only the highest valid commit is given to
+	/// `Environment::finalize_block`, after which its target is noted as
+	/// finalized with `note_finalized`. Returns the newly finalized block, if
+	/// any.
+	pub fn import_commits<I, F>(
+		&mut self,
+		commits: I,
+		mut voters_by_round: F,
+	) -> Result<Option<(H, N)>, E::Error>
+	where
+		I: IntoIterator<Item = (u64, Commit<H, N, E::Signature, E::Id>)>,
+		F: FnMut(u64) -> VoterSet<E::Id>,
+	{
+		let mut commits = commits.into_iter().collect::<Vec<_>>();
+		commits.sort_by_key(|(_, commit)| commit.target_number);
+
+		let mut finalized = self.last_finalized_in_rounds.clone();
+		let mut best_commit = None;
+		for (round_number, commit) in commits {
+			if commit.target_number <= finalized.1 || commit.target_number <= self.last_finalized.1
+			{
+				continue
+			}
+
+			if !self
+				.env
+				.is_equal_or_descendent_of(finalized.0.clone(), commit.target_hash.clone())
+			{
+				trace!(target: "afg", "Skipping commit for {:?} in round {} not descending from {:?}",
+					(&commit.target_hash, commit.target_number),
+					round_number,
+					finalized,
+				);
+				continue
+			}
+
+			let voters = voters_by_round(round_number);
+			if !validate_commit(&commit, &voters, &*self.env)?.is_valid() {
+				trace!(target: "afg", "Skipping invalid commit for {:?} in round {}",
+					(&commit.target_hash, commit.target_number),
+					round_number,
+				);
+				continue
+			}
+
+			finalized = (commit.target_hash.clone(), commit.target_number);
+			best_commit = Some((round_number, commit));
+		}
+
+		let (round_number, commit) = match best_commit {
+			Some(best_commit) => best_commit,
+			None => return Ok(None),
+		};
+
+		self.note_finalized(finalized.0.clone(), finalized.1)?;
+		self.finalize_block(finalized.0.clone(), finalized.1, round_number, commit)?;
+
+		Ok(Some(finalized))
+	}
+
+	/// Get the round which finalized the given block.
+	///
+	/// Only blocks given to `Environment::finalize_block` are known, not the
+	/// ancestors finalized along with them or blocks noted with
+	/// `note_finalized`, and only for the last 1024 finalized blocks.
+	pub fn finalization_round(&self, hash: &H) -> Option<u64> {
+		self.finalization_rounds
+			.iter()
+			.rev()
+			.find(|(h, _)| h == hash)
+			.map(|(_, round)| *round)
+	}
+
+	/// Pause the voter before it starts the given round, e.g. to upgrade all
+	/// nodes at the same round boundary.
+	///
+	/// The voter keeps voting until the previous round is completable and it
+	/// has precommitted in it, then calls `Environment::paused_at` instead of
+	/// starting the round. While pausing, the voter still imports votes and
+	/// commits for the rounds it runs, but ignores catch-ups to the round or
+	/// later. Returns `false`, without pausing, if the round has already been
+	/// started.
+	pub fn pause_at(&mut self, round: u64) -> bool {
+		if round <= self.inner.lock().best_round.round_number() {
+			return false
+		}
+
+		self.unpause();
+		self.pause_at = Some(round);
+		true
+	}
+
+	/// Stop pausing, see `pause_at`. A paused voter starts the round it paused
+	/// before.
+	pub fn unpause(&mut self) {
+		self.pause_at = None;
+		if let Some(waker) = self.paused.take() {
+			waker.wake();
+		}
+	}
+
+	// finalize a block with the environment, noting the round it was finalized in.
+	fn finalize_block(
+		&mut self,
+		hash: H,
+		number: N,
+		round: u64,
+		commit: Commit<H, N, E::Signature, E::Id>,
+	) -> Result<(), E::Error> {
+		if self.finalization_rounds.len() == FINALIZATION_ROUNDS_KEPT {
+			self.finalization_rounds.pop_front();
+		}
+		self.finalization_rounds.push_back((hash.clone(), round));
+
+		self.env.finalize_block(hash, number, round, commit)
+	}
+
+	// note that the given block was finalized by a round or a commit, returning
+	// whether it is higher than the last finalized block and should be given to
+	// `Environment::finalize_block`. rounds and commits may finalize different
+	// blocks concurrently, which is fine as long as they are on the same chain.
+	// a higher block conflicting with the last finalized one is an error, since
+	// finalizing both is a safety violation.
+	fn set_last_finalized(&mut self, hash: H, number: N) -> Result<bool, E::Error> {
+		let (ref last_hash, last_number) = self.last_finalized;
+		if number <= last_number {
+			return Ok(false)
+		}
+
+		if !self.env.is_equal_or_descendent_of(last_hash.clone(), hash.clone()) {
+			warn!(target: "afg", "Finalized block {:?} conflicts with last finalized block {:?}",
+				(&hash, number),
+				self.last_finalized,
+			);
+
+			return Err(crate::Error::ConflictingFinality.into())
+		}
+
+		self.last_finalized = (hash, number);
+		Ok(true)
+	}
+}
+
+/// A builder for a `Voter`.
+///
+/// The environment, voter set, global communication channels and last
+/// finalized block are required. By default the voter starts from the genesis
+/// state with the last finalized block as base, this can be changed by
+/// providing the last completed round with `last_round` or by resuming from
+/// the environment's `persistence` with `resume`. All other hooks are provided
+/// by the `Environment`.
+pub struct VoterBuilder<H: Eq, N: BlockNumberOps, E: Environment<H, N>, GlobalIn, GlobalOut> {
+	env: Arc<E>,
+	voters: VoterSet<E::Id>,
+	global_comms: (GlobalIn, GlobalOut),
+	last_finalized: (H, N),
+	last_round: Option<(u64, Vec<SignedMessage<H, N, E::Signature, E::Id>>, (H, N))>,
+	resume: bool,
+	commits_capacity: Option<usize>,
+	observer: bool,
+}
+
+impl<H, N, E: Environment<H, N>, GlobalIn, GlobalOut> VoterBuilder<H, N, E, GlobalIn, GlobalOut>
+where
+	H: Clone + Eq + Ord + ::std::fmt::Debug,
+	N: Copy + BlockNumberOps + ::std::fmt::Debug,
+	GlobalIn: Stream<Item = Result<CommunicationIn<H, N, E::Signature, E::Id>, E::Error>> + Unpin,
+	GlobalOut: Sink<CommunicationOut<H, N, E::Signature, E::Id>, Error = E::Error> + Unpin,
+{
+	/// Create a builder from the pieces required by every voter.
+	pub fn new(
+		env: Arc<E>,
+		voters: VoterSet<E::Id>,
+		global_comms: (GlobalIn, GlobalOut),
+		last_finalized: (H, N),
+	) -> Self {
+		VoterBuilder {
+			env,
+			voters,
+			global_comms,
+			last_finalized,
+			last_round: None,
+			resume: false,
+			commits_capacity: None,
+			observer: false,
+		}
+	}
+
+	/// Start from the given last completed round, see `Voter::new`.
+	pub fn last_round(
+		mut self,
+		number: u64,
+		votes: Vec<SignedMessage<H, N, E::Signature, E::Id>>,
+		base: (H, N),
+	) -> Self {
+		self.last_round = Some((number, votes, base));
+		self
+	}
+
+	/// Start from the last completed round saved to the environment's
+	/// `persistence`, see `Voter::resume`. Takes precedence over `last_round`.
+	pub fn resume(mut self) -> Self {
+		self.resume = true;
+		self
+	}
+
+	/// Subscribe to the commits assembled by the voter with a channel of the
+	/// given capacity, see `Voter::commits`. The stream is returned by `build`.
+	pub fn commits(mut self, capacity: usize) -> Self {
+		self.commits_capacity = Some(capacity);
+		self
+	}
+
+	/// Never vote, only follow the rounds, see `Voter::new_observer`.
+	pub fn observer(mut self) -> Self {
+		self.observer = true;
+		self
+	}
+
+	/// Build the voter, along with the stream of its commits if requested with
+	/// `commits`. Only fails when resuming from `persistence` fails.
+	#[allow(clippy::type_complexity)]
+	pub fn build(
+		self,
+	) -> Result<
+		(
+			Voter<H, N, E, GlobalIn, GlobalOut>,
+			Option<mpsc::Receiver<Commit<H, N, E::Signature, E::Id>>>,
+		),
+		E::Error,
+	> {
+		let (number, votes, base) = if self.resume {
+			Voter::<H, N, E, GlobalIn, GlobalOut>::load_last_round(
+				&*self.env,
+				self.last_finalized.clone(),
+			)?
+		} else {
+			self.last_round.unwrap_or_else(|| (0, Vec::new(), self.last_finalized.clone()))
+		};
+
+		let mut voter = Voter::start(
+			self.env,
+			self.voters,
+			self.global_comms,
+			number,
+			votes,
+			base,
+			self.last_finalized,
+			self.observer,
+		);
+
+		let commits = self.commits_capacity.map(|capacity| voter.commits(capacity));
+
+		Ok((voter, commits))
+	}
+}
+
+impl<H, N, E: Environment<H, N>, GlobalIn, GlobalOut> Future for Voter<H, N, E, GlobalIn, GlobalOut>
+where
+	H: Clone + Eq + Ord + ::std::fmt::Debug,
+	N: Copy + BlockNumberOps + ::std::fmt::Debug,
+	GlobalIn: Stream<Item = Result<CommunicationIn<H, N, E::Signature, E::Id>, E::Error>> + Unpin,
+	GlobalOut: Sink<CommunicationOut<H, N, E::Signature, E::Id>, Error = E::Error> + Unpin,
+{
+	type Output = Result<(), E::Error>;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), E::Error>> {
+		self.process_incoming(cx)?;
+		self.prune_background_rounds(cx)?;
+		let _ = self.global_out.poll(cx)?;
+
+		self.process_best_round(cx)
+	}
+}
+
+impl<H, N, E: Environment<H, N>, GlobalIn, GlobalOut> Unpin for Voter<H, N, E, GlobalIn, GlobalOut>
+where
+	H: Clone + Eq + Ord + ::std::fmt::Debug,
+	N: Copy + BlockNumberOps + ::std::fmt::Debug,
+	GlobalIn: Stream<Item = Result<CommunicationIn<H, N, E::Signature, E::Id>, E::Error>> + Unpin,
+	GlobalOut: Sink<CommunicationOut<H, N, E::Signature, E::Id>, Error = E::Error> + Unpin,
+{
+}
+
+/// Trait for querying the state of the voter. Used by `Voter` to return a queryable object
+/// without exposing too many data types.
+pub trait VoterState<Id: Eq + std::hash::Hash> {
+	/// Returns a plain data type, `report::VoterState`, describing the current state
+	/// of the voter relevant to the voting process.
+	fn get(&self) -> report::VoterState<Id>;
+}
+
+/// Contains a number of data transfer objects for reporting data to the outside world.
+pub mod report {
+	use crate::{
+		round::Blocker,
+		weights::{VoteWeight, VoterWeight},
+	};
+	use std::collections::{HashMap, HashSet};
+
+	/// Basic data struct for the state of a round.
+	#[derive(PartialEq, Eq, Clone)]
+	#[cfg_attr(test, derive(Debug))]
+	pub struct RoundState<Id: Eq + std::hash::Hash> {
+		/// Total weight of all votes.
+		pub total_weight: VoterWeight,
+		/// The threshold voter weight.
+		pub threshold_weight: VoterWeight,
+
+		/// Current weight of the prevotes.
+		pub prevote_current_weight: VoteWeight,
+		/// The identities of nodes that have cast prevotes so far.
+		pub prevote_ids: HashSet<Id>,
+
+		/// Current weight of the precommits.
+		pub precommit_current_weight: VoteWeight,
+		/// The identities of nodes that have cast precommits so far.
+		pub precommit_ids: HashSet<Id>,
+
+		/// The number of times precommitting was held back because the
+		/// prevote-GHOST didn't include the previous round's estimate yet.
+		pub precommit_gated: usize,
+
+		/// The conditions which keep the round from being completed.
+		pub completability_blockers: Vec<Blocker>,
+	}
+
+	/// The phase of a voting round.
+	#[derive(PartialEq, Eq, Clone, Copy)]
+	#[cfg_attr(test, derive(Debug))]
+	pub enum RoundPhase {
+		/// No votes have been cast yet.
+		Start,
+		/// The primary block has been proposed.
+		Proposed,
+		/// Waiting for the best chain to prevote on.
+		Prevoting,
+		/// The prevote has been cast.
+		Prevoted,
+		/// The precommit has been cast.
+		Precommitted,
+		/// No further votes are cast in the round, e.g. because it was restored
+		/// as completed or because the local node isn't a voter.
+		NotVoting,
+	}
+
+	/// A snapshot of a voting round.
+	#[derive(PartialEq, Eq, Clone)]
+	#[cfg_attr(test, derive(Debug))]
+	pub struct RoundSummary<H, N> {
+		/// The round number.
+		pub number: u64,
+		/// The phase of the round.
+		pub phase: RoundPhase,
+		/// The best block finalized in the round.
+		pub finalized: Option<(H, N)>,
+	}
+
+	/// Basic data struct for the current state of the voter in a form suitable
+	/// for passing on to other systems.
+	#[derive(PartialEq, Eq)]
+	#[cfg_attr(test, derive(Debug))]
+	pub struct VoterState<Id: Eq + std::hash::Hash> {
+		/// Voting rounds running in the background.
+		pub background_rounds: HashMap<u64, RoundState<Id>>,
+		/// The current best voting round.
+		pub best_round: (u64, RoundState<Id>),
+	}
+}
+
+struct SharedVoterState<H, N, E>(Arc<Mutex<InnerVoterState<H, N, E>>>)
 where
 	H: Clone + Ord + std::fmt::Debug,
 	N: BlockNumberOps,
@@ -930,6 +1965,8 @@ where
 					prevote_ids: voting_round.prevote_ids().collect(),
 					precommit_current_weight: voting_round.precommit_weight(),
 					precommit_ids: voting_round.precommit_ids().collect(),
+					precommit_gated: voting_round.precommit_gated(),
+					completability_blockers: voting_round.completability_blockers(),
 				},
 			)
 		};
@@ -1008,163 +2045,2062 @@ where
 					}
 				}
 
-				(pv, pc)
-			},
-		);
+				(pv, pc)
+			},
+		);
+
+		let threshold = voters.threshold();
+		if pv < threshold || pc < threshold {
+			trace!(target: "afg",
+				   "Ignoring invalid catch up, missing voter threshold"
+			);
+
+			return None
+		}
+	}
+
+	let mut round = crate::round::Round::new(crate::round::RoundParams {
+		round_number: catch_up.round_number,
+		voters: voters.clone(),
+		base: (catch_up.base_hash.clone(), catch_up.base_number),
+	});
+
+	// import prevotes first.
+	for crate::SignedPrevote { prevote, id, signature } in catch_up.prevotes {
+		match round.import_prevote(env, prevote, id, signature) {
+			Ok(_) => {},
+			Err(e) => {
+				trace!(target: "afg",
+					   "Ignoring invalid catch up, error importing prevote: {:?}",
+					   e,
+				);
+
+				return None
+			},
+		}
+	}
+
+	// then precommits.
+	for crate::SignedPrecommit { precommit, id, signature } in catch_up.precommits {
+		match round.import_precommit(env, precommit, id, signature) {
+			Ok(_) => {},
+			Err(e) => {
+				trace!(target: "afg",
+					   "Ignoring invalid catch up, error importing precommit: {:?}",
+					   e,
+				);
+
+				return None
+			},
+		}
+	}
+
+	let state = round.state();
+	if !state.completable {
+		return None
+	}
+
+	Some(round)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		round::Blocker,
+		testing::{
+			self,
+			chain::GENESIS_HASH,
+			environment::{make_environment, Environment, Id, Signature},
+		},
+		weights::{VoteWeight, VoterWeight},
+		SignedPrecommit,
+	};
+	use futures::{executor::LocalPool, task::SpawnExt};
+	use futures_timer::Delay;
+	use std::{collections::HashSet, iter, time::Duration};
+
+	// run the voter on the pool until the condition holds, panicking if the
+	// voter exits first.
+	fn run_voter_until<E, GlobalIn, GlobalOut>(
+		pool: &mut LocalPool,
+		voter: &mut Voter<&'static str, u32, E, GlobalIn, GlobalOut>,
+		mut condition: impl FnMut(
+			&mut Context,
+			&Voter<&'static str, u32, E, GlobalIn, GlobalOut>,
+		) -> bool,
+	) where
+		E: crate::voter::Environment<&'static str, u32>,
+		GlobalIn: Stream<Item = Result<CommunicationIn<&'static str, u32, E::Signature, E::Id>, E::Error>>
+			+ Unpin,
+		GlobalOut: Sink<CommunicationOut<&'static str, u32, E::Signature, E::Id>, Error = E::Error>
+			+ Unpin,
+	{
+		pool.run_until(future::poll_fn(|cx| {
+			if let Poll::Ready(res) = voter.poll_unpin(cx) {
+				panic!("voter exited early: {:?}", res);
+			}
+
+			if condition(cx, voter) {
+				Poll::Ready(())
+			} else {
+				Poll::Pending
+			}
+		}))
+	}
+
+	#[test]
+	fn talking_to_myself() {
+		let local_id = Id(5);
+		let voters = VoterSet::new(std::iter::once((local_id, 100))).unwrap();
+
+		let (network, routing_task) = testing::environment::make_network();
+
+		let global_comms = network.make_global_comms();
+		let env = Arc::new(Environment::new(network, local_id));
+
+		// initialize chain
+		let last_finalized = env.with_chain(|chain| {
+			chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+			chain.last_finalized()
+		});
+
+		// run voter in background. scheduling it to shut down at the end.
+		let finalized = env.finalized_stream();
+		let voter = Voter::new(
+			env.clone(),
+			voters,
+			global_comms,
+			0,
+			Vec::new(),
+			last_finalized,
+			last_finalized,
+		);
+
+		let mut pool = LocalPool::new();
+		pool.spawner().spawn(voter.map(|v| v.expect("Error voting"))).unwrap();
+		pool.spawner().spawn(routing_task).unwrap();
+
+		// wait for the best block to finalize.
+		pool.run_until(
+			finalized
+				.take_while(|&(_, n, _)| future::ready(n < 6))
+				.for_each(|_| future::ready(())),
+		)
+	}
+
+	#[test]
+	fn notes_rounds_becoming_completable_before_completion() {
+		// an environment recording when rounds become completable and when
+		// they are completed.
+		struct EventEnvironment(Environment, Mutex<Vec<(&'static str, u64)>>);
+
+		impl Chain<&'static str, u32> for EventEnvironment {
+			testing::environment::wrap_environment!(@forward 0: ancestry);
+		}
+
+		impl crate::voter::Environment<&'static str, u32> for EventEnvironment {
+			testing::environment::wrap_environment!(@forward 0:
+				types,
+				best_chain_containing,
+				voting_rule,
+				primary_selector,
+				round_data,
+				round_commit_timer,
+				concluded,
+				finalize_block,
+				proposed,
+				prevoted,
+				precommitted,
+				persistence,
+				invariant_level,
+				verify_persisted_vote,
+				is_block_known,
+				prevote_equivocation,
+				precommit_equivocation,
+			);
+
+			fn round_completable(&self, round: u64, _elapsed: Duration) {
+				self.1.lock().push(("completable", round));
+			}
+
+			fn completed(
+				&self,
+				round: u64,
+				state: RoundState<&'static str, u32>,
+				base: (&'static str, u32),
+				votes: &HistoricalVotes<&'static str, u32, Signature, Id>,
+			) -> Result<(), crate::Error> {
+				self.1.lock().push(("completed", round));
+				crate::voter::Environment::completed(&self.0, round, state, base, votes)
+			}
+		}
+
+		let local_id = Id(5);
+		let voters = VoterSet::new(iter::once((local_id, 100))).unwrap();
+		let (env, network, mut pool) = make_environment(local_id, &["A", "B", "C", "D", "E"]);
+		let env = Arc::new(EventEnvironment(env, Mutex::new(Vec::new())));
+
+		let mut voter = Voter::new(
+			env.clone(),
+			voters,
+			network.make_global_comms(),
+			0,
+			Vec::new(),
+			(GENESIS_HASH, 1),
+			(GENESIS_HASH, 1),
+		);
+
+		run_voter_until(&mut pool, &mut voter, |_, _| env.0.last_completed_and_concluded().0 >= 2);
+
+		assert_eq!(
+			env.1.lock()[..4],
+			[("completable", 1), ("completed", 1), ("completable", 2), ("completed", 2)],
+		);
+	}
+	#[test]
+	fn votes_are_noted_before_being_sent() {
+		let local_id = Id(5);
+		let voters = VoterSet::new(std::iter::once((local_id, 100))).unwrap();
+
+		let (network, routing_task) = testing::environment::make_network();
+		let env = Arc::new(Environment::new(network.clone(), local_id));
+
+		let last_finalized = env.with_chain(|chain| {
+			chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+			chain.last_finalized()
+		});
+
+		let finalized = env.finalized_stream();
+		let voter = Voter::new(
+			env.clone(),
+			voters,
+			network.make_global_comms(),
+			0,
+			Vec::new(),
+			last_finalized,
+			last_finalized,
+		);
+
+		let mut pool = LocalPool::new();
+		pool.spawner().spawn(voter.map(|v| v.expect("Error voting"))).unwrap();
+		pool.spawner().spawn(routing_task).unwrap();
+
+		pool.run_until(
+			finalized
+				.take_while(|&(_, n, _)| future::ready(n < 6))
+				.for_each(|_| future::ready(())),
+		);
+
+		assert_eq!(
+			env.noted_votes()[..2],
+			[
+				(1, Message::Prevote(Prevote::new("E", 6))),
+				(1, Message::Precommit(Precommit::new("E", 6))),
+			],
+		);
+		assert!(env.unnoted_votes().is_empty());
+	}
+
+	#[test]
+	fn pausing_at_round_boundary() {
+		// an environment recording the rounds the voter paused before.
+		struct PausingEnvironment(Environment, Mutex<Vec<u64>>);
+
+		testing::environment::wrap_environment!(PausingEnvironment, 0, {
+			fn paused_at(&self, round: u64) {
+				self.1.lock().push(round);
+			}
+		});
+
+		let local_id = Id(5);
+		let voters = VoterSet::new(iter::once((local_id, 100))).unwrap();
+		let (env, network, mut pool) = make_environment(local_id, &["A", "B", "C", "D", "E"]);
+		let env = Arc::new(PausingEnvironment(env, Mutex::new(Vec::new())));
+
+		let mut voter = Voter::new(
+			env.clone(),
+			voters,
+			network.make_global_comms(),
+			0,
+			Vec::new(),
+			(GENESIS_HASH, 1),
+			(GENESIS_HASH, 1),
+		);
+
+		// the current round can't be paused at.
+		assert!(!voter.pause_at(1));
+		assert!(voter.pause_at(3));
+
+		run_voter_until(&mut pool, &mut voter, |_, _| !env.1.lock().is_empty());
+		assert_eq!(*env.1.lock(), vec![3]);
+
+		// round 2 is done, but round 3 isn't started while the voter is paused.
+		{
+			let inner = voter.inner.lock();
+			assert_eq!(inner.best_round.round_number(), 2);
+			assert!(inner.best_round.round_state().completable);
+			assert!(matches!(inner.best_round.state(), Some(&VotingRoundState::Precommitted)));
+		}
+
+		let mut wait = Delay::new(Duration::from_millis(200));
+		run_voter_until(&mut pool, &mut voter, |cx, _| wait.poll_unpin(cx).is_ready());
+		assert_eq!(voter.inner.lock().best_round.round_number(), 2);
+		assert_eq!(*env.1.lock(), vec![3]);
+
+		// resuming starts round 3 and keeps going.
+		voter.unpause();
+		run_voter_until(&mut pool, &mut voter, |_, voter| {
+			voter.inner.lock().best_round.round_number() == 4
+		});
+		assert_eq!(*env.1.lock(), vec![3]);
+	}
+	#[test]
+	fn building_minimal_voter() {
+		let local_id = Id(5);
+		let voters = VoterSet::new(std::iter::once((local_id, 100))).unwrap();
+
+		let (network, routing_task) = testing::environment::make_network();
+		let mut pool = LocalPool::new();
+		pool.spawner().spawn(routing_task).unwrap();
+
+		let env = Arc::new(Environment::new(network.clone(), local_id));
+		let last_finalized = env.with_chain(|chain| {
+			chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+			chain.last_finalized()
+		});
+
+		let (voter, commits) =
+			VoterBuilder::new(env.clone(), voters, network.make_global_comms(), last_finalized)
+				.build()
+				.unwrap();
+		assert!(commits.is_none());
+
+		let finalized = env.finalized_stream();
+		pool.spawner().spawn(voter.map(|v| v.expect("Error voting"))).unwrap();
+		pool.run_until(
+			finalized
+				.take_while(|&(_, n, _)| future::ready(n < 6))
+				.for_each(|_| future::ready(())),
+		);
+	}
+
+	#[test]
+	fn building_voter_with_persistence_and_commits() {
+		let local_id = Id(5);
+		let voters = VoterSet::new(std::iter::once((local_id, 100))).unwrap();
+
+		let (network, routing_task) = testing::environment::make_network();
+		let mut pool = LocalPool::new();
+		pool.spawner().spawn(routing_task).unwrap();
+
+		// the voter is resumed from persistence, which takes precedence over
+		// the given last round, and streams its commits.
+		let persistence = Arc::new(persistence::InMemoryPersistence::new());
+		let state = RoundState {
+			round: 3,
+			prevote_ghost: Some(("E", 6)),
+			finalized: Some(("E", 6)),
+			estimate: Some(("E", 6)),
+			completable: true,
+		};
+		persistence.save_completed(3, state, ("C", 4), &HistoricalVotes::new()).unwrap();
+
+		let env =
+			Arc::new(Environment::new(network.clone(), local_id).with_persistence(persistence));
+		env.with_chain(|chain| {
+			chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+			chain.push_blocks("E", &["F"]);
+		});
+
+		let (voter, commits) =
+			VoterBuilder::new(env.clone(), voters, network.make_global_comms(), ("E", 6))
+				.last_round(1, Vec::new(), (GENESIS_HASH, 1))
+				.resume()
+				.commits(1)
+				.build()
+				.unwrap();
+		assert_eq!(voter.inner.lock().best_round.round_number(), 4);
+
+		pool.spawner().spawn(voter.map(|v| v.expect("Error voting"))).unwrap();
+		let commit = pool.run_until(commits.unwrap().next()).unwrap();
+		assert_eq!((commit.target_hash, commit.target_number), ("F", 7));
+	}
+
+	#[test]
+	fn observer_follows_finality_without_voting() {
+		let voters = VoterSet::new((0..4).map(|i| (Id(i), 1))).unwrap();
+
+		let (network, routing_task) = testing::environment::make_network();
+		let mut pool = LocalPool::new();
+
+		// the observer is configured with the id of the last voter, which
+		// is offline.
+		let (envs, finalized_streams): (Vec<_>, Vec<_>) = (0..4)
+			.map(|i| {
+				let env = Arc::new(Environment::new(network.clone(), Id(i)));
+				let last_finalized = env.with_chain(|chain| {
+					chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+					chain.last_finalized()
+				});
+
+				let mut builder = VoterBuilder::new(
+					env.clone(),
+					voters.clone(),
+					network.make_global_comms(),
+					last_finalized,
+				);
+				if i == 3 {
+					builder = builder.observer();
+				}
+
+				let finalized = env.finalized_stream();
+				let (voter, _) = builder.build().unwrap();
+				pool.spawner().spawn(voter.map(|v| v.expect("Error voting"))).unwrap();
+
+				let finalized = finalized
+					.take_while(|&(_, n, _)| future::ready(n < 6))
+					.for_each(|_| future::ready(()));
+
+				(env, finalized)
+			})
+			.unzip();
+
+		pool.spawner().spawn(routing_task).unwrap();
+		pool.run_until(future::join_all(finalized_streams));
+
+		let (observer, voters) = envs.split_last().unwrap();
+		for env in voters {
+			assert!(!env.noted_votes().is_empty());
+		}
+		assert!(observer.noted_votes().is_empty());
+	}
+
+	#[test]
+	fn voting_with_only_required_environment_methods() {
+		// an environment which relies on the defaults of all optional hooks.
+		struct MinimalEnvironment(Environment);
+
+		impl Chain<&'static str, u32> for MinimalEnvironment {
+			fn ancestry(
+				&self,
+				base: &'static str,
+				block: &'static str,
+			) -> Result<Vec<&'static str>, crate::Error> {
+				self.0.ancestry(base, block)
+			}
+		}
+
+		impl super::Environment<&'static str, u32> for MinimalEnvironment {
+			type Timer = <Environment as super::Environment<&'static str, u32>>::Timer;
+			type BestChain = <Environment as super::Environment<&'static str, u32>>::BestChain;
+			type Id = Id;
+			type Signature = Signature;
+			type In = <Environment as super::Environment<&'static str, u32>>::In;
+			type Out = <Environment as super::Environment<&'static str, u32>>::Out;
+			type Error = crate::Error;
+
+			fn best_chain_containing(&self, base: &'static str) -> Self::BestChain {
+				self.0.best_chain_containing(base)
+			}
+
+			fn round_data(
+				&self,
+				round: u64,
+			) -> RoundData<Self::Id, Self::Timer, Self::In, Self::Out> {
+				self.0.round_data(round)
+			}
+
+			fn round_commit_timer(&self) -> Self::Timer {
+				self.0.round_commit_timer()
+			}
+
+			fn proposed(
+				&self,
+				round: u64,
+				propose: PrimaryPropose<&'static str, u32>,
+			) -> Result<(), crate::Error> {
+				self.0.proposed(round, propose)
+			}
+
+			fn prevoted(
+				&self,
+				round: u64,
+				prevote: Prevote<&'static str, u32>,
+			) -> Result<(), crate::Error> {
+				self.0.prevoted(round, prevote)
+			}
+
+			fn precommitted(
+				&self,
+				round: u64,
+				precommit: Precommit<&'static str, u32>,
+			) -> Result<(), crate::Error> {
+				self.0.precommitted(round, precommit)
+			}
+
+			fn completed(
+				&self,
+				round: u64,
+				state: RoundState<&'static str, u32>,
+				base: (&'static str, u32),
+				votes: &HistoricalVotes<&'static str, u32, Signature, Id>,
+			) -> Result<(), crate::Error> {
+				self.0.completed(round, state, base, votes)
+			}
+
+			fn concluded(
+				&self,
+				round: u64,
+				state: RoundState<&'static str, u32>,
+				base: (&'static str, u32),
+				votes: &HistoricalVotes<&'static str, u32, Signature, Id>,
+			) -> Result<(), crate::Error> {
+				self.0.concluded(round, state, base, votes)
+			}
+
+			fn finalize_block(
+				&self,
+				hash: &'static str,
+				number: u32,
+				round: u64,
+				commit: Commit<&'static str, u32, Signature, Id>,
+			) -> Result<(), crate::Error> {
+				self.0.finalize_block(hash, number, round, commit)
+			}
+		}
+
+		let local_id = Id(5);
+		let voters = VoterSet::new(std::iter::once((local_id, 100))).unwrap();
+
+		let (network, routing_task) = testing::environment::make_network();
+		let env = Environment::new(network.clone(), local_id);
+		let last_finalized = env.with_chain(|chain| {
+			chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+			chain.last_finalized()
+		});
+
+		let finalized = env.finalized_stream();
+		let voter = Voter::new(
+			Arc::new(MinimalEnvironment(env)),
+			voters,
+			network.make_global_comms(),
+			0,
+			Vec::new(),
+			last_finalized,
+			last_finalized,
+		);
+
+		let mut pool = LocalPool::new();
+		pool.spawner().spawn(voter.map(|v| v.expect("Error voting"))).unwrap();
+		pool.spawner().spawn(routing_task).unwrap();
+
+		pool.run_until(
+			finalized
+				.take_while(|&(_, n, _)| future::ready(n < 6))
+				.for_each(|_| future::ready(())),
+		)
+	}
+
+	#[test]
+	fn adopts_highest_block_finalized_by_rounds_and_commits() {
+		let local_id = Id(5);
+		let voters = VoterSet::new((5..9).map(|i| (Id(i), 1))).unwrap();
+
+		let (network, routing_task) = testing::environment::make_network();
+		let env = Arc::new(Environment::new(network.clone(), local_id));
+		env.with_chain(|chain| {
+			chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+			chain.push_blocks("B", &["C2", "D2", "E2", "F2"]);
+		});
+
+		let signed = |message, id| SignedMessage { message, signature: Signature(id), id: Id(id) };
+		let commit = |target_hash, target_number| {
+			CommunicationIn::Commit(
+				2,
+				CompactCommit::from(Commit {
+					target_hash,
+					target_number,
+					precommits: (5..8)
+						.map(|id| SignedPrecommit {
+							precommit: Precommit::new(target_hash, target_number),
+							signature: Signature(id),
+							id: Id(id),
+						})
+						.collect(),
+				}),
+				Callback::Blank,
+			)
+		};
+
+		// the last round finalizes `C`, then commits from the current round
+		// finalize `E` and the lower `D`, and a conflicting commit `F2`.
+		let last_round_votes = vec![
+			signed(Message::Prevote(Prevote::new("C", 4)), 5),
+			signed(Message::Prevote(Prevote::new("C", 4)), 6),
+			signed(Message::Prevote(Prevote::new("C", 4)), 7),
+			signed(Message::Precommit(Precommit::new("C", 4)), 5),
+			signed(Message::Precommit(Precommit::new("C", 4)), 6),
+			signed(Message::Precommit(Precommit::new("C", 4)), 7),
+		];
+
+		let mut finalized = env.finalized_stream();
+		let mut voter = Voter::new(
+			env.clone(),
+			voters,
+			network.make_global_comms(),
+			1,
+			last_round_votes,
+			(GENESIS_HASH, 1),
+			(GENESIS_HASH, 1),
+		);
+
+		let mut pool = LocalPool::new();
+		pool.spawner().spawn(routing_task).unwrap();
+
+		run_voter_until(&mut pool, &mut voter, |_, voter| voter.last_finalized == ("C", 4));
+
+		network.send_message(commit("E", 6));
+		run_voter_until(&mut pool, &mut voter, |_, voter| voter.last_finalized == ("E", 6));
+
+		// the lower block isn't finalized again.
+		network.send_message(commit("D", 5));
+		network.send_message(commit("F2", 7));
+
+		// the conflicting block is a safety violation.
+		assert_eq!(pool.run_until(&mut voter), Err(crate::Error::ConflictingFinality));
+		assert_eq!(voter.last_finalized, ("E", 6));
+
+		let finalized = iter::from_fn(|| finalized.try_recv().ok().map(|(hash, _, _)| hash))
+			.collect::<Vec<_>>();
+		assert_eq!(finalized, vec!["C", "E"]);
+	}
+
+	#[test]
+	fn recording_finalization_rounds() {
+		let local_id = Id(5);
+		let voters = VoterSet::new((5..9).map(|i| (Id(i), 1))).unwrap();
+
+		let (network, routing_task) = testing::environment::make_network();
+		let env = Arc::new(Environment::new(network.clone(), local_id));
+		env.with_chain(|chain| {
+			chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+		});
+
+		let signed = |message, id| SignedMessage { message, signature: Signature(id), id: Id(id) };
+		let commit = |round, target_hash, target_number| {
+			CommunicationIn::Commit(
+				round,
+				CompactCommit::from(Commit {
+					target_hash,
+					target_number,
+					precommits: (5..8)
+						.map(|id| SignedPrecommit {
+							precommit: Precommit::new(target_hash, target_number),
+							signature: Signature(id),
+							id: Id(id),
+						})
+						.collect(),
+				}),
+				Callback::Blank,
+			)
+		};
+
+		// the last round finalizes `A`.
+		let last_round_votes = (5..8)
+			.flat_map(|id| {
+				vec![
+					signed(Message::Prevote(Prevote::new("A", 2)), id),
+					signed(Message::Precommit(Precommit::new("A", 2)), id),
+				]
+			})
+			.collect();
+
+		let mut voter = Voter::new(
+			env.clone(),
+			voters,
+			network.make_global_comms(),
+			1,
+			last_round_votes,
+			(GENESIS_HASH, 1),
+			(GENESIS_HASH, 1),
+		);
+
+		let mut pool = LocalPool::new();
+		pool.spawner().spawn(routing_task).unwrap();
+
+		let mut finalize = |voter: &mut _, block| {
+			run_voter_until(&mut pool, voter, |_, voter| voter.last_finalized == block)
+		};
+
+		finalize(&mut voter, ("A", 2));
+
+		// later blocks are finalized by commits from the rounds after.
+		network.send_message(commit(4, "C", 4));
+		finalize(&mut voter, ("C", 4));
+		network.send_message(commit(6, "E", 6));
+		finalize(&mut voter, ("E", 6));
+
+		assert_eq!(voter.finalization_round(&"A"), Some(1));
+		assert_eq!(voter.finalization_round(&"C"), Some(4));
+		assert_eq!(voter.finalization_round(&"E"), Some(6));
+
+		// `B` was finalized along with `C`, but never given to the environment.
+		assert_eq!(voter.finalization_round(&"B"), None);
+		assert_eq!(voter.finalization_round(&"X"), None);
+	}
+
+	#[test]
+	fn streams_a_commit_per_finalized_block() {
+		let local_id = Id(5);
+		let voters = VoterSet::new(std::iter::once((local_id, 100))).unwrap();
+
+		let (network, routing_task) = testing::environment::make_network();
+
+		let global_comms = network.make_global_comms();
+		let env = Arc::new(Environment::new(network, local_id));
+
+		let last_finalized = env.with_chain(|chain| {
+			chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+			chain.last_finalized()
+		});
+
+		let mut voter = Voter::new(
+			env.clone(),
+			voters.clone(),
+			global_comms,
+			0,
+			Vec::new(),
+			last_finalized,
+			last_finalized,
+		);
+		let mut commits = voter.commits(1);
+
+		let mut pool = LocalPool::new();
+		pool.spawner().spawn(voter.map(|v| v.expect("Error voting"))).unwrap();
+		pool.spawner().spawn(routing_task).unwrap();
+
+		// each time a commit is received, extend the chain so the next round
+		// finalizes a new block.
+		for (parent, block, number) in [("E", "F", 6), ("F", "G", 7), ("G", "H", 8)] {
+			let commit = pool.run_until(commits.next()).unwrap();
+			assert_eq!((commit.target_hash, commit.target_number), (parent, number));
+			assert!(env
+				.with_chain(|chain| validate_commit(&commit, &voters, &*chain))
+				.unwrap()
+				.is_valid());
+
+			env.with_chain(|chain| chain.push_blocks(parent, &[block]));
+		}
+	}
+
+	#[test]
+	fn best_chain_is_cached_while_generation_is_unchanged() {
+		// an environment counting best chain queries, with a best block
+		// generation set by the test.
+		struct GenerationEnvironment {
+			env: Environment,
+			generation: Mutex<u64>,
+			queries: Mutex<usize>,
+		}
+
+		impl Chain<&'static str, u32> for GenerationEnvironment {
+			testing::environment::wrap_environment!(@forward env: ancestry);
+		}
+
+		impl crate::voter::Environment<&'static str, u32> for GenerationEnvironment {
+			testing::environment::wrap_environment!(@forward env:
+				types,
+				voting_rule,
+				primary_selector,
+				round_data,
+				round_commit_timer,
+				completed,
+				concluded,
+				finalize_block,
+				proposed,
+				prevoted,
+				precommitted,
+				persistence,
+				invariant_level,
+				verify_persisted_vote,
+				is_block_known,
+				prevote_equivocation,
+				precommit_equivocation,
+			);
+
+			fn best_chain_containing(&self, base: &'static str) -> Self::BestChain {
+				*self.queries.lock() += 1;
+				crate::voter::Environment::best_chain_containing(&self.env, base)
+			}
+
+			fn best_block_generation(&self) -> Option<u64> {
+				Some(*self.generation.lock())
+			}
+		}
+
+		let local_id = Id(5);
+		let voters = VoterSet::new(iter::once((local_id, 100))).unwrap();
+		let (env, network, mut pool) = make_environment(local_id, &["A", "B", "C", "D", "E"]);
+		let env = Arc::new(GenerationEnvironment {
+			env,
+			generation: Mutex::new(0),
+			queries: Mutex::new(0),
+		});
+
+		let mut voter = Voter::new(
+			env.clone(),
+			voters,
+			network.make_global_comms(),
+			0,
+			Vec::new(),
+			(GENESIS_HASH, 1),
+			(GENESIS_HASH, 1),
+		);
+
+		// run the voter until round 4 has started.
+		run_voter_until(&mut pool, &mut voter, |_, voter| {
+			voter.inner.lock().best_round.round_number() >= 4
+		});
+
+		// round 1 queries the best chain containing genesis and round 2 the one
+		// containing `E`. round 3 votes on top of `E` again and uses the cache.
+		assert_eq!(*env.queries.lock(), 2);
+
+		// a new generation invalidates the cache.
+		env.env.with_chain(|chain| chain.push_blocks("E", &["F"]));
+		*env.generation.lock() = 1;
+
+		run_voter_until(&mut pool, &mut voter, |_, voter| {
+			voter.inner.lock().best_round.round_number() >= 5
+		});
+
+		assert_eq!(*env.queries.lock(), 3);
+	}
+	#[test]
+	fn regressing_round_base_is_an_error() {
+		let local_id = Id(5);
+		let voters = VoterSet::new(std::iter::once((local_id, 100))).unwrap();
+
+		let (network, routing_task) = testing::environment::make_network();
+
+		let global_comms = network.make_global_comms();
+		let env = Arc::new(Environment::new(network, local_id));
+		env.set_invariant_level(InvariantLevel::Always);
+
+		env.with_chain(|chain| chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]));
+
+		let mut voter =
+			Voter::new(env.clone(), voters, global_comms, 0, Vec::new(), ("E", 6), ("E", 6));
+
+		// simulate a misconfigured voter which would start the next round from
+		// a base below the one of the current round.
+		voter.last_finalized_in_rounds = (GENESIS_HASH, 1);
+
+		let mut pool = LocalPool::new();
+		pool.spawner().spawn(routing_task).unwrap();
+
+		assert_eq!(pool.run_until(voter), Err(crate::Error::BaseRegression));
+	}
+
+	#[test]
+	fn invariant_level_controls_round_base_check() {
+		let run = |level| {
+			let local_id = Id(5);
+			let voters = VoterSet::new(std::iter::once((local_id, 100))).unwrap();
+
+			let (network, routing_task) = testing::environment::make_network();
+
+			let global_comms = network.make_global_comms();
+			let env = Arc::new(Environment::new(network, local_id));
+			env.set_invariant_level(level);
+
+			env.with_chain(|chain| chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]));
+
+			let mut voter =
+				Voter::new(env.clone(), voters, global_comms, 0, Vec::new(), ("E", 6), ("E", 6));
+			voter.last_finalized_in_rounds = (GENESIS_HASH, 1);
+
+			let mut pool = LocalPool::new();
+			pool.spawner().spawn(routing_task).unwrap();
+
+			pool.run_until(future::poll_fn(|cx| match voter.poll_unpin(cx) {
+				Poll::Ready(res) => Poll::Ready(Some(res)),
+				Poll::Pending if voter.inner.lock().best_round.round_number() >= 3 =>
+					Poll::Ready(None),
+				Poll::Pending => Poll::Pending,
+			}))
+		};
+
+		assert_eq!(run(InvariantLevel::Always), Some(Err(crate::Error::BaseRegression)));
+
+		// the voter keeps running rounds without checking the base.
+		assert_eq!(run(InvariantLevel::Off), None);
+	}
+
+	#[test]
+	fn resuming_with_persisted_votes_does_not_equivocate() {
+		let local_id = Id(0);
+		let voters = VoterSet::new(vec![(local_id, 1), (Id(1), 1)]).unwrap();
+
+		let (network, routing_task) = testing::environment::make_network();
+		let persistence = Arc::new(persistence::InMemoryPersistence::new());
+		let env = Arc::new(
+			Environment::new(network.clone(), local_id).with_persistence(persistence.clone()),
+		);
+
+		let last_finalized = env.with_chain(|chain| {
+			chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+			chain.last_finalized()
+		});
+
+		let mut pool = LocalPool::new();
+		pool.spawner().spawn(routing_task).unwrap();
+
+		let start_voter = |pool: &mut LocalPool| {
+			let voter = Voter::new(
+				env.clone(),
+				voters.clone(),
+				network.make_global_comms(),
+				0,
+				Vec::new(),
+				last_finalized,
+				last_finalized,
+			);
+
+			let (voter, handle) = future::abortable(voter);
+			pool.spawner().spawn(voter.map(|_| ())).unwrap();
+			handle
+		};
+
+		// the other voter is driven manually, so that the round can't complete.
+		let (round_stream, round_sink) = network.make_round_comms(1, Id(1));
+		let mut local_prevotes = round_stream.filter_map(move |message| {
+			future::ready(match message {
+				Ok(SignedMessage { message: Message::Prevote(prevote), id, .. })
+					if id == local_id =>
+					Some(prevote),
+				_ => None,
+			})
+		});
+
+		// crash the voter right after it prevoted.
+		let handle = start_voter(&mut pool);
+		let prevote = pool.run_until(local_prevotes.next()).unwrap();
+		assert_eq!(prevote, Prevote::new("E", 6));
+
+		handle.abort();
+		pool.run_until_stalled();
+
+		// the best chain has moved on while the voter was down, if it prevoted
+		// again it would now vote for "F" and equivocate.
+		env.with_chain(|chain| chain.push_blocks("E", &["F"]));
+
+		let finalized = env.finalized_stream();
+		let _handle = start_voter(&mut pool);
+
+		let votes = vec![
+			Message::Prevote(Prevote::new("E", 6)),
+			Message::Precommit(Precommit::new("E", 6)),
+		];
+		pool.spawner()
+			.spawn(stream::iter(votes.into_iter().map(Ok)).forward(round_sink).map(|_| ()))
+			.unwrap();
+
+		let (hash, _, _) = pool.run_until(finalized.into_future()).0.unwrap();
+		assert_eq!(hash, "E");
+
+		// the voter re-sent its original prevote rather than casting a new one.
+		assert_eq!(pool.run_until(local_prevotes.next()), Some(prevote));
+		assert_eq!(persistence.load_completed().unwrap().map(|round| round.number), Some(1));
+	}
+
+	#[test]
+	fn shutting_down_mid_round_resumes_without_recasting_votes() {
+		let local_id = Id(0);
+		let voters = VoterSet::new(vec![(local_id, 1), (Id(1), 1)]).unwrap();
+
+		let (network, routing_task) = testing::environment::make_network();
+		let persistence = Arc::new(persistence::InMemoryPersistence::new());
+		let env = Arc::new(
+			Environment::new(network.clone(), local_id).with_persistence(persistence.clone()),
+		);
+
+		let last_finalized = env.with_chain(|chain| {
+			chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+			chain.last_finalized()
+		});
+
+		let mut pool = LocalPool::new();
+		pool.spawner().spawn(routing_task).unwrap();
+
+		// the other voter is driven manually, so that the round can't complete.
+		let (round_stream, round_sink) = network.make_round_comms(1, Id(1));
+		let mut local_prevotes = round_stream.filter_map(move |message| {
+			future::ready(match message {
+				Ok(SignedMessage { message: Message::Prevote(prevote), id, .. })
+					if id == local_id =>
+					Some(prevote),
+				_ => None,
+			})
+		});
+
+		// shut the voter down right after it prevoted.
+		let mut voter = Voter::new(
+			env.clone(),
+			voters.clone(),
+			network.make_global_comms(),
+			0,
+			Vec::new(),
+			last_finalized,
+			last_finalized,
+		);
+		let prevote = match pool.run_until(future::select(&mut voter, local_prevotes.next())) {
+			future::Either::Right((prevote, _)) => prevote.unwrap(),
+			future::Either::Left((res, _)) => panic!("voter exited early: {:?}", res),
+		};
+		assert_eq!(prevote, Prevote::new("E", 6));
+
+		let deadline = Box::new(Delay::new(Duration::from_secs(1)).map(Ok));
+		let snapshot = pool.run_until(voter.shutdown(deadline)).unwrap();
+		assert_eq!(snapshot.round, 1);
+		assert_eq!(snapshot.votes, vec![Message::Prevote(prevote.clone())]);
+		assert_eq!(snapshot.last_finalized, last_finalized);
+		assert_eq!(snapshot.last_completed, None);
+
+		// if the voter prevoted again after resuming it would now vote for "F"
+		// and equivocate.
+		env.with_chain(|chain| chain.push_blocks("E", &["F"]));
+
+		let finalized = env.finalized_stream();
+		let voter = Voter::resume(
+			env.clone(),
+			voters,
+			network.make_global_comms(),
+			snapshot.last_finalized,
+		)
+		.unwrap();
+		pool.spawner().spawn(voter.map(|v| v.expect("Error voting"))).unwrap();
+
+		let votes = vec![
+			Message::Prevote(Prevote::new("E", 6)),
+			Message::Precommit(Precommit::new("E", 6)),
+		];
+		pool.spawner()
+			.spawn(stream::iter(votes.into_iter().map(Ok)).forward(round_sink).map(|_| ()))
+			.unwrap();
+
+		let (hash, _, _) = pool.run_until(finalized.into_future()).0.unwrap();
+		assert_eq!(hash, "E");
+
+		// the voter re-sent its original prevote rather than casting a new one.
+		assert_eq!(pool.run_until(local_prevotes.next()), Some(prevote));
+	}
+
+	#[test]
+	fn noting_finalized_block_prunes_rounds_and_moves_base() {
+		let local_id = Id(5);
+		let voters = VoterSet::new((5..9).map(|i| (Id(i), 1))).unwrap();
+
+		let (network, routing_task) = testing::environment::make_network();
+		let env = Arc::new(Environment::new(network.clone(), local_id));
+		env.with_chain(|chain| {
+			chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E", "F", "G", "H"]);
+			chain.push_blocks(GENESIS_HASH, &["A2"]);
+		});
+
+		// the last round finalized `C` but its estimate is `E`.
+		let signed = |message, id| SignedMessage { message, signature: Signature(id), id: Id(id) };
+		let last_round_votes = vec![
+			signed(Message::Prevote(Prevote::new("E", 6)), 5),
+			signed(Message::Prevote(Prevote::new("E", 6)), 6),
+			signed(Message::Prevote(Prevote::new("E", 6)), 7),
+			signed(Message::Precommit(Precommit::new("E", 6)), 5),
+			signed(Message::Precommit(Precommit::new("E", 6)), 6),
+			signed(Message::Precommit(Precommit::new("C", 4)), 7),
+		];
+
+		let mut voter = Voter::new(
+			env.clone(),
+			voters,
+			network.make_global_comms(),
+			1,
+			last_round_votes,
+			(GENESIS_HASH, 1),
+			(GENESIS_HASH, 1),
+		);
+
+		let mut pool = LocalPool::new();
+		let spawner = pool.spawner();
+		spawner.spawn(routing_task).unwrap();
+
+		run_voter_until(&mut pool, &mut voter, |_, voter| {
+			voter.last_finalized_in_rounds == ("C", 4)
+		});
+		assert_eq!(voter.inner.lock().past_rounds.voting_rounds().count(), 1);
+
+		// blocks which don't descend from the last finalized block are rejected.
+		assert_eq!(voter.note_finalized("A2", 2), Err(crate::Error::NotDescendent));
+		assert_eq!(voter.note_finalized("B", 3), Err(crate::Error::NotDescendent));
+
+		// the background round finalized `C`, so its vote-graph is pruned to
+		// it. the current round hasn't finalized anything yet and isn't.
+		voter.note_finalized("C", 4).unwrap();
+		{
+			let inner = voter.inner.lock();
+			let background = inner.past_rounds.voting_rounds().next().unwrap();
+			assert_eq!(background.finalized_floor(), Some(&("C", 4)));
+			assert_eq!(background.dag_base(), (GENESIS_HASH, 1));
+			assert_eq!(inner.best_round.finalized_floor(), None);
+		}
+
+		voter.note_finalized("G", 8).unwrap();
+		assert_eq!(voter.last_finalized, ("G", 8));
+
+		// the background round is no longer needed.
+		run_voter_until(&mut pool, &mut voter, |_, voter| {
+			voter.inner.lock().past_rounds.voting_rounds().count() == 0
+		});
+
+		// complete round 2, the next round is based on the new floor.
+		let (_, round_sink) = network.make_round_comms(2, Id(6));
+		let votes = vec![
+			Message::Prevote(Prevote::new("E", 6)),
+			Message::Precommit(Precommit::new("E", 6)),
+		];
+		spawner
+			.spawn(stream::iter(votes.clone().into_iter().map(Ok)).forward(round_sink).map(|_| ()))
+			.unwrap();
+
+		let (_, round_sink) = network.make_round_comms(2, Id(7));
+		spawner
+			.spawn(stream::iter(votes.into_iter().map(Ok)).forward(round_sink).map(|_| ()))
+			.unwrap();
+
+		run_voter_until(&mut pool, &mut voter, |_, voter| {
+			voter.inner.lock().best_round.round_number() == 3
+		});
+		assert_eq!(voter.inner.lock().best_round.dag_base(), ("G", 8));
+	}
+
+	#[test]
+	fn importing_commits_finalizes_highest_valid() {
+		let set_a = VoterSet::new((0..4).map(|i| (Id(i), 1))).unwrap();
+		let set_b = VoterSet::new((4..8).map(|i| (Id(i), 1))).unwrap();
+
+		let (network, _) = testing::environment::make_network();
+		let env = Arc::new(Environment::new(network.clone(), Id(0)));
+		env.with_chain(|chain| {
+			chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E", "F", "G", "H"]);
+			chain.push_blocks("C", &["D2", "E2", "F2", "G2"]);
+		});
+
+		let mut finalized = env.finalized_stream();
+		let mut voter = Voter::new(
+			env.clone(),
+			set_a.clone(),
+			network.make_global_comms(),
+			0,
+			Vec::new(),
+			(GENESIS_HASH, 1),
+			(GENESIS_HASH, 1),
+		);
+
+		let commit = |target_hash, target_number, ids: std::ops::Range<u32>| Commit {
+			target_hash,
+			target_number,
+			precommits: ids
+				.map(|id| SignedPrecommit {
+					precommit: Precommit::new(target_hash, target_number),
+					signature: Signature(id),
+					id: Id(id),
+				})
+				.collect(),
+		};
+
+		// set B takes over from round 3, the commits are given out of order.
+		let commits = vec![
+			// signed by the stale set.
+			(4, commit("H", 9, 0..4)),
+			(3, commit("E", 6, 4..7)),
+			(1, commit("C", 4, 0..3)),
+			// not enough precommits.
+			(4, commit("G", 8, 4..6)),
+			// a fork of the highest valid commit.
+			(4, commit("G2", 8, 4..8)),
+			(3, commit("F", 7, 4..8)),
+			(2, commit("D", 5, 0..4)),
+		];
+
+		let result = voter
+			.import_commits(commits, |round| if round < 3 { set_a.clone() } else { set_b.clone() })
+			.unwrap();
+
+		assert_eq!(result, Some(("F", 7)));
+		assert_eq!(voter.last_finalized_in_rounds, ("F", 7));
+		assert_eq!(voter.last_finalized, ("F", 7));
+
+		// only the highest valid commit is finalized.
+		let (hash, number, finalized_commit) = finalized.try_recv().unwrap();
+		assert_eq!((hash, number, finalized_commit.precommits.len()), ("F", 7, 4));
+		assert!(finalized.try_recv().is_err());
+
+		// commits below the finalized block are ignored.
+		assert_eq!(
+			voter.import_commits(vec![(3, commit("E", 6, 4..8))], |_| set_b.clone()),
+			Ok(None)
+		);
+	}
+
+	#[test]
+	fn resuming_reverifies_persisted_signatures() {
+		let voters = VoterSet::new(vec![(Id(0), 1), (Id(1), 1)]).unwrap();
+		let (network, _) = testing::environment::make_network();
+
+		let signed = |message, signature, id| SignedMessage {
+			message,
+			signature: Signature(signature),
+			id: Id(id),
+		};
+
+		// the storage contains a completed round with a tampered prevote
+		// signature.
+		let mut votes = HistoricalVotes::new();
+		votes.push_vote(signed(Message::Prevote(Prevote::new("E", 6)), 0, 0));
+		votes.push_vote(signed(Message::Prevote(Prevote::new("E", 6)), 7, 1));
+		votes.push_vote(signed(Message::Precommit(Precommit::new("E", 6)), 0, 0));
+		votes.push_vote(signed(Message::Precommit(Precommit::new("E", 6)), 1, 1));
+
+		let persistence = Arc::new(persistence::InMemoryPersistence::new());
+		let state = RoundState {
+			round: 1,
+			prevote_ghost: Some(("E", 6)),
+			finalized: Some(("E", 6)),
+			estimate: Some(("E", 6)),
+			completable: true,
+		};
+		persistence.save_completed(1, state, (GENESIS_HASH, 1), &votes).unwrap();
+
+		// an environment that doesn't trust the persisted votes.
+		struct ReverifyingEnvironment(Environment);
+
+		testing::environment::wrap_environment!(ReverifyingEnvironment, 0, {
+			fn reverify_persisted_votes(&self) -> bool {
+				true
+			}
+		});
+
+		let make_env = || {
+			let env =
+				Environment::new(network.clone(), Id(0)).with_persistence(persistence.clone());
+			env.with_chain(|chain| chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]));
+			env
+		};
+
+		// the storage is trusted by default.
+		let voter = Voter::resume(
+			Arc::new(make_env()),
+			voters.clone(),
+			network.make_global_comms(),
+			("E", 6),
+		)
+		.unwrap();
+		assert_eq!(voter.inner.lock().best_round.round_number(), 2);
+
+		let voter = Voter::resume(
+			Arc::new(ReverifyingEnvironment(make_env())),
+			voters.clone(),
+			network.make_global_comms(),
+			("E", 6),
+		);
+		assert_eq!(voter.err(), Some(crate::Error::CorruptPersistence));
+	}
+	#[test]
+	fn enumerating_rounds() {
+		let voters = VoterSet::new((5..9).map(|i| (Id(i), 1))).unwrap();
+
+		let (network, _) = testing::environment::make_network();
+		let env = Arc::new(Environment::new(network.clone(), Id(5)));
+		env.with_chain(|chain| {
+			chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+		});
+
+		let signed = |message, id| SignedMessage { message, signature: Signature(id), id: Id(id) };
+		let votes = |finalized: (&'static str, u32), estimate: (&'static str, u32)| {
+			let mut votes = (5..8)
+				.map(|id| signed(Message::Prevote(Prevote::new(estimate.0, estimate.1)), id))
+				.collect::<Vec<_>>();
+			votes.push(signed(Message::Precommit(Precommit::new(estimate.0, estimate.1)), 5));
+			votes.push(signed(Message::Precommit(Precommit::new(estimate.0, estimate.1)), 6));
+			votes.push(signed(Message::Precommit(Precommit::new(finalized.0, finalized.1)), 7));
+			votes
+		};
+
+		// round 2 finalized `C` and round 1 `B`, neither estimate is finalized.
+		let voter = Voter::new(
+			env.clone(),
+			voters.clone(),
+			network.make_global_comms(),
+			2,
+			votes(("C", 4), ("E", 6)),
+			(GENESIS_HASH, 1),
+			(GENESIS_HASH, 1),
+		);
+
+		{
+			let mut inner = voter.inner.lock();
+			let round = instantiate_last_round(
+				voters,
+				votes(("B", 3), ("D", 5)),
+				1,
+				(GENESIS_HASH, 1),
+				inner.best_round.finalized_sender(),
+				env.clone(),
+			)
+			.unwrap();
+
+			inner.past_rounds.push(&*env, round);
+		}
+
+		assert_eq!(
+			voter.rounds(),
+			vec![
+				report::RoundSummary {
+					number: 3,
+					phase: report::RoundPhase::Start,
+					finalized: None
+				},
+				report::RoundSummary {
+					number: 1,
+					phase: report::RoundPhase::NotVoting,
+					finalized: Some(("B", 3)),
+				},
+				report::RoundSummary {
+					number: 2,
+					phase: report::RoundPhase::NotVoting,
+					finalized: Some(("C", 4)),
+				},
+			],
+		);
+	}
+
+	#[test]
+	fn votes_for_unknown_blocks_are_handled_once_imported() {
+		let local_id = Id(0);
+		let voters = VoterSet::new((0..4).map(|i| (Id(i), 1))).unwrap();
+
+		let (network, routing_task) = testing::environment::make_network();
+		let env = Arc::new(Environment::new(network.clone(), local_id));
+		env.with_chain(|chain| chain.push_blocks(GENESIS_HASH, &["A", "B"]));
+
+		let mut voter = Voter::new(
+			env.clone(),
+			voters,
+			network.make_global_comms(),
+			0,
+			Vec::new(),
+			(GENESIS_HASH, 1),
+			(GENESIS_HASH, 1),
+		);
+
+		let mut pool = LocalPool::new();
+		let spawner = pool.spawner();
+		spawner.spawn(routing_task).unwrap();
+
+		// another voter is ahead of us and prevotes for `C`.
+		let (_, round_sink) = network.make_round_comms(1, Id(1));
+		let prevote = Message::Prevote(Prevote::new("C", 4));
+		spawner
+			.spawn(stream::once(future::ok(prevote)).forward(round_sink).map(|_| ()))
+			.unwrap();
+
+		// poll the voter once the prevote has been routed.
+		pool.run_until_stalled();
+		run_voter_until(&mut pool, &mut voter, |_, _| true);
+		assert_eq!(voter.inner.lock().best_round.prevote_ids().collect::<Vec<_>>(), vec![]);
+
+		env.with_chain(|chain| chain.push_blocks("B", &["C"]));
+		voter.block_imported("C").unwrap();
+
+		assert_eq!(voter.inner.lock().best_round.prevote_ids().collect::<Vec<_>>(), vec![Id(1)]);
+	}
+
+	#[test]
+	fn refuses_to_resume_with_conflicting_persisted_votes() {
+		let local_id = Id(0);
+		let voters = VoterSet::new(vec![(local_id, 1), (Id(1), 1)]).unwrap();
+
+		let (network, routing_task) = testing::environment::make_network();
+
+		// the storage contains two different prevotes of ours in round 1.
+		let persistence = Arc::new(persistence::InMemoryPersistence::new());
+		persistence.save_vote(1, Message::Prevote(Prevote::new("E", 6))).unwrap();
+		persistence.save_vote(1, Message::Prevote(Prevote::new("D", 5))).unwrap();
+
+		let env = Arc::new(
+			Environment::new(network.clone(), local_id).with_persistence(persistence.clone()),
+		);
+		let last_finalized = env.with_chain(|chain| {
+			chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+			chain.last_finalized()
+		});
+
+		let voter = Voter::new(
+			env.clone(),
+			voters,
+			network.make_global_comms(),
+			0,
+			Vec::new(),
+			last_finalized,
+			last_finalized,
+		);
+
+		let mut pool = LocalPool::new();
+		pool.spawner().spawn(routing_task).unwrap();
+
+		assert_eq!(pool.run_until(voter), Err(crate::Error::InconsistentPersistence));
+	}
+
+	#[test]
+	fn finalizing_at_fault_threshold() {
+		// 10 voters
+		let voters = VoterSet::new((0..10).map(|i| (Id(i), 1))).expect("nonempty");
+
+		let (network, routing_task) = testing::environment::make_network();
+		let mut pool = LocalPool::new();
+
+		// 3 voters offline.
+		let finalized_streams = (0..7)
+			.map(|i| {
+				let local_id = Id(i);
+				// initialize chain
+				let env = Arc::new(Environment::new(network.clone(), local_id));
+				let last_finalized = env.with_chain(|chain| {
+					chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+					chain.last_finalized()
+				});
+
+				// run voter in background. scheduling it to shut down at the end.
+				let finalized = env.finalized_stream();
+				let voter = Voter::new(
+					env.clone(),
+					voters.clone(),
+					network.make_global_comms(),
+					0,
+					Vec::new(),
+					last_finalized,
+					last_finalized,
+				);
+
+				pool.spawner().spawn(voter.map(|v| v.expect("Error voting"))).unwrap();
+
+				// wait for the best block to be finalized by all honest voters
+				finalized
+					.take_while(|&(_, n, _)| future::ready(n < 6))
+					.for_each(|_| future::ready(()))
+			})
+			.collect::<Vec<_>>();
+
+		pool.spawner().spawn(routing_task.map(|_| ())).unwrap();
+
+		pool.run_until(future::join_all(finalized_streams));
+	}
+
+	#[test]
+	fn finalized_blocks_are_on_one_chain() {
+		use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+		// the block tree the scenarios are drawn from.
+		//
+		// GENESIS - A - B - C - D - E
+		//               |       \ G1 - G2
+		//               \ F1 - F2 - F3 - F4
+		let tree = {
+			let mut tree = testing::chain::DummyChain::new();
+			tree.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+			tree.push_blocks("D", &["G1", "G2"]);
+			tree.push_blocks("B", &["F1", "F2", "F3", "F4"]);
+			tree
+		};
+		let blocks = ["A", "B", "C", "D", "E", "G1", "G2", "F1", "F2", "F3", "F4"];
+
+		let mut rng = StdRng::seed_from_u64(0x0067_7261_6e64_7061);
+		let mut pool = LocalPool::new();
+
+		let scenarios = (0..100)
+			.map(|_| {
+				let weights =
+					(0..rng.gen_range(4..8)).map(|_| rng.gen_range(1..4)).collect::<Vec<_>>();
+				let voters =
+					VoterSet::new(weights.iter().enumerate().map(|(i, w)| (Id(i as u32), *w)))
+						.expect("nonempty");
+
+				// pick faulty voters with at most the tolerated weight.
+				let tolerated = (voters.total_weight().get() - 1) / 3;
+				let mut order = (0..weights.len()).collect::<Vec<_>>();
+				order.shuffle(&mut rng);
+				let mut faulty_weight = 0;
+				let faulty = order
+					.into_iter()
+					.filter(|&i| {
+						let faulty = rng.gen_bool(0.5) && faulty_weight + weights[i] <= tolerated;
+						if faulty {
+							faulty_weight += weights[i];
+						}
+						faulty
+					})
+					.collect::<HashSet<_>>();
+
+				// the blocks built on `B` which are known to the honest voters.
+				let main_len = rng.gen_range(0..4);
+				let g_len = if main_len >= 2 { rng.gen_range(0..3) } else { 0 };
+				let f_len = rng.gen_range(0..5);
+
+				let (network, routing_task) = testing::environment::make_network();
+				pool.spawner().spawn(routing_task).unwrap();
+
+				let finalized = Arc::new(Mutex::new(Vec::new()));
+				let finalized_streams = (0..weights.len())
+					.filter_map(|i| {
+						let local_id = Id(i as u32);
+
+						// faulty voters are either offline, or vote for arbitrary
+						// blocks in the first rounds. they don't equivocate since
+						// the test environment treats equivocations as errors.
+						if faulty.contains(&i) {
+							if rng.gen_bool(0.5) {
+								for round in 1..4 {
+									let prevote = *blocks.choose(&mut rng).unwrap();
+									let precommit = *blocks.choose(&mut rng).unwrap();
+									let votes = vec![
+										Message::Prevote(Prevote::new(
+											prevote,
+											tree.number(prevote),
+										)),
+										Message::Precommit(Precommit::new(
+											precommit,
+											tree.number(precommit),
+										)),
+									];
+
+									let (_, round_sink) = network.make_round_comms(round, local_id);
+									pool.spawner()
+										.spawn(
+											stream::iter(votes.into_iter().map(Ok))
+												.forward(round_sink)
+												.map(|_| ()),
+										)
+										.unwrap();
+								}
+							}
+
+							return None
+						}
+
+						// honest voters know the same blocks, but the order they
+						// learned about the forks in decides between forks of the
+						// same length.
+						let fork_first = rng.gen_bool(0.5);
+						let env = Arc::new(Environment::new(network.clone(), local_id));
+						env.with_chain(|chain| {
+							chain.break_ties_by_arrival();
+							chain.push_blocks(GENESIS_HASH, &["A", "B"]);
+							if fork_first {
+								chain.push_blocks("B", &["F1", "F2", "F3", "F4"][..f_len]);
+							}
+							chain.push_blocks("B", &["C", "D", "E"][..main_len]);
+							chain.push_blocks("D", &["G1", "G2"][..g_len]);
+							if !fork_first {
+								chain.push_blocks("B", &["F1", "F2", "F3", "F4"][..f_len]);
+							}
+						});
+
+						let voter = Voter::new(
+							env.clone(),
+							voters.clone(),
+							network.make_global_comms(),
+							0,
+							Vec::new(),
+							(GENESIS_HASH, 1),
+							(GENESIS_HASH, 1),
+						);
+						pool.spawner().spawn(voter.map(|v| v.expect("Error voting"))).unwrap();
+
+						let finalized = finalized.clone();
+						Some(
+							env.finalized_stream()
+								.inspect(move |&(hash, number, _)| {
+									finalized.lock().push((hash, number))
+								})
+								.take_while(|&(_, n, _)| future::ready(n < 3))
+								.for_each(|_| future::ready(())),
+						)
+					})
+					.collect::<Vec<_>>();
+
+				future::join_all(finalized_streams).map(move |_| {
+					let finalized = finalized.lock().clone();
+					finalized
+				})
+			})
+			.collect::<Vec<_>>();
+
+		for finalized in pool.run_until(future::join_all(scenarios)) {
+			for (i, a) in finalized.iter().enumerate() {
+				for b in &finalized[i + 1..] {
+					assert!(
+						tree.is_equal_or_descendent_of(a.0, b.0) ||
+							tree.is_equal_or_descendent_of(b.0, a.0),
+						"Safety violation: finalized conflicting blocks {:?} and {:?}",
+						a,
+						b,
+					);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn byzantine_voters_dont_break_safety() {
+		use ByzantineBehavior::*;
+
+		// how a voter deviates from the protocol. the blocks given must be known
+		// to the voter's chain.
+		#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+		enum ByzantineBehavior {
+			// follow every prevote and precommit with a conflicting one for the
+			// given block.
+			Equivocate(&'static str),
+			// prevote and precommit for the given block instead.
+			VoteFor(&'static str),
+			// follow every primary proposal with a conflicting one for the
+			// given block.
+			ConflictingProposals(&'static str),
+			// send no votes or proposals at all.
+			WithholdVotes,
+		}
+
+		impl ByzantineBehavior {
+			fn block(&self) -> Option<&'static str> {
+				match *self {
+					Equivocate(hash) | VoteFor(hash) | ConflictingProposals(hash) => Some(hash),
+					WithholdVotes => None,
+				}
+			}
+
+			// the messages sent instead of the given one, where `number` is the
+			// number of the block misbehaved towards.
+			fn misbehave(
+				&self,
+				message: Message<&'static str, u32>,
+				number: u32,
+			) -> Vec<Message<&'static str, u32>> {
+				let retarget =
+					|message: &Message<&'static str, u32>, hash: &'static str| match message {
+						Message::Prevote(_) => Message::Prevote(Prevote::new(hash, number)),
+						Message::Precommit(_) => Message::Precommit(Precommit::new(hash, number)),
+						Message::PrimaryPropose(_) =>
+							Message::PrimaryPropose(PrimaryPropose::new(hash, number)),
+					};
+
+				match (*self, &message) {
+					(WithholdVotes, _) => Vec::new(),
+					(Equivocate(hash), Message::Prevote(_)) |
+					(Equivocate(hash), Message::Precommit(_)) |
+					(ConflictingProposals(hash), Message::PrimaryPropose(_)) => {
+						let conflicting = retarget(&message, hash);
+						vec![message, conflicting]
+					},
+					(VoteFor(hash), Message::Prevote(_)) |
+					(VoteFor(hash), Message::Precommit(_)) => vec![retarget(&message, hash)],
+					_ => vec![message],
+				}
+			}
+		}
+
+		// an environment whose outgoing messages follow the given behavior.
+		struct ByzantineEnvironment(Environment, ByzantineBehavior);
+
+		impl Chain<&'static str, u32> for ByzantineEnvironment {
+			testing::environment::wrap_environment!(@forward 0: ancestry);
+		}
+
+		impl crate::voter::Environment<&'static str, u32> for ByzantineEnvironment {
+			testing::environment::wrap_environment!(@forward 0:
+				types,
+				best_chain_containing,
+				voting_rule,
+				primary_selector,
+				round_commit_timer,
+				completed,
+				concluded,
+				finalize_block,
+				proposed,
+				prevoted,
+				precommitted,
+				persistence,
+				invariant_level,
+				verify_persisted_vote,
+				is_block_known,
+				prevote_equivocation,
+				precommit_equivocation,
+			);
+
+			fn round_data(
+				&self,
+				round: u64,
+			) -> RoundData<Self::Id, Self::Timer, Self::In, Self::Out> {
+				let behavior = self.1;
+				let number = behavior
+					.block()
+					.map_or(0, |hash| self.0.with_chain(|chain| chain.number(hash)));
+				let round_data = crate::voter::Environment::round_data(&self.0, round);
+				let outgoing = round_data.outgoing.with_flat_map(move |message| {
+					stream::iter(behavior.misbehave(message, number).into_iter().map(Ok))
+				});
+
+				RoundData { outgoing: Box::pin(outgoing), ..round_data }
+			}
+		}
+
+		// GENESIS - A - B - C - D - E
+		//               \ F1 - F2 - F3
+		let tree = {
+			let mut tree = testing::chain::DummyChain::new();
+			tree.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+			tree.push_blocks("B", &["F1", "F2", "F3"]);
+			tree
+		};
+
+		// 7 voters tolerate 2 faulty ones. voter 1 is the primary of the first
+		// round, so it gets to send conflicting proposals.
+		let voters = VoterSet::new((0..7).map(|i| (Id(i), 1))).expect("nonempty");
+		let faulty = [1, 4];
+		let scenarios = [
+			[Equivocate("F3"), Equivocate("F3")],
+			[VoteFor("F3"), WithholdVotes],
+			[ConflictingProposals("F3"), Equivocate("F3")],
+		];
+
+		let mut pool = LocalPool::new();
+		for behaviors in scenarios {
+			let (network, routing_task) = testing::environment::make_network();
+			pool.spawner().spawn(routing_task).unwrap();
+
+			let finalized = Arc::new(Mutex::new(Vec::new()));
+			let mut honest_envs = Vec::new();
+			let finalized_streams = (0..7)
+				.filter_map(|i| {
+					let env = Environment::new(network.clone(), Id(i));
+					env.record_equivocations();
+
+					// the forks are of the same length, so the honest voters
+					// are split by the order they learned about them in.
+					let fork_first = i % 3 == 0;
+					env.with_chain(|chain| {
+						chain.break_ties_by_arrival();
+						chain.push_blocks(GENESIS_HASH, &["A", "B"]);
+						if fork_first {
+							chain.push_blocks("B", &["F1", "F2", "F3"]);
+						}
+						chain.push_blocks("B", &["C", "D", "E"]);
+						if !fork_first {
+							chain.push_blocks("B", &["F1", "F2", "F3"]);
+						}
+					});
+
+					if let Some(f) = faulty.iter().position(|&f| f == i) {
+						let voter = Voter::new(
+							Arc::new(ByzantineEnvironment(env, behaviors[f])),
+							voters.clone(),
+							network.make_global_comms(),
+							0,
+							Vec::new(),
+							(GENESIS_HASH, 1),
+							(GENESIS_HASH, 1),
+						);
+						pool.spawner().spawn(voter.map(|_| ())).unwrap();
+						return None
+					}
+
+					let env = Arc::new(env);
+					let voter = Voter::new(
+						env.clone(),
+						voters.clone(),
+						network.make_global_comms(),
+						0,
+						Vec::new(),
+						(GENESIS_HASH, 1),
+						(GENESIS_HASH, 1),
+					);
+					pool.spawner().spawn(voter.map(|v| v.expect("Error voting"))).unwrap();
+					honest_envs.push(env.clone());
+
+					let finalized = finalized.clone();
+					Some(
+						env.finalized_stream()
+							.inspect(move |&(hash, number, _)| {
+								finalized.lock().push((hash, number))
+							})
+							.take_while(|&(_, n, _)| future::ready(n < 3))
+							.for_each(|_| future::ready(())),
+					)
+				})
+				.collect::<Vec<_>>();
+
+			pool.run_until(future::join_all(finalized_streams));
+
+			let finalized = finalized.lock().clone();
+			for (i, a) in finalized.iter().enumerate() {
+				for b in &finalized[i + 1..] {
+					assert!(
+						tree.is_equal_or_descendent_of(a.0, b.0) ||
+							tree.is_equal_or_descendent_of(b.0, a.0),
+						"Safety violation: finalized conflicting blocks {:?} and {:?}",
+						a,
+						b,
+					);
+				}
+			}
+
+			// exactly the equivocating voters are seen equivocating, before
+			// anything is finalized.
+			let equivocators = faulty
+				.iter()
+				.zip(behaviors)
+				.filter(|(_, behavior)| matches!(behavior, Equivocate(_)))
+				.map(|(&i, _)| Id(i))
+				.collect::<HashSet<_>>();
+
+			for env in &honest_envs {
+				let seen =
+					env.equivocations().into_iter().map(|(_, id)| id).collect::<HashSet<_>>();
+				assert_eq!(seen, equivocators);
+			}
+		}
+	}
+
+	#[test]
+	fn finality_continues_across_voter_set_change() {
+		// set A finalizes up to the block `C` which enacts the change to set B.
+		let set_a = VoterSet::new((0..4).map(|i| (Id(i), 1))).expect("nonempty");
+		let set_b = VoterSet::new((4..8).map(|i| (Id(i), 1))).expect("nonempty");
+
+		let (network, routing_task) = testing::environment::make_network();
+		let mut pool = LocalPool::new();
+
+		let finalized_streams = (0..4)
+			.map(|i| {
+				let env = Arc::new(Environment::new(network.clone(), Id(i)));
+				let last_finalized = env.with_chain(|chain| {
+					chain.push_blocks(GENESIS_HASH, &["A", "B", "C"]);
+					chain.last_finalized()
+				});
+
+				let finalized = env.finalized_stream();
+				let voter = Voter::new(
+					env.clone(),
+					set_a.clone(),
+					network.make_global_comms(),
+					0,
+					Vec::new(),
+					last_finalized,
+					last_finalized,
+				);
+
+				pool.spawner().spawn(voter.map(|v| v.expect("Error voting"))).unwrap();
 
-		let threshold = voters.threshold();
-		if pv < threshold || pc < threshold {
-			trace!(target: "afg",
-				   "Ignoring invalid catch up, missing voter threshold"
-			);
+				finalized
+					.take_while(|&(_, n, _)| future::ready(n < 4))
+					.for_each(|_| future::ready(()))
+			})
+			.collect::<Vec<_>>();
 
-			return None
-		}
-	}
+		pool.spawner().spawn(routing_task).unwrap();
+		pool.run_until(future::join_all(finalized_streams));
 
-	let mut round = crate::round::Round::new(crate::round::RoundParams {
-		round_number: catch_up.round_number,
-		voters: voters.clone(),
-		base: (catch_up.base_hash.clone(), catch_up.base_number),
-	});
+		// set A is stopped and set B starts voting from `C` on a new network.
+		drop(pool);
 
-	// import prevotes first.
-	for crate::SignedPrevote { prevote, id, signature } in catch_up.prevotes {
-		match round.import_prevote(env, prevote, id, signature) {
-			Ok(_) => {},
-			Err(e) => {
-				trace!(target: "afg",
-					   "Ignoring invalid catch up, error importing prevote: {:?}",
-					   e,
-				);
+		let (network, routing_task) = testing::environment::make_network();
+		let mut pool = LocalPool::new();
 
-				return None
-			},
+		// the voters of set A keep voting for a fork in the first round of set B,
+		// with enough weight to finalize it if their votes were counted.
+		for i in 0..4 {
+			let (_, round_sink) = network.make_round_comms(1, Id(i));
+			let votes = vec![
+				Message::Prevote(Prevote::new("X2", 6)),
+				Message::Precommit(Precommit::new("X2", 6)),
+			];
+			pool.spawner()
+				.spawn(stream::iter(votes.into_iter().map(Ok)).forward(round_sink).map(|_| ()))
+				.unwrap();
 		}
-	}
 
-	// then precommits.
-	for crate::SignedPrecommit { precommit, id, signature } in catch_up.precommits {
-		match round.import_precommit(env, precommit, id, signature) {
-			Ok(_) => {},
-			Err(e) => {
-				trace!(target: "afg",
-					   "Ignoring invalid catch up, error importing precommit: {:?}",
-					   e,
-				);
+		let finalized_streams = (4..8)
+			.map(|i| {
+				let env = Arc::new(Environment::new(network.clone(), Id(i)));
+				env.with_chain(|chain| {
+					chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E", "F"]);
+					chain.push_blocks("C", &["X1", "X2"]);
+					chain.set_last_finalized(("C", 4));
+				});
 
-				return None
-			},
-		}
-	}
+				let finalized = env.finalized_stream();
+				let voter = Voter::new(
+					env.clone(),
+					set_b.clone(),
+					network.make_global_comms(),
+					0,
+					Vec::new(),
+					("C", 4),
+					("C", 4),
+				);
 
-	let state = round.state();
-	if !state.completable {
-		return None
-	}
+				pool.spawner().spawn(voter.map(|v| v.expect("Error voting"))).unwrap();
 
-	Some(round)
-}
+				finalized.take_while(|&(_, n, _)| future::ready(n < 7)).for_each(
+					|(hash, _, commit)| {
+						// only votes of set B make it into commits.
+						assert!(!hash.starts_with('X'));
+						assert!(commit.precommits.iter().all(|p| set_b.contains(&p.id)));
+						future::ready(())
+					},
+				)
+			})
+			.collect::<Vec<_>>();
 
-#[cfg(test)]
-mod tests {
-	use super::*;
-	use crate::{
-		testing::{
-			self,
-			chain::GENESIS_HASH,
-			environment::{Environment, Id, Signature},
-		},
-		weights::{VoteWeight, VoterWeight},
-		SignedPrecommit,
-	};
-	use futures::{executor::LocalPool, task::SpawnExt};
-	use futures_timer::Delay;
-	use std::{collections::HashSet, iter, time::Duration};
+		pool.spawner().spawn(routing_task).unwrap();
+		pool.run_until(future::join_all(finalized_streams));
+	}
 
 	#[test]
-	fn talking_to_myself() {
-		let local_id = Id(5);
-		let voters = VoterSet::new(std::iter::once((local_id, 100))).unwrap();
+	fn equally_good_forks_are_chosen_deterministically() {
+		let voters = VoterSet::new((0..4).map(|i| (Id(i), 1))).unwrap();
 
 		let (network, routing_task) = testing::environment::make_network();
+		let mut pool = LocalPool::new();
 
-		let global_comms = network.make_global_comms();
-		let env = Arc::new(Environment::new(network, local_id));
+		let finalized_streams = (0..4)
+			.map(|i| {
+				let env = Arc::new(Environment::new(network.clone(), Id(i)));
 
-		// initialize chain
-		let last_finalized = env.with_chain(|chain| {
-			chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
-			chain.last_finalized()
-		});
+				// the voters learn about the forks in different orders.
+				env.with_chain(|chain| {
+					if i % 2 == 0 {
+						chain.push_blocks(GENESIS_HASH, &["Y1", "Y2"]);
+						chain.push_blocks(GENESIS_HASH, &["X1", "X2"]);
+					} else {
+						chain.push_blocks(GENESIS_HASH, &["X1", "X2"]);
+						chain.push_blocks(GENESIS_HASH, &["Y1", "Y2"]);
+					}
 
-		// run voter in background. scheduling it to shut down at the end.
-		let finalized = env.finalized_stream();
-		let voter = Voter::new(
-			env.clone(),
-			voters,
-			global_comms,
-			0,
-			Vec::new(),
-			last_finalized,
-			last_finalized,
-		);
+					assert_eq!(chain.best_chain_containing(GENESIS_HASH), Some(("X2", 3)));
+				});
+
+				let finalized = env.finalized_stream();
+				let voter = Voter::new(
+					env.clone(),
+					voters.clone(),
+					network.make_global_comms(),
+					0,
+					Vec::new(),
+					(GENESIS_HASH, 1),
+					(GENESIS_HASH, 1),
+				);
+				pool.spawner().spawn(voter.map(|v| v.expect("Error voting"))).unwrap();
+
+				finalized.into_future().map(|(finalized, _)| finalized.unwrap())
+			})
+			.collect::<Vec<_>>();
 
-		let mut pool = LocalPool::new();
-		pool.spawner().spawn(voter.map(|v| v.expect("Error voting"))).unwrap();
 		pool.spawner().spawn(routing_task).unwrap();
 
-		// wait for the best block to finalize.
-		pool.run_until(
-			finalized
-				.take_while(|&(_, n, _)| future::ready(n < 6))
-				.for_each(|_| future::ready(())),
-		)
+		// all voters prevote for the same fork, so it is finalized right away.
+		for (hash, number, _) in pool.run_until(future::join_all(finalized_streams)) {
+			assert_eq!((hash, number), ("X2", 3));
+		}
 	}
 
 	#[test]
-	fn finalizing_at_fault_threshold() {
-		// 10 voters
-		let voters = VoterSet::new((0..10).map(|i| (Id(i), 1))).expect("nonempty");
+	fn view_change_recovers_from_stalled_rounds() {
+		// an environment requesting a view change after two stalled rounds,
+		// which resyncs it with the block the voters agree on.
+		struct ViewChangeEnvironment(Environment, Mutex<Vec<u64>>);
+
+		testing::environment::wrap_environment!(ViewChangeEnvironment, 0, {
+			fn view_change_threshold(&self) -> Option<usize> {
+				Some(2)
+			}
+
+			fn view_change(&self, round: u64) {
+				self.1.lock().push(round);
+				self.0.with_chain(|chain| chain.push_blocks("X2", &["X3"]));
+			}
+		});
+
+		let voters = VoterSet::new((0..4).map(|i| (Id(i), 1))).unwrap();
 
 		let (network, routing_task) = testing::environment::make_network();
 		let mut pool = LocalPool::new();
 
-		// 3 voters offline.
-		let finalized_streams = (0..7)
+		// the node with id 9 is an observer.
+		let (envs, finalized_streams): (Vec<_>, Vec<_>) = [0, 1, 2, 3, 9]
+			.into_iter()
 			.map(|i| {
-				let local_id = Id(i);
-				// initialize chain
-				let env = Arc::new(Environment::new(network.clone(), local_id));
-				let last_finalized = env.with_chain(|chain| {
-					chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
-					chain.last_finalized()
+				let env = Environment::new(network.clone(), Id(i));
+
+				// the voters know about both forks but are split on which one is
+				// best, so they only finalize the base.
+				env.with_chain(|chain| {
+					chain.break_ties_by_arrival();
+					if i % 2 == 0 {
+						chain.push_blocks(GENESIS_HASH, &["Y1", "Y2"]);
+						chain.push_blocks(GENESIS_HASH, &["X1", "X2"]);
+					} else {
+						chain.push_blocks(GENESIS_HASH, &["X1", "X2"]);
+						chain.push_blocks(GENESIS_HASH, &["Y1", "Y2"]);
+					}
+
+					// the observer is synced with the block the voters agree on.
+					if i == 9 {
+						chain.push_blocks("X2", &["X3"]);
+					}
 				});
 
-				// run voter in background. scheduling it to shut down at the end.
-				let finalized = env.finalized_stream();
+				let env = Arc::new(ViewChangeEnvironment(env, Mutex::new(Vec::new())));
+				let finalized = env.0.finalized_stream();
 				let voter = Voter::new(
 					env.clone(),
 					voters.clone(),
 					network.make_global_comms(),
 					0,
 					Vec::new(),
-					last_finalized,
-					last_finalized,
+					(GENESIS_HASH, 1),
+					(GENESIS_HASH, 1),
 				);
-
 				pool.spawner().spawn(voter.map(|v| v.expect("Error voting"))).unwrap();
 
-				// wait for the best block to be finalized by all honest voters
-				finalized
-					.take_while(|&(_, n, _)| future::ready(n < 6))
-					.for_each(|_| future::ready(()))
+				(env, finalized.into_future().map(|(finalized, _)| finalized.unwrap()))
 			})
-			.collect::<Vec<_>>();
+			.unzip();
 
-		pool.spawner().spawn(routing_task.map(|_| ())).unwrap();
+		pool.spawner().spawn(routing_task).unwrap();
+
+		// after the view change the voters agree on `X3`.
+		for (hash, number, _) in pool.run_until(future::join_all(finalized_streams)) {
+			assert_eq!((hash, number), ("X3", 4));
+		}
 
-		pool.run_until(future::join_all(finalized_streams.into_iter()));
+		let (observer, voters) = envs.split_last().unwrap();
+		for env in voters {
+			assert_eq!(*env.1.lock(), vec![2]);
+		}
+
+		// the observer follows the stalled rounds without requesting a view
+		// change.
+		assert!(observer.1.lock().is_empty());
 	}
 
 	#[test]
@@ -1222,6 +4158,8 @@ mod tests {
 			prevote_ids: Default::default(),
 			precommit_current_weight: VoteWeight(0),
 			precommit_ids: Default::default(),
+			precommit_gated: 0,
+			completability_blockers: vec![Blocker::NoPrevoteGhost, Blocker::NoEstimate],
 		};
 
 		assert_eq!(
@@ -1233,7 +4171,7 @@ mod tests {
 		);
 
 		pool.spawner().spawn(routing_task.map(|_| ())).unwrap();
-		pool.run_until(future::join_all(finalized_streams.into_iter()));
+		pool.run_until(future::join_all(finalized_streams));
 
 		assert_eq!(voter_state.get().best_round, (2, expected_round_state.clone()));
 	}
@@ -1332,14 +4270,10 @@ mod tests {
 					.into_future()
 					.then(|(value, stream)| {
 						// wait for a prevote
-						assert!(match value {
-							Some(Ok(SignedMessage {
-								message: Message::Prevote(_),
-								id: Id(5),
-								..
-							})) => true,
-							_ => false,
-						});
+						assert!(matches!(
+							value,
+							Some(Ok(SignedMessage { message: Message::Prevote(_), id: Id(5), .. }))
+						));
 						let votes = vec![prevote, precommit].into_iter().map(Result::Ok);
 						futures::stream::iter(votes).forward(round_sink).map(|_| stream) // send our prevote
 					})
@@ -1444,13 +4378,77 @@ mod tests {
 		assert_eq!(finalized, commit);
 	}
 
+	#[test]
+	fn malformed_commit_does_not_stop_voter() {
+		let local_id = Id(5);
+		let test_id = Id(42);
+		let voters =
+			VoterSet::new([(local_id, 100), (test_id, 201)].iter().cloned()).expect("nonempty");
+
+		let (network, routing_task) = testing::environment::make_network();
+		let (_, commits_sink) = network.make_global_comms();
+
+		let commit = |precommits: Vec<(Id, &'static str, u32)>| Commit {
+			target_hash: "E",
+			target_number: 6,
+			precommits: precommits
+				.into_iter()
+				.map(|(id, target_hash, target_number)| SignedPrecommit {
+					precommit: Precommit { target_hash, target_number },
+					signature: Signature(id.0),
+					id,
+				})
+				.collect(),
+		};
+
+		// `E` is block 6, but the commit claims a higher number for it.
+		let malformed = commit(vec![(local_id, "A", 2), (test_id, "E", 9)]);
+		let valid = commit(vec![(test_id, "E", 6)]);
+
+		let global_comms = network.make_global_comms();
+		let env = Arc::new(Environment::new(network, local_id));
+
+		let last_finalized = env.with_chain(|chain| {
+			chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+			chain.last_finalized()
+		});
+
+		let voter = Voter::new(
+			env.clone(),
+			voters.clone(),
+			global_comms,
+			1,
+			Vec::new(),
+			last_finalized,
+			last_finalized,
+		);
+
+		let mut pool = LocalPool::new();
+		pool.spawner().spawn(voter.map(|v| v.expect("Error voting"))).unwrap();
+		pool.spawner().spawn(routing_task.map(|_| ())).unwrap();
+
+		let commits = vec![
+			Ok(CommunicationOut::Commit(0, malformed)),
+			Ok(CommunicationOut::Commit(0, valid.clone())),
+		];
+		pool.spawner()
+			.spawn(stream::iter(commits).forward(commits_sink).map(|_| ()))
+			.unwrap();
+
+		// the voter skips the malformed commit and imports the next one.
+		let finalized = pool
+			.run_until(env.finalized_stream().into_future().map(move |(msg, _)| msg.unwrap().2));
+
+		assert_eq!(finalized, valid);
+	}
+
 	#[test]
 	fn skips_to_latest_round_after_catch_up() {
 		// 3 voters
 		let voters = VoterSet::new((0..3).map(|i| (Id(i), 1u64))).expect("nonempty");
 		let total_weight = voters.total_weight();
 		let threshold_weight = voters.threshold();
-		let voter_ids: HashSet<Id> = (0..3).map(|i| Id(i)).collect();
+		let voter_ids: HashSet<Id> = (0..3).map(Id).collect();
 
 		let (network, routing_task) = testing::environment::make_network();
 		let mut pool = LocalPool::new();
@@ -1536,6 +4534,8 @@ mod tests {
 					prevote_ids: Default::default(),
 					precommit_current_weight: VoteWeight(0),
 					precommit_ids: Default::default(),
+					precommit_gated: 0,
+					completability_blockers: vec![Blocker::NoPrevoteGhost, Blocker::NoEstimate],
 				}
 			)
 		);
@@ -1549,10 +4549,67 @@ mod tests {
 				prevote_ids: voter_ids.clone(),
 				precommit_current_weight: VoteWeight(3),
 				precommit_ids: voter_ids,
+				precommit_gated: 0,
+				completability_blockers: Vec::new(),
 			})
 		);
 	}
 
+	#[test]
+	fn answers_catch_up_requests_of_lagging_voters() {
+		let voters = VoterSet::new((0..3).map(|i| (Id(i), 1u64))).expect("nonempty");
+
+		let (network, routing_task) = testing::environment::make_network();
+		let mut pool = LocalPool::new();
+		pool.spawner().spawn(routing_task.map(|_| ())).unwrap();
+
+		let env = Arc::new(Environment::new(network.clone(), Id(4)));
+		let last_finalized = env.with_chain(|chain| {
+			chain.push_blocks(GENESIS_HASH, &["A", "B", "C", "D", "E"]);
+			chain.last_finalized()
+		});
+
+		let mut voter = Voter::new(
+			env.clone(),
+			voters,
+			network.make_global_comms(),
+			0,
+			Vec::new(),
+			last_finalized,
+			last_finalized,
+		);
+
+		// no round has been completed yet.
+		assert_eq!(voter.catch_up(), None);
+
+		let pv = |id| crate::SignedPrevote {
+			prevote: crate::Prevote { target_hash: "C", target_number: 4 },
+			id: Id(id),
+			signature: Signature(id),
+		};
+
+		let pc = |id| crate::SignedPrecommit {
+			precommit: crate::Precommit { target_hash: "C", target_number: 4 },
+			id: Id(id),
+			signature: Signature(id),
+		};
+
+		let mut catch_up = CatchUp {
+			base_number: 1,
+			base_hash: GENESIS_HASH,
+			round_number: 5,
+			prevotes: vec![pv(2), pv(0), pv(1)],
+			precommits: vec![pc(1), pc(2), pc(0)],
+		};
+		network.send_message(CommunicationIn::CatchUp(catch_up.clone(), Callback::Blank));
+
+		// once caught up, the voter proves round 5 to voters lagging behind.
+		run_voter_until(&mut pool, &mut voter, |_, voter| voter.catch_up().is_some());
+
+		catch_up.canonicalize();
+		assert_eq!(voter.catch_up(), Some(catch_up));
+	}
+
 	#[test]
 	fn pick_up_from_prior_without_grandparent_state() {
 		let local_id = Id(5);
@@ -78,7 +78,22 @@ where
 		//   - if we skipped forward we may never complete this round and we don't need
 		//     to keep it forever.
 		self.round_committer.is_none() &&
-			self.inner.round_state().estimate.map_or(true, |x| x.1 <= self.finalized_number)
+			self.inner.estimate().is_none_or(|x| x.1 <= self.finalized_number)
+	}
+
+	fn block_imported(&mut self, hash: &H) -> Result<(), E::Error> {
+		self.inner.block_imported(hash)?;
+
+		// the imported votes may allow the round to be committed or concluded.
+		if let Some(ref waker) = self.waker {
+			waker.wake_by_ref();
+		}
+
+		Ok(())
+	}
+
+	fn prune(&mut self, hash: H, number: N) {
+		self.inner.prune(hash, number);
 	}
 
 	fn update_finalized(&mut self, new_finalized: N) {
@@ -291,6 +306,29 @@ where
 		}
 	}
 
+	/// prune the blocks below the given finalized block from the vote-graphs
+	/// of the background rounds.
+	pub(super) fn prune(&mut self, hash: H, number: N) {
+		for bg in self.past_rounds.iter_mut() {
+			bg.mutate(|f| f.prune(hash.clone(), number));
+		}
+	}
+
+	/// handle the votes for the given block which were kept by the background
+	/// rounds until it was imported.
+	pub(super) fn block_imported(&mut self, hash: &H) -> Result<(), E::Error> {
+		let mut result = Ok(());
+		for bg in self.past_rounds.iter_mut() {
+			bg.mutate(|f| {
+				if result.is_ok() {
+					result = f.block_imported(hash);
+				}
+			});
+		}
+
+		result
+	}
+
 	/// Get the underlying `VotingRound` items that are being run in the background.
 	pub(super) fn voting_rounds(&self) -> impl Iterator<Item = &VotingRound<H, N, E>> {
 		self.past_rounds
@@ -299,6 +337,24 @@ where
 			.map(|background_round| background_round.voting_round())
 	}
 
+	/// Send the votes cast in the background rounds which haven't been sent yet.
+	/// Ready once all rounds are flushed.
+	pub(super) fn poll_flush(&mut self, cx: &mut Context) -> Poll<Result<(), E::Error>> {
+		let mut result = Poll::Ready(Ok(()));
+		for bg in self.past_rounds.iter_mut() {
+			bg.mutate(|f| match f.inner.poll_flush(cx) {
+				Poll::Ready(Ok(())) => {},
+				Poll::Ready(Err(e)) => result = Poll::Ready(Err(e)),
+				Poll::Pending =>
+					if let Poll::Ready(Ok(())) = result {
+						result = Poll::Pending
+					},
+			});
+		}
+
+		result
+	}
+
 	// import the commit into the given backgrounded round. If not possible,
 	// just return and process the commit.
 	pub(super) fn import_commit(
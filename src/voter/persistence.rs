@@ -0,0 +1,191 @@
+// Copyright 2018-2019 Parity Technologies (UK) Ltd
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Storage of the voter state which must survive a restart.
+//!
+//! A voter which forgets the votes it has cast in the current round may cast
+//! a conflicting vote after restarting, which is indistinguishable from an
+//! equivocation. The voter saves every vote it casts before broadcasting it and
+//! re-sends the saved votes instead of casting new ones when a round is resumed.
+//! Completed rounds are saved so that they can be used to resume the voter with
+//! `Voter::new`.
+
+use parking_lot::Mutex;
+use std::{collections::BTreeMap, marker::PhantomData};
+
+use crate::{round::State as RoundState, HistoricalVotes, Message};
+
+/// A round which has been completed, as saved by the voter.
+#[derive(Clone, PartialEq)]
+#[cfg_attr(test, derive(Debug))]
+pub struct CompletedRound<H, N, S, Id> {
+	/// The round number.
+	pub number: u64,
+	/// The state of the round when it was completed.
+	pub state: RoundState<H, N>,
+	/// The base block of the round.
+	pub base: (H, N),
+	/// All the votes seen in the round.
+	pub votes: HistoricalVotes<H, N, S, Id>,
+}
+
+/// The state of a voter which has been shut down, see `Voter::shutdown`.
+#[derive(Clone, PartialEq)]
+#[cfg_attr(test, derive(Debug))]
+pub struct VoterSnapshot<H, N, S, Id> {
+	/// The last block finalized by the voter.
+	pub last_finalized: (H, N),
+	/// The last round which has been completed, if any.
+	pub last_completed: Option<CompletedRound<H, N, S, Id>>,
+	/// The round the voter was voting in.
+	pub round: u64,
+	/// The votes cast by the local voter in `round`, as saved to the storage.
+	pub votes: Vec<Message<H, N>>,
+}
+
+/// A storage backend for the voter.
+///
+/// All methods are called from within the voter's future, so they should not
+/// block for long.
+pub trait Persistence<H, N, S, Id> {
+	/// The error returned when reading from or writing to the storage fails.
+	type Error;
+
+	/// Save a vote cast by the local voter in the given round. This is called
+	/// before the vote is broadcast, so the vote must be durably stored when
+	/// this returns.
+	fn save_vote(&self, round: u64, vote: Message<H, N>) -> Result<(), Self::Error>;
+
+	/// Load the votes cast by the local voter in the given round, in the order
+	/// they were saved.
+	fn load_round(&self, round: u64) -> Result<Vec<Message<H, N>>, Self::Error>;
+
+	/// Save a round which has been completed.
+	fn save_completed(
+		&self,
+		round: u64,
+		state: RoundState<H, N>,
+		base: (H, N),
+		votes: &HistoricalVotes<H, N, S, Id>,
+	) -> Result<(), Self::Error>;
+
+	/// Load the last round which has been completed, if any.
+	fn load_completed(&self) -> Result<Option<CompletedRound<H, N, S, Id>>, Self::Error>;
+}
+
+/// A `Persistence` implementation which keeps everything in memory.
+///
+/// This doesn't survive a process restart, but it is useful for tests and
+/// for restarting a voter within the same process.
+pub struct InMemoryPersistence<H, N, S, Id, E> {
+	votes: Mutex<BTreeMap<u64, Vec<Message<H, N>>>>,
+	completed: Mutex<Option<CompletedRound<H, N, S, Id>>>,
+	_error: PhantomData<fn() -> E>,
+}
+
+impl<H, N, S, Id, E> InMemoryPersistence<H, N, S, Id, E> {
+	/// Create a new, empty, in-memory storage.
+	pub fn new() -> Self {
+		InMemoryPersistence {
+			votes: Mutex::new(BTreeMap::new()),
+			completed: Mutex::new(None),
+			_error: PhantomData,
+		}
+	}
+}
+
+impl<H, N, S, Id, E> Default for InMemoryPersistence<H, N, S, Id, E> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<H, N, S, Id, E> Persistence<H, N, S, Id> for InMemoryPersistence<H, N, S, Id, E>
+where
+	H: Clone,
+	N: Clone,
+	S: Clone,
+	Id: Clone,
+{
+	type Error = E;
+
+	fn save_vote(&self, round: u64, vote: Message<H, N>) -> Result<(), E> {
+		self.votes.lock().entry(round).or_default().push(vote);
+		Ok(())
+	}
+
+	fn load_round(&self, round: u64) -> Result<Vec<Message<H, N>>, E> {
+		Ok(self.votes.lock().get(&round).cloned().unwrap_or_default())
+	}
+
+	fn save_completed(
+		&self,
+		round: u64,
+		state: RoundState<H, N>,
+		base: (H, N),
+		votes: &HistoricalVotes<H, N, S, Id>,
+	) -> Result<(), E> {
+		let mut completed = self.completed.lock();
+
+		// a round may be completed again after catching up, only keep the latest.
+		if completed.as_ref().is_none_or(|c| c.number <= round) {
+			*completed = Some(CompletedRound { number: round, state, base, votes: votes.clone() });
+		}
+
+		// our votes in rounds up to and including the completed one are no longer
+		// needed, since we never vote in them again.
+		let mut votes = self.votes.lock();
+		*votes = votes.split_off(&(round + 1));
+
+		Ok(())
+	}
+
+	fn load_completed(&self) -> Result<Option<CompletedRound<H, N, S, Id>>, E> {
+		Ok(self.completed.lock().clone())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{Error, Precommit, Prevote};
+
+	type Storage = InMemoryPersistence<&'static str, u32, u32, u32, Error>;
+
+	#[test]
+	fn votes_are_pruned_on_completion() {
+		let storage = Storage::new();
+
+		let prevote = Message::Prevote(Prevote::new("A", 2));
+		let precommit = Message::Precommit(Precommit::new("A", 2));
+
+		storage.save_vote(1, prevote.clone()).unwrap();
+		storage.save_vote(1, precommit.clone()).unwrap();
+		storage.save_vote(2, prevote.clone()).unwrap();
+
+		assert_eq!(storage.load_round(1).unwrap(), vec![prevote.clone(), precommit]);
+
+		let state = RoundState::genesis(("A", 2));
+		storage
+			.save_completed(1, state.clone(), ("A", 2), &HistoricalVotes::new())
+			.unwrap();
+
+		assert!(storage.load_round(1).unwrap().is_empty());
+		assert_eq!(storage.load_round(2).unwrap(), vec![prevote]);
+
+		// completing an older round doesn't override the last completed round.
+		storage.save_completed(0, state, ("A", 2), &HistoricalVotes::new()).unwrap();
+		assert_eq!(storage.load_completed().unwrap().map(|c| c.number), Some(1));
+	}
+}
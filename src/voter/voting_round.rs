@@ -19,20 +19,24 @@ use futures::ready;
 use futures::{channel::mpsc::UnboundedSender, prelude::*};
 #[cfg(feature = "std")]
 use log::{debug, trace, warn};
+use parking_lot::Mutex;
 
 use std::{
+	collections::VecDeque,
+	mem,
 	pin::Pin,
 	sync::Arc,
 	task::{Context, Poll},
+	time::Instant,
 };
 
-use super::{Buffered, Environment, FinalizedNotification};
+use super::{report, Buffered, Environment, FinalizedNotification, VoteDropReason};
 use crate::{
-	round::{Round, State as RoundState},
+	round::{Blocker, Round, State as RoundState},
 	validate_commit,
 	voter_set::VoterSet,
 	weights::VoteWeight,
-	BlockNumberOps, Commit, HistoricalVotes, ImportResult, Message, Precommit, Prevote,
+	BlockNumberOps, CatchUp, Commit, HistoricalVotes, ImportResult, Message, Precommit, Prevote,
 	PrimaryPropose, SignedMessage, SignedPrecommit,
 };
 
@@ -57,6 +61,35 @@ impl<T, W> std::fmt::Debug for State<T, W> {
 	}
 }
 
+/// The result of the last call to `Environment::best_chain_containing`, shared
+/// between rounds. See `Environment::best_block_generation`.
+pub(super) struct BestChainCache<H, N>(Mutex<Option<CachedBestChain<H, N>>>);
+
+struct CachedBestChain<H, N> {
+	base: H,
+	generation: u64,
+	best_chain: Option<(H, N)>,
+}
+
+impl<H: Clone + Eq, N: Clone> BestChainCache<H, N> {
+	pub(super) fn new() -> Self {
+		BestChainCache(Mutex::new(None))
+	}
+
+	// get the cached best chain containing `base`, if it was computed at the given generation.
+	fn get(&self, base: &H, generation: u64) -> Option<Option<(H, N)>> {
+		match *self.0.lock() {
+			Some(ref cached) if cached.base == *base && cached.generation == generation =>
+				Some(cached.best_chain.clone()),
+			_ => None,
+		}
+	}
+
+	fn insert(&self, base: H, generation: u64, best_chain: Option<(H, N)>) {
+		*self.0.lock() = Some(CachedBestChain { base, generation, best_chain });
+	}
+}
+
 /// Logic for a voter on a specific round.
 pub(super) struct VotingRound<H, N, E: Environment<H, N>>
 where
@@ -68,10 +101,25 @@ where
 	votes: Round<E::Id, H, N, E::Signature>,
 	incoming: E::In,
 	outgoing: Buffered<E::Out, Message<H, N>>,
-	state: Option<State<E::Timer, (H, E::BestChain)>>, // state machine driving votes.
+	// state machine driving votes.
+	state: Option<State<E::Timer, ((H, N), Option<u64>, E::BestChain, Option<E::Timer>)>>,
 	bridged_round_state: Option<crate::bridge_state::PriorView<H, N>>, // updates to later round
-	last_round_state: Option<crate::bridge_state::LatterView<H, N>>, // updates from prior round
-	primary_block: Option<(H, N)>,                     // a block posted by primary as a hint.
+	last_round_state: Option<crate::bridge_state::LatterView<H, N>>,   // updates from prior round
+	primary_voter: E::Id,          // the voter whose primary proposals we accept.
+	primary_block: Option<(H, N)>, // a block posted by primary as a hint.
+	best_chain_cache: Arc<BestChainCache<H, N>>,
+	unproductive_polls: usize, // polls without prevoting, see `check_stuck`.
+	prevote_timer_rearmed: usize, // see `Environment::rearm_prevote_timer`.
+	best_chain_retries: usize, // see `Environment::best_chain_retry_timer`.
+	precommit_gated: usize,    // polls where the p-GHOST didn't include the last round estimate.
+	precommit_gated_since: Option<Instant>, // when the p-GHOST first didn't include it.
+	restored: bool,            // whether the votes we cast before a restart have been restored.
+	pending_votes: VecDeque<SignedMessage<H, N, E::Signature, E::Id>>, // votes for blocks not imported yet.
+	silence: Vec<Option<E::Timer>>, // by voter position, see `check_silence`.
+	started: Instant,               // when the round was started.
+	noted_completable: bool,        // whether `Environment::round_completable` was called.
+	caught_up: bool,                // whether the round was started after catching up.
+	precommit_timer_fired: bool,    // see `poll_precommit_timer`.
 	finalized_sender: UnboundedSender<FinalizedNotification<H, N, E>>,
 	best_finalized: Option<Commit<H, N, E::Signature, E::Id>>,
 }
@@ -111,21 +159,94 @@ where
 		base: (H, N),
 		last_round_state: Option<crate::bridge_state::LatterView<H, N>>,
 		finalized_sender: UnboundedSender<FinalizedNotification<H, N, E>>,
+		best_chain_cache: Arc<BestChainCache<H, N>>,
+		env: Arc<E>,
+	) -> VotingRound<H, N, E> {
+		Self::start(
+			round_number,
+			voters,
+			base,
+			last_round_state,
+			finalized_sender,
+			best_chain_cache,
+			env,
+			false,
+		)
+	}
+
+	/// Create a new round in which we never vote, even if the local voter id
+	/// given in the `RoundData` is part of the voter set. Incoming votes are
+	/// still imported, so the round is completed as usual.
+	pub(super) fn new_observer(
+		round_number: u64,
+		voters: VoterSet<E::Id>,
+		base: (H, N),
+		last_round_state: Option<crate::bridge_state::LatterView<H, N>>,
+		finalized_sender: UnboundedSender<FinalizedNotification<H, N, E>>,
+		best_chain_cache: Arc<BestChainCache<H, N>>,
+		env: Arc<E>,
+	) -> VotingRound<H, N, E> {
+		Self::start(
+			round_number,
+			voters,
+			base,
+			last_round_state,
+			finalized_sender,
+			best_chain_cache,
+			env,
+			true,
+		)
+	}
+
+	#[allow(clippy::too_many_arguments)]
+	fn start(
+		round_number: u64,
+		voters: VoterSet<E::Id>,
+		base: (H, N),
+		last_round_state: Option<crate::bridge_state::LatterView<H, N>>,
+		finalized_sender: UnboundedSender<FinalizedNotification<H, N, E>>,
+		best_chain_cache: Arc<BestChainCache<H, N>>,
 		env: Arc<E>,
+		observer: bool,
 	) -> VotingRound<H, N, E> {
+		debug_assert!(
+			last_round_state
+				.as_ref()
+				.is_none_or(|view| view.latest().round + 1 == round_number),
+			"round-state is only bridged to the following round",
+		);
+
 		let round_data = env.round_data(round_number);
 		let round_params = crate::round::RoundParams { voters, base, round_number };
 
-		let votes = Round::new(round_params);
+		let mut votes = Round::new(round_params);
+		if let Some(depth) = env.sparse_votes_depth() {
+			votes.flag_sparse_votes(depth);
+		}
 
-		let voting = if round_data.voter_id.as_ref() == Some(votes.primary_voter().0) {
+		let primary_voter = votes.primary_voter(env.primary_selector()).0.clone();
+		let voting = if observer {
+			Voting::No
+		} else if round_data.voter_id.as_ref() == Some(&primary_voter) {
 			Voting::Primary
-		} else if round_data.voter_id.as_ref().map_or(false, |id| votes.voters().contains(id)) {
+		} else if round_data.voter_id.as_ref().is_some_and(|id| votes.voters().contains(id)) {
 			Voting::Yes
 		} else {
 			Voting::No
 		};
 
+		let silence = votes
+			.voters()
+			.iter()
+			.map(|(id, _)| {
+				if round_data.voter_id.as_ref() == Some(id) {
+					None
+				} else {
+					env.voter_silence_timer(round_number, id)
+				}
+			})
+			.collect();
+
 		VotingRound {
 			votes,
 			voting,
@@ -133,7 +254,22 @@ where
 			outgoing: Buffered::new(round_data.outgoing),
 			state: Some(State::Start(round_data.prevote_timer, round_data.precommit_timer)),
 			bridged_round_state: None,
+			primary_voter,
 			primary_block: None,
+			best_chain_cache,
+			unproductive_polls: 0,
+			prevote_timer_rearmed: 0,
+			best_chain_retries: 0,
+			precommit_gated: 0,
+			precommit_gated_since: None,
+			// an observer doesn't resend the votes cast before a restart either.
+			restored: observer,
+			pending_votes: VecDeque::new(),
+			silence,
+			started: Instant::now(),
+			noted_completable: false,
+			caught_up: false,
+			precommit_timer_fired: false,
 			best_finalized: None,
 			env,
 			last_round_state,
@@ -149,7 +285,15 @@ where
 		last_round_state: Option<crate::bridge_state::LatterView<H, N>>,
 		env: Arc<E>,
 	) -> VotingRound<H, N, E> {
+		debug_assert!(
+			last_round_state
+				.as_ref()
+				.is_none_or(|view| view.latest().round + 1 == votes.number()),
+			"round-state is only bridged to the following round",
+		);
+
 		let round_data = env.round_data(votes.number());
+		let primary_voter = votes.primary_voter(env.primary_selector()).0.clone();
 
 		VotingRound {
 			votes,
@@ -158,7 +302,21 @@ where
 			outgoing: Buffered::new(round_data.outgoing),
 			state: None,
 			bridged_round_state: None,
+			primary_voter,
 			primary_block: None,
+			best_chain_cache: Arc::new(BestChainCache::new()),
+			unproductive_polls: 0,
+			prevote_timer_rearmed: 0,
+			best_chain_retries: 0,
+			precommit_gated: 0,
+			precommit_gated_since: None,
+			restored: true,
+			pending_votes: VecDeque::new(),
+			silence: Vec::new(),
+			started: Instant::now(),
+			noted_completable: true,
+			caught_up: false,
+			precommit_timer_fired: false,
 			env,
 			last_round_state,
 			finalized_sender,
@@ -174,17 +332,26 @@ where
 		let pre_state = self.votes.state();
 		self.process_incoming(cx)?;
 
+		if !self.votes.completable() {
+			self.check_silence(cx)?;
+		}
+
+		if !self.restored {
+			self.restore_votes()?;
+		}
+
 		// we only cast votes when we have access to the previous round state.
 		// we might have started this round as a prospect "future" round to
 		// check whether the voter is lagging behind the current round.
-		let last_round_state = self.last_round_state.as_ref().map(|s| s.get(cx).clone());
+		let last_round_state =
+			self.last_round_state.as_mut().map(|s| s.get(cx)).map(|s| self.or_genesis(s));
 		if let Some(ref last_round_state) = last_round_state {
 			self.primary_propose(last_round_state)?;
 			self.prevote(cx, last_round_state)?;
 			self.precommit(cx, last_round_state)?;
 		}
 
-		ready!(self.outgoing.poll(cx))?;
+		ready!(self.poll_outgoing(cx))?;
 		self.process_incoming(cx)?; // in case we got a new message signed locally.
 
 		// broadcast finality notifications after attempting to cast votes
@@ -196,35 +363,13 @@ where
 			return Poll::Pending
 		}
 
-		// make sure that the previous round estimate has been finalized
-		let last_round_estimate_finalized = match last_round_state {
-			Some(RoundState {
-				estimate: Some((_, last_round_estimate)),
-				finalized: Some((_, last_round_finalized)),
-				..
-			}) => {
-				// either it was already finalized in the previous round
-				let finalized_in_last_round = last_round_estimate <= last_round_finalized;
-
-				// or it must be finalized in the current round
-				let finalized_in_current_round =
-					self.finalized().map_or(false, |(_, current_round_finalized)| {
-						last_round_estimate <= *current_round_finalized
-					});
-
-				finalized_in_last_round || finalized_in_current_round
-			},
-			None => {
-				// NOTE: when we catch up to a round we complete the round
-				// without any last round state. in this case we already started
-				// a new round after we caught up so this guard is unneeded.
-				true
-			},
-			_ => false,
-		};
+		if !self.noted_completable {
+			self.noted_completable = true;
+			self.env.round_completable(self.round_number(), self.started.elapsed());
+		}
 
 		// the previous round estimate must be finalized
-		if !last_round_estimate_finalized {
+		if self.completability_blockers().contains(&Blocker::PreviousEstimateNotFinalized) {
 			trace!(target: "afg", "Round {} completable but estimate not finalized.", self.round_number());
 			self.log_participation(log::Level::Trace);
 			return Poll::Pending
@@ -240,13 +385,68 @@ where
 	}
 
 	/// Inspect the state of this round.
-	pub(super) fn state(&self) -> Option<&State<E::Timer, (H, E::BestChain)>> {
+	pub(super) fn state(
+		&self,
+	) -> Option<&State<E::Timer, ((H, N), Option<u64>, E::BestChain, Option<E::Timer>)>> {
 		self.state.as_ref()
 	}
 
+	/// Whether we cast votes in this round, i.e. the local voter id given in
+	/// the `RoundData` is part of the voter set. Observers don't.
+	pub(super) fn is_voting(&self) -> bool {
+		self.voting.is_active()
+	}
+
+	/// The phase the round is in.
+	pub(super) fn phase(&self) -> report::RoundPhase {
+		match self.state {
+			Some(State::Start(..)) => report::RoundPhase::Start,
+			Some(State::Proposed(..)) => report::RoundPhase::Proposed,
+			Some(State::Prevoting(..)) => report::RoundPhase::Prevoting,
+			Some(State::Prevoted(..)) => report::RoundPhase::Prevoted,
+			Some(State::Precommitted) => report::RoundPhase::Precommitted,
+			None => report::RoundPhase::NotVoting,
+		}
+	}
+
+	/// The conditions which keep this round from being completed.
+	pub(super) fn completability_blockers(&self) -> Vec<Blocker> {
+		match self.last_round_state {
+			Some(ref last_round_state) => {
+				let last_round_state = self.or_genesis(last_round_state.latest().clone());
+				self.votes.completability_blockers(&last_round_state)
+			},
+			None => {
+				// NOTE: when we catch up to a round we complete the round
+				// without any last round state. in this case we already started
+				// a new round after we caught up so the previous round estimate
+				// doesn't need to be finalized.
+				let base = RoundState::genesis(self.votes.base());
+				self.votes.completability_blockers(&base)
+			},
+		}
+	}
+
+	// the first round of a voter set has no previous round, which can be given
+	// as a state without an estimate when the round isn't bridged to a prior
+	// one. it is treated like a completed round which finalized the base of this
+	// round, see `RoundState::genesis`, so that the round prevotes and precommits
+	// on the best chain containing its base. any other round only starts once
+	// its previous round is completable.
+	fn or_genesis(&self, last_round_state: RoundState<H, N>) -> RoundState<H, N> {
+		let is_first_round = self.round_number() == 1 &&
+			self.last_round_state.as_ref().is_some_and(|view| !view.is_bridged());
+
+		if is_first_round && last_round_state.estimate.is_none() {
+			RoundState::genesis(self.votes.base())
+		} else {
+			last_round_state
+		}
+	}
+
 	/// Get access to the underlying environment.
 	pub(super) fn env(&self) -> &E {
-		&*self.env
+		&self.env
 	}
 
 	/// Get the round number.
@@ -264,6 +464,11 @@ where
 		self.votes.base()
 	}
 
+	/// Get the block known to be finalized the round was pruned to, if any.
+	pub(super) fn finalized_floor(&self) -> Option<&(H, N)> {
+		self.votes.finalized_floor()
+	}
+
 	/// Get the voters in this round.
 	pub(super) fn voters(&self) -> &VoterSet<E::Id> {
 		self.votes.voters()
@@ -274,6 +479,16 @@ where
 		self.votes.finalized()
 	}
 
+	/// Get the round-estimate.
+	pub(super) fn estimate(&self) -> Option<&(H, N)> {
+		self.votes.estimate()
+	}
+
+	/// Whether the round is completable.
+	pub(super) fn completable(&self) -> bool {
+		self.votes.completable()
+	}
+
 	/// Get the current total weight of prevotes.
 	pub(super) fn prevote_weight(&self) -> VoteWeight {
 		self.votes.prevote_participation().0
@@ -284,6 +499,12 @@ where
 		self.votes.precommit_participation().0
 	}
 
+	/// Get the number of times precommitting was held back because the
+	/// prevote-GHOST didn't include the last round estimate yet.
+	pub(super) fn precommit_gated(&self) -> usize {
+		self.precommit_gated
+	}
+
 	/// Get the Ids of the prevoters.
 	pub(super) fn prevote_ids(&self) -> impl Iterator<Item = E::Id> {
 		self.votes.prevotes().into_iter().map(|pv| pv.0)
@@ -305,8 +526,15 @@ where
 		}
 
 		for SignedPrecommit { precommit, signature, id } in commit.precommits.iter().cloned() {
+			let target = (precommit.target_hash.clone(), precommit.target_number);
 			let import_result =
-				self.votes.import_precommit(&*self.env, precommit, id, signature)?;
+				match self.votes.import_precommit(&*self.env, precommit, id.clone(), signature) {
+					Ok(import_result) => import_result,
+					Err(e) => {
+						self.note_invalid_target(&id, target, e);
+						return Ok(None)
+					},
+				};
 			if let ImportResult { equivocation: Some(e), .. } = import_result {
 				self.env.precommit_equivocation(self.round_number(), e);
 			}
@@ -315,6 +543,27 @@ where
 		Ok(Some((commit.target_hash.clone(), commit.target_number)))
 	}
 
+	/// Note that the round has been completed, and save it to the persistent storage.
+	pub(super) fn note_completed(&self) -> Result<(), E::Error> {
+		self.env.completed(
+			self.round_number(),
+			self.round_state(),
+			self.dag_base(),
+			self.historical_votes(),
+		)?;
+
+		if let Some(persistence) = self.env.persistence() {
+			persistence.save_completed(
+				self.round_number(),
+				self.round_state(),
+				self.dag_base(),
+				self.historical_votes(),
+			)?;
+		}
+
+		Ok(())
+	}
+
 	/// Get a clone of the finalized sender.
 	pub(super) fn finalized_sender(&self) -> UnboundedSender<FinalizedNotification<H, N, E>> {
 		self.finalized_sender.clone()
@@ -333,11 +582,29 @@ where
 		latter_view
 	}
 
+	/// Send the votes which have been cast but not sent yet, without processing
+	/// any incoming messages or casting new votes.
+	pub(super) fn poll_flush(&mut self, cx: &mut Context) -> Poll<Result<(), E::Error>> {
+		self.poll_outgoing(cx)
+	}
+
 	/// Get a commit justifying the best finalized block.
 	pub(super) fn finalizing_commit(&self) -> Option<&Commit<H, N, E::Signature, E::Id>> {
 		self.best_finalized.as_ref()
 	}
 
+	/// Get a catch-up proving the round completable, if it is.
+	pub(super) fn catch_up(&self) -> Option<CatchUp<H, N, E::Signature, E::Id>> {
+		self.votes.catch_up()
+	}
+
+	/// Note that the round was started right after catching up to the previous
+	/// round. We then prevote without waiting for the prevote timer if the
+	/// previous round's estimate is already finalized.
+	pub(super) fn note_caught_up(&mut self) {
+		self.caught_up = true;
+	}
+
 	/// Return all votes for the round (prevotes and precommits), sorted by
 	/// imported order and indicating the indices where we voted. At most two
 	/// prevotes and two precommits per voter are present, further equivocations
@@ -351,42 +618,176 @@ where
 		&mut self,
 		vote: SignedMessage<H, N, E::Signature, E::Id>,
 	) -> Result<(), E::Error> {
+		if !self.env.is_block_known(vote.target_hash()) {
+			self.stash_vote(vote);
+			return Ok(())
+		}
+
 		let SignedMessage { message, signature, id } = vote;
 		if !self
 			.env
-			.is_equal_or_descendent_of(self.votes.base().0, message.target().0.clone())
+			.is_equal_or_descendent_of(self.votes.base().0, message.target_hash().clone())
 		{
 			trace!(target: "afg", "Ignoring message targeting {:?} lower than round base {:?}",
 				message.target(),
 				self.votes.base(),
 			);
+			self.env.vote_dropped(self.round_number(), VoteDropReason::BelowBase);
 			return Ok(())
 		}
 
+		// a vote which can't be imported, e.g. because its target number is wrong,
+		// is dropped instead of failing the round.
+		let target = (message.target_hash().clone(), message.target_number());
+
 		match message {
 			Message::Prevote(prevote) => {
 				let import_result =
-					self.votes.import_prevote(&*self.env, prevote, id, signature)?;
+					match self.votes.import_prevote(&*self.env, prevote, id.clone(), signature) {
+						Ok(import_result) => import_result,
+						Err(e) => {
+							self.note_invalid_target(&id, target, e);
+							return Ok(())
+						},
+					};
+				self.note_dropped(import_result.valid_voter, import_result.duplicated);
 				if let ImportResult { equivocation: Some(e), .. } = import_result {
 					self.env.prevote_equivocation(self.votes.number(), e);
 				}
 			},
 			Message::Precommit(precommit) => {
 				let import_result =
-					self.votes.import_precommit(&*self.env, precommit, id, signature)?;
+					match self.votes.import_precommit(&*self.env, precommit, id.clone(), signature)
+					{
+						Ok(import_result) => import_result,
+						Err(e) => {
+							self.note_invalid_target(&id, target, e);
+							return Ok(())
+						},
+					};
+				self.note_dropped(import_result.valid_voter, import_result.duplicated);
 				if let ImportResult { equivocation: Some(e), .. } = import_result {
 					self.env.precommit_equivocation(self.votes.number(), e);
 				}
 			},
 			Message::PrimaryPropose(primary) => {
-				let primary_id = self.votes.primary_voter().0.clone();
 				// note that id here refers to the party which has cast the vote
 				// and not the id of the party which has received the vote message.
-				if id == primary_id {
+				if id == self.primary_voter {
 					self.primary_block = Some((primary.target_hash, primary.target_number));
+				} else {
+					warn!(target: "afg",
+						"Ignoring primary proposal for round {} from {:?}, the primary is {:?}",
+						self.votes.number(),
+						id,
+						self.primary_voter,
+					);
+					self.env.note_misbehaving_voter(self.votes.number(), id);
+				}
+			},
+		}
+
+		if let Some(depth) = self.votes.take_sparse_votes() {
+			self.env.sparse_votes(self.votes.number(), depth);
+		}
+
+		Ok(())
+	}
+
+	// note a vote which couldn't be imported into the round.
+	fn note_invalid_target(&self, id: &E::Id, target: (H, N), error: crate::Error) {
+		debug!(target: "afg", "Ignoring message from {:?} with invalid target {:?}: {}",
+			id,
+			target,
+			error,
+		);
+		self.env.vote_dropped(self.round_number(), VoteDropReason::InvalidTarget);
+	}
+
+	// note a vote which wasn't imported, given the result of importing it.
+	fn note_dropped(&self, valid_voter: bool, duplicated: bool) {
+		if !valid_voter {
+			self.env.vote_dropped(self.round_number(), VoteDropReason::UnknownVoter);
+		} else if duplicated {
+			self.env.vote_dropped(self.round_number(), VoteDropReason::Duplicate);
+		}
+	}
+
+	// keep a vote for a block which hasn't been imported yet until it is, see
+	// `block_imported`. the vote is dropped if it can't be for a descendent of
+	// the round base or if the voter already has a prevote and a precommit
+	// pending, to bound the memory used by votes which may never be imported.
+	// past `Environment::max_pending_votes` the oldest pending vote is dropped.
+	fn stash_vote(&mut self, vote: SignedMessage<H, N, E::Signature, E::Id>) {
+		let plausible = vote.target_number() > self.votes.base().1 &&
+			self.voters().contains(&vote.id) &&
+			self.pending_votes.iter().all(|pending| {
+				pending.id != vote.id ||
+					mem::discriminant(&pending.message) != mem::discriminant(&vote.message)
+			});
+
+		if !plausible {
+			trace!(target: "afg", "Ignoring message from {:?} targeting unknown block {:?}",
+				vote.id,
+				vote.target(),
+			);
+			self.env.vote_dropped(self.round_number(), VoteDropReason::UnknownBlock);
+			return
+		}
+
+		if let Some(max_pending_votes) = self.env.max_pending_votes() {
+			if max_pending_votes == 0 {
+				self.env.vote_dropped(self.round_number(), VoteDropReason::UnknownBlock);
+				return
+			}
+
+			while self.pending_votes.len() >= max_pending_votes {
+				if let Some(evicted) = self.pending_votes.pop_front() {
+					trace!(target: "afg", "Round {}: dropping oldest message from {:?} targeting unknown block {:?}",
+						self.round_number(),
+						evicted.id,
+						evicted.target(),
+					);
+					self.env.vote_dropped(self.round_number(), VoteDropReason::UnknownBlock);
 				}
+			}
+		}
+
+		trace!(target: "afg", "Round {}: keeping message from {:?} targeting unknown block {:?}",
+			self.round_number(),
+			vote.id,
+			vote.target(),
+		);
+		self.pending_votes.push_back(vote);
+	}
+
+	/// Prune the blocks below the given finalized block from the vote-graph,
+	/// see `Round::prune`. Returns whether the graph was pruned.
+	pub(super) fn prune(&mut self, hash: H, number: N) -> bool {
+		if self.dag_base().1 >= number || self.finalized_floor().is_some_and(|f| f.1 >= number) {
+			return false
+		}
+
+		match self.votes.prune(&*self.env, hash, number) {
+			Ok(pruned) => pruned,
+			Err(e) => {
+				debug!(target: "afg", "Not pruning round {}: {:?}", self.round_number(), e);
+				false
 			},
 		}
+	}
+
+	/// Handle the votes for the given block which were kept until it was
+	/// imported.
+	pub(super) fn block_imported(&mut self, hash: &H) -> Result<(), E::Error> {
+		let (votes, pending) = mem::take(&mut self.pending_votes)
+			.into_iter()
+			.partition(|vote| vote.target_hash() == hash);
+		self.pending_votes = pending;
+
+		for vote in votes {
+			self.handle_vote(vote)?;
+		}
 
 		Ok(())
 	}
@@ -411,24 +812,178 @@ where
 		while let Poll::Ready(Some(incoming)) = Stream::poll_next(Pin::new(&mut self.incoming), cx)
 		{
 			trace!(target: "afg", "Round {}: Got incoming message", self.round_number());
-			self.handle_vote(incoming?)?;
+			let incoming = incoming?;
+			self.note_heard(&incoming);
+			self.handle_vote(incoming)?;
+		}
+
+		Ok(())
+	}
+
+	// note that a voter has been heard from, or is done voting in the round.
+	fn note_heard(&mut self, vote: &SignedMessage<H, N, E::Signature, E::Id>) {
+		let position = match self.votes.voters().get(&vote.id) {
+			Some(info) => info.position(),
+			None => return,
+		};
+
+		if let Some(timer @ Some(_)) = self.silence.get_mut(position) {
+			*timer = match vote.message {
+				Message::Precommit(_) => None,
+				_ => self.env.voter_silence_timer(self.votes.number(), &vote.id),
+			};
+		}
+	}
+
+	// call `voter_silent` for the watched voters whose silence timer fired.
+	fn check_silence(&mut self, cx: &mut Context) -> Result<(), E::Error> {
+		for (id, info) in self.votes.voters().iter() {
+			let timer = match self.silence.get_mut(info.position()) {
+				Some(timer) => timer,
+				None => continue,
+			};
+
+			if let Some(Poll::Ready(result)) = timer.as_mut().map(|timer| timer.poll_unpin(cx)) {
+				result?;
+				debug!(target: "afg", "Voter {:?} has been silent in round {}", id, self.votes.number());
+
+				*timer = None;
+				self.env.voter_silent(self.votes.number(), id.clone());
+			}
+		}
+
+		Ok(())
+	}
+
+	// restore the votes we cast in this round before a restart, if any. they are
+	// sent again instead of casting new votes, which could conflict with them.
+	fn restore_votes(&mut self) -> Result<(), E::Error> {
+		self.restored = true;
+
+		let votes = match self.env.persistence() {
+			Some(persistence) => persistence.load_round(self.round_number())?,
+			None => return Ok(()),
+		};
+
+		// re-sending conflicting votes would make us equivocate, something
+		// has gone badly wrong with the storage.
+		let conflicting = votes.iter().enumerate().any(|(i, a)| {
+			votes[..i]
+				.iter()
+				.any(|b| mem::discriminant(a) == mem::discriminant(b) && a != b)
+		});
+
+		if conflicting {
+			warn!(target: "afg", "Refusing to restore conflicting votes in round {}: {:?}",
+				self.votes.number(),
+				votes.iter().map(|vote| vote.target()).collect::<Vec<_>>(),
+			);
+
+			return Err(crate::Error::InconsistentPersistence.into())
+		}
+
+		for vote in votes {
+			self.state = match (self.state.take(), &vote) {
+				(
+					Some(State::Start(prevote_timer, precommit_timer)),
+					Message::PrimaryPropose(_),
+				) => Some(State::Proposed(prevote_timer, precommit_timer)),
+				(
+					Some(State::Start(_, precommit_timer)) |
+					Some(State::Proposed(_, precommit_timer)),
+					Message::Prevote(_),
+				) => {
+					self.votes.set_prevoted_index();
+					Some(State::Prevoted(precommit_timer))
+				},
+				(Some(State::Prevoted(_)), Message::Precommit(_)) => {
+					self.votes.set_precommitted_index();
+					Some(State::Precommitted)
+				},
+				(state, _) => {
+					warn!(target: "afg", "Ignoring restored vote {:?} in round {}, step = {:?}",
+						vote.target(),
+						self.votes.number(),
+						state,
+					);
+
+					self.state = state;
+					continue
+				},
+			};
+
+			debug!(target: "afg", "Restored vote for {:?} in round {}", vote.target(), self.votes.number());
+			self.outgoing.push(vote);
 		}
 
 		Ok(())
 	}
 
+	// whether we can cast a vote, i.e. we are voting in this round and can sign
+	// the vote. if signing became unavailable we stop voting for the rest of
+	// the round, see `Environment::can_sign`.
+	fn can_sign(&mut self) -> bool {
+		if !self.voting.is_active() {
+			return false
+		}
+
+		if !self.env.can_sign(self.round_number()) {
+			self.stop_voting_without_signing();
+			return false
+		}
+
+		true
+	}
+
+	fn stop_voting_without_signing(&mut self) {
+		warn!(target: "afg", "Signing unavailable, not voting for the rest of round {}",
+			self.round_number(),
+		);
+
+		self.voting = Voting::No;
+		self.env.signing_unavailable(self.round_number());
+	}
+
+	// send our votes, skipping those the outgoing sink fails to sign, see
+	// `Environment::is_signing_failure`.
+	fn poll_outgoing(&mut self, cx: &mut Context) -> Poll<Result<(), E::Error>> {
+		loop {
+			match self.outgoing.poll(cx) {
+				Poll::Ready(Err(e)) if self.env.is_signing_failure(&e) => {
+					if self.voting.is_active() {
+						self.stop_voting_without_signing();
+					}
+
+					// the failed vote was dropped, try sending the remaining ones.
+					if self.outgoing.is_empty() {
+						return Poll::Ready(Ok(()))
+					}
+				},
+				polled => return polled,
+			}
+		}
+	}
+
+	// save a vote we are about to cast, so that it isn't forgotten on restart.
+	fn save_vote(&self, vote: &Message<H, N>) -> Result<(), E::Error> {
+		match self.env.persistence() {
+			Some(persistence) => persistence.save_vote(self.round_number(), vote.clone()),
+			None => Ok(()),
+		}
+	}
+
 	fn primary_propose(&mut self, last_round_state: &RoundState<H, N>) -> Result<(), E::Error> {
 		match self.state.take() {
 			Some(State::Start(prevote_timer, precommit_timer)) => {
 				let maybe_estimate = last_round_state.estimate.clone();
 
-				match (maybe_estimate, self.voting.is_primary()) {
+				match (maybe_estimate, self.voting.is_primary() && self.can_sign()) {
 					(Some(last_round_estimate), true) => {
 						let maybe_finalized = last_round_state.finalized.clone();
 
 						// Last round estimate has not been finalized.
 						let should_send_primary =
-							maybe_finalized.map_or(true, |f| last_round_estimate.1 > f.1);
+							maybe_finalized.is_none_or(|f| last_round_estimate.1 > f.1);
 						if should_send_primary {
 							debug!(target: "afg", "Sending primary block hint for round {}", self.votes.number());
 							let primary = PrimaryPropose {
@@ -436,7 +991,9 @@ where
 								target_number: last_round_estimate.1,
 							};
 							self.env.proposed(self.round_number(), primary.clone())?;
-							self.outgoing.push(Message::PrimaryPropose(primary));
+							let message = Message::PrimaryPropose(primary);
+							self.save_vote(&message)?;
+							self.outgoing.push(message);
 							self.state = Some(State::Proposed(prevote_timer, precommit_timer));
 
 							return Ok(())
@@ -468,31 +1025,68 @@ where
 		last_round_state: &RoundState<H, N>,
 	) -> Result<(), E::Error> {
 		let state = self.state.take();
+		let best_chain_retries = self.best_chain_retries;
 
 		let start_prevoting = |this: &mut Self,
 		                       mut prevote_timer: E::Timer,
 		                       precommit_timer: E::Timer,
 		                       proposed: bool,
 		                       cx: &mut Context| {
-			let should_prevote = match prevote_timer.poll_unpin(cx) {
-				Poll::Ready(Err(e)) => return Err(e),
-				Poll::Ready(Ok(())) => true,
-				Poll::Pending => this.votes.completable(),
+			// nothing the prevote timer waits for can change our prevote once
+			// the last round's estimate is finalized, see
+			// `last_round_estimate_finalized`.
+			let should_prevote = loop {
+				if this.last_round_estimate_finalized(last_round_state) {
+					break true
+				}
+
+				match prevote_timer.poll_unpin(cx) {
+					Poll::Ready(Err(e)) => return Err(e),
+					Poll::Ready(Ok(())) if this.votes.completable() => break true,
+					Poll::Ready(Ok(())) => {
+						let rearmed = this.prevote_timer_rearmed;
+						match this.env.rearm_prevote_timer(this.round_number(), rearmed) {
+							Some(timer) => {
+								trace!(target: "afg", "Re-arming prevote timer of round {}", this.round_number());
+								this.prevote_timer_rearmed += 1;
+								prevote_timer = timer;
+							},
+							None => break true,
+						}
+					},
+					Poll::Pending => break this.votes.completable(),
+				}
 			};
 
 			if should_prevote {
-				if this.voting.is_active() {
+				if this.can_sign() {
 					debug!(target: "afg", "Constructing prevote for round {}", this.votes.number());
 
-					let (base, best_chain) = this.construct_prevote(last_round_state);
+					let base = this.construct_prevote(last_round_state)?;
+					let generation = this.env.best_block_generation();
 
-					// since we haven't polled the future above yet we need to
-					// manually schedule the current task to be awoken so the
-					// `best_chain` future is then polled below after we switch the
-					// state to `Prevoting`.
-					cx.waker().wake_by_ref();
-
-					this.state = Some(State::Prevoting(precommit_timer, (base, best_chain)));
+					match generation.and_then(|g| this.best_chain_cache.get(&base.0, g)) {
+						Some(best_chain) => {
+							trace!(target: "afg", "Using cached best chain containing {:?}", base);
+							this.cast_prevote(precommit_timer, base, best_chain)?;
+						},
+						None => {
+							let best_chain = this.env.best_chain_containing(base.0.clone());
+							let deadline =
+								this.env.best_chain_deadline(this.round_number(), base.clone());
+
+							// since we haven't polled the future above yet we need to
+							// manually schedule the current task to be awoken so the
+							// `best_chain` future is then polled below after we switch the
+							// state to `Prevoting`.
+							cx.waker().wake_by_ref();
+
+							this.state = Some(State::Prevoting(
+								precommit_timer,
+								(base, generation, best_chain, deadline),
+							));
+						},
+					}
 				} else {
 					this.state = Some(State::Prevoted(precommit_timer));
 				}
@@ -506,41 +1100,49 @@ where
 		};
 
 		let finish_prevoting = |this: &mut Self,
-		                        precommit_timer: E::Timer,
-		                        base: H,
+		                        mut precommit_timer: E::Timer,
+		                        base: (H, N),
+		                        generation: Option<u64>,
 		                        mut best_chain: E::BestChain,
+		                        mut deadline: Option<E::Timer>,
 		                        cx: &mut Context| {
 			let best_chain = match best_chain.poll_unpin(cx) {
 				Poll::Ready(Err(e)) => return Err(e),
 				Poll::Ready(Ok(best_chain)) => best_chain,
 				Poll::Pending => {
-					this.state = Some(State::Prevoting(precommit_timer, (base, best_chain)));
-					return Ok(())
-				},
-			};
+					// the chain backend may hang, so once the prevote window is
+					// over, or at the earlier deadline of the environment, we
+					// treat the block as unknown rather than stalling the round.
+					let timed_out = match deadline.as_mut().map(|d| d.poll_unpin(cx)) {
+						Some(Poll::Ready(Err(e))) => return Err(e),
+						Some(Poll::Ready(Ok(()))) => true,
+						Some(Poll::Pending) | None =>
+							this.poll_precommit_timer(&mut precommit_timer, cx)?,
+					};
 
-			if let Some(target) = best_chain {
-				let prevote = Prevote { target_hash: target.0, target_number: target.1 };
+					if !timed_out {
+						this.state = Some(State::Prevoting(
+							precommit_timer,
+							(base, generation, best_chain, deadline),
+						));
+						return Ok(())
+					}
 
-				debug!(target: "afg", "Casting prevote for round {}", this.votes.number());
-				this.env.prevoted(this.round_number(), prevote.clone())?;
-				this.votes.set_prevoted_index();
-				this.outgoing.push(Message::Prevote(prevote));
-				this.state = Some(State::Prevoted(precommit_timer));
-			} else {
-				// if this block is considered unknown, something has gone wrong.
-				// log and handle, but skip casting a vote.
-				warn!(target: "afg",
-					"Could not cast prevote: previously known block {:?} has disappeared",
-					base,
-				);
+					warn!(target: "afg",
+						"Timed out waiting for best chain containing {:?} in round {}",
+						base,
+						this.round_number(),
+					);
+
+					return this.cast_prevote(precommit_timer, base, None)
+				},
+			};
 
-				// when we can't construct a prevote, we shouldn't precommit.
-				this.state = None;
-				this.voting = Voting::No;
+			if let Some(generation) = generation {
+				this.best_chain_cache.insert(base.0.clone(), generation, best_chain.clone());
 			}
 
-			Ok(())
+			this.cast_prevote(precommit_timer, base, best_chain)
 		};
 
 		match state {
@@ -550,14 +1152,103 @@ where
 			Some(State::Proposed(prevote_timer, precommit_timer)) => {
 				start_prevoting(self, prevote_timer, precommit_timer, true, cx)?;
 			},
-			Some(State::Prevoting(precommit_timer, (base, best_chain))) => {
-				finish_prevoting(self, precommit_timer, base, best_chain, cx)?;
+			Some(State::Prevoting(precommit_timer, (base, generation, best_chain, deadline))) => {
+				finish_prevoting(
+					self,
+					precommit_timer,
+					base,
+					generation,
+					best_chain,
+					deadline,
+					cx,
+				)?;
 			},
 			x => {
 				self.state = x;
 			},
 		}
 
+		// the timer to retry the best chain query with hasn't been polled yet,
+		// so we schedule the current task to be awoken to poll it.
+		if self.best_chain_retries != best_chain_retries {
+			cx.waker().wake_by_ref();
+		}
+
+		if let Some(State::Start(..)) | Some(State::Proposed(..)) = self.state {
+			self.check_stuck()?;
+		}
+
+		Ok(())
+	}
+
+	// whether we caught up to the last round, its estimate is finalized and the
+	// base of this round already includes it. the estimate can then no longer
+	// move back and no primary block is proposed on top of it, so there is
+	// nothing left to wait for before prevoting.
+	fn last_round_estimate_finalized(&self, last_round_state: &RoundState<H, N>) -> bool {
+		if !self.caught_up {
+			return false
+		}
+
+		match (&last_round_state.estimate, &last_round_state.finalized) {
+			(Some(estimate), Some(finalized)) =>
+				estimate.1 <= finalized.1 && estimate.1 <= self.votes.base().1,
+			_ => false,
+		}
+	}
+
+	// poll the precommit timer of the round, unless it already fired. it may
+	// fire while waiting for the best chain to prevote on, and isn't polled
+	// again after that.
+	fn poll_precommit_timer(
+		&mut self,
+		precommit_timer: &mut E::Timer,
+		cx: &mut Context,
+	) -> Result<bool, E::Error> {
+		if !self.precommit_timer_fired {
+			if let Poll::Ready(result) = precommit_timer.poll_unpin(cx) {
+				result?;
+				self.precommit_timer_fired = true;
+			}
+		}
+
+		Ok(self.precommit_timer_fired)
+	}
+
+	// note that we didn't manage to prevote when polled, and call the watchdog
+	// hook if that happened too many times.
+	fn check_stuck(&mut self) -> Result<(), E::Error> {
+		let threshold = match self.env.round_stuck_threshold() {
+			Some(threshold) => threshold,
+			None => return Ok(()),
+		};
+
+		self.unproductive_polls += 1;
+		if self.unproductive_polls != threshold {
+			return Ok(())
+		}
+
+		warn!(target: "afg", "Round {} polled {} times without prevoting, state = {:?}",
+			self.votes.number(),
+			threshold,
+			self.votes.state(),
+		);
+
+		if !self.env.round_stuck(self.round_number(), self.votes.state()) {
+			return Ok(())
+		}
+
+		if let Some(State::Start(_, precommit_timer)) | Some(State::Proposed(_, precommit_timer)) =
+			self.state.take()
+		{
+			if self.can_sign() {
+				let base = self.votes.base();
+				self.cast_prevote(precommit_timer, base.clone(), Some(base))?;
+			} else {
+				self.state = Some(State::Prevoted(precommit_timer));
+			}
+		}
+
 		Ok(())
 	}
 
@@ -568,32 +1259,60 @@ where
 	) -> Result<(), E::Error> {
 		match self.state.take() {
 			Some(State::Prevoted(mut precommit_timer)) => {
-				let last_round_estimate = last_round_state
-					.estimate
-					.clone()
-					.expect("Rounds only started when prior round completable; qed");
-
-				let should_precommit = {
-					// we wait for the last round's estimate to be equal to or
-					// the ancestor of the current round's p-Ghost before precommitting.
-					self.votes.state().prevote_ghost.as_ref().map_or(false, |p_g| {
+				let last_round_estimate = last_round_state.estimate.clone().expect(
+					"rounds only started when prior round completable or with genesis state; qed",
+				);
+
+				// we wait for the last round's estimate to be equal to or
+				// the ancestor of the current round's p-Ghost before precommitting.
+				// without a p-Ghost, e.g. when hardly anyone prevoted, we precommit
+				// the round base if that is the last round's estimate, since it is
+				// included in any p-Ghost the round could still get.
+				let prevote_ghost = self.votes.prevote_ghost().cloned();
+				let ghost_includes_estimate = match prevote_ghost {
+					Some(ref p_g) =>
 						p_g == &last_round_estimate ||
 							self.env
-								.is_equal_or_descendent_of(last_round_estimate.0, p_g.0.clone())
-					})
-				} && match precommit_timer.poll_unpin(cx) {
-					Poll::Ready(Err(e)) => return Err(e),
-					Poll::Ready(Ok(())) => true,
-					Poll::Pending => self.votes.completable(),
+								.is_equal_or_descendent_of(last_round_estimate.0, p_g.0.clone()),
+					None => last_round_estimate == self.votes.base(),
 				};
 
+				if prevote_ghost.is_some() && !ghost_includes_estimate {
+					self.precommit_gated += 1;
+					self.precommit_gated_since.get_or_insert_with(Instant::now);
+				}
+
+				let should_precommit = ghost_includes_estimate &&
+					(self.poll_precommit_timer(&mut precommit_timer, cx)? ||
+						self.votes.completable());
+
 				if should_precommit {
-					if self.voting.is_active() {
-						debug!(target: "afg", "Casting precommit for round {}", self.votes.number());
+					if let Some(since) = self.precommit_gated_since.take() {
+						self.env.precommit_waited(self.round_number(), since.elapsed());
+					}
+
+					// the prevote-GHOST includes the last round estimate on the
+					// chain, but a corrupted vote graph could still have made it up.
+					// we don't precommit in the round then, which is always safe.
+					let base = self.votes.base().0;
+					let unknown_ghost = prevote_ghost.filter(|(g_hash, _)| {
+						self.env.invariant_level().is_enabled() &&
+							!self.env.is_equal_or_descendent_of(base, g_hash.clone())
+					});
+
+					if let Some(ghost) = unknown_ghost {
+						warn!(target: "afg", "Not precommitting for prevote-GHOST {:?} not descending from the base in round {}",
+							ghost,
+							self.round_number(),
+						);
+					} else if self.can_sign() {
+						debug!(target: "afg", "Casting precommit for round {}", self.votes.number());
 						let precommit = self.construct_precommit();
 						self.env.precommitted(self.round_number(), precommit.clone())?;
+						let message = Message::Precommit(precommit);
+						self.save_vote(&message)?;
 						self.votes.set_precommitted_index();
-						self.outgoing.push(Message::Precommit(precommit));
+						self.outgoing.push(message);
 					}
 					self.state = Some(State::Precommitted);
 				} else {
@@ -609,32 +1328,32 @@ where
 	}
 
 	// construct a prevote message based on local state.
-	fn construct_prevote(&self, last_round_state: &RoundState<H, N>) -> (H, E::BestChain) {
+	// the returned block is the one whose best chain we should prevote for.
+	fn construct_prevote(&self, last_round_state: &RoundState<H, N>) -> Result<(H, N), E::Error> {
 		let last_round_estimate = last_round_state
 			.estimate
 			.clone()
-			.expect("Rounds only started when prior round completable; qed");
+			.expect("rounds only started when prior round completable or with genesis state; qed");
 
 		let find_descendent_of = match self.primary_block {
 			None => {
 				// vote for best chain containing prior round-estimate.
-				last_round_estimate.0
+				last_round_estimate
 			},
 			Some(ref primary_block) => {
 				// we will vote for the best chain containing `p_hash` iff
 				// the last round's prevote-GHOST included that block and
 				// that block is a strict descendent of the last round-estimate that we are
 				// aware of.
-				let last_prevote_g = last_round_state
-					.prevote_ghost
-					.clone()
-					.expect("Rounds only started when prior round completable; qed");
+				let last_prevote_g = last_round_state.prevote_ghost.clone().expect(
+					"rounds only started when prior round completable or with genesis state; qed",
+				);
 
 				// if the blocks are equal, we don't check ancestry.
 				if primary_block == &last_prevote_g {
-					primary_block.0.clone()
+					primary_block.clone()
 				} else if primary_block.1 >= last_prevote_g.1 {
-					last_round_estimate.0
+					last_round_estimate
 				} else {
 					// from this point onwards, the number of the primary-broadcasted
 					// block is less than the last prevote-GHOST's number.
@@ -649,13 +1368,13 @@ where
 							let offset: usize = if last_prevote_g.1 < to_sub {
 								0
 							} else {
-								(last_prevote_g.1 - to_sub).as_()
+								(last_prevote_g.1 - to_sub).try_into().unwrap_or(usize::MAX)
 							};
 
-							if ancestry.get(offset).map_or(false, |b| b == p_hash) {
-								p_hash.clone()
+							if ancestry.get(offset) == Some(p_hash) {
+								(p_hash.clone(), p_num)
 							} else {
-								last_round_estimate.0
+								last_round_estimate
 							}
 						},
 						Err(crate::Error::NotDescendent) => {
@@ -667,20 +1386,123 @@ where
 								last_round_estimate,
 							);
 
-							last_round_estimate.0
+							if self.env.halt_on_massive_equivocation() {
+								return Err(crate::Error::MassiveEquivocation.into())
+							}
+
+							last_round_estimate
+						},
+						Err(e) => {
+							// the ancestry could not be resolved, which is no evidence
+							// of equivocation. treat the primary block as unknown.
+							debug!(target: "afg",
+								"Unable to check primary block {:?} against last round prevote GHOST {:?}: {:?}",
+								primary_block,
+								last_prevote_g,
+								e,
+							);
+
+							last_round_estimate
 						},
 					}
 				}
 			},
 		};
 
-		(find_descendent_of.clone(), self.env.best_chain_containing(find_descendent_of))
+		Ok(find_descendent_of)
+	}
+
+	// cast a prevote for the best chain containing `base`.
+	fn cast_prevote(
+		&mut self,
+		precommit_timer: E::Timer,
+		base: (H, N),
+		best_chain: Option<(H, N)>,
+	) -> Result<(), E::Error> {
+		if let Some(best_target) = best_chain {
+			let target = self.restrict_vote(base, best_target);
+			let prevote = Prevote { target_hash: target.0, target_number: target.1 };
+
+			debug!(target: "afg", "Casting prevote for round {}", self.votes.number());
+			self.env.prevoted(self.round_number(), prevote.clone())?;
+			let message = Message::Prevote(prevote);
+			self.save_vote(&message)?;
+			self.votes.set_prevoted_index();
+			self.outgoing.push(message);
+			self.state = Some(State::Prevoted(precommit_timer));
+		} else if let Some(retry_timer) = self.env.best_chain_retry_timer(
+			self.round_number(),
+			base.clone(),
+			self.best_chain_retries,
+		) {
+			// the block may not have been synced yet, so we start prevoting
+			// again once the timer fires.
+			debug!(target: "afg",
+				"Retrying best chain containing unknown block {:?} in round {}",
+				base,
+				self.round_number(),
+			);
+
+			self.best_chain_retries += 1;
+			self.state = Some(State::Start(retry_timer, precommit_timer));
+		} else {
+			// if this block is considered unknown, something has gone wrong.
+			// log and handle, but skip casting a vote.
+			warn!(target: "afg",
+				"Could not cast prevote: previously known block {:?} has disappeared",
+				base,
+			);
+
+			// when we can't construct a prevote, we shouldn't precommit.
+			self.state = None;
+			self.voting = Voting::No;
+		}
+
+		Ok(())
+	}
+
+	// apply the voting rule of the environment to the head of the best chain
+	// containing `base`. a restricted target outside of that chain is ignored.
+	fn restrict_vote(&self, base: (H, N), best_target: (H, N)) -> (H, N) {
+		let voting_rule = match self.env.voting_rule() {
+			Some(voting_rule) => voting_rule,
+			None => return best_target,
+		};
+
+		let restricted =
+			voting_rule.restrict_vote(base.clone(), best_target.clone(), best_target.clone());
+		let restricted = match restricted {
+			Some(restricted) => restricted,
+			None => return best_target,
+		};
+
+		if self.env.is_equal_or_descendent_of(base.0.clone(), restricted.0.clone()) &&
+			self.env.is_equal_or_descendent_of(restricted.0.clone(), best_target.0.clone())
+		{
+			debug!(target: "afg", "Voting rule restricted prevote target in round {} from {:?} to {:?}",
+				self.votes.number(),
+				best_target,
+				restricted,
+			);
+
+			restricted
+		} else {
+			warn!(target: "afg",
+				"Ignoring voting rule target {:?} outside of the chain from {:?} to {:?} in round {}",
+				restricted,
+				base,
+				best_target,
+				self.votes.number(),
+			);
+
+			best_target
+		}
 	}
 
 	// construct a precommit message based on local state.
 	fn construct_precommit(&self) -> Precommit<H, N> {
-		let t = match self.votes.state().prevote_ghost {
-			Some(target) => target,
+		let t = match self.votes.prevote_ghost() {
+			Some(target) => target.clone(),
 			None => self.votes.base(),
 		};
 
@@ -713,13 +1535,9 @@ where
 
 			if precommitted || cant_vote {
 				if let Some((f_hash, f_number)) = new_state.finalized {
-					let commit = Commit {
-						target_hash: f_hash.clone(),
-						target_number: f_number,
-						precommits: self.votes.finalizing_precommits(&*self.env)
-							.expect("always returns none if something was finalized; this is checked above; qed")
-							.collect(),
-					};
+					let commit = self.votes.commit(&*self.env).expect(
+						"always returns none if something was finalized; this is checked above; qed",
+					);
 					let finalized = (f_hash, f_number, self.votes.number(), commit.clone());
 					let _ = self.finalized_sender.unbounded_send(finalized);
 					self.best_finalized = Some(commit);
@@ -728,3 +1546,1240 @@ where
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		bridge_state::LatterView,
+		testing::{
+			self,
+			chain::GENESIS_HASH,
+			environment::{make_environment, Environment, Id, Network, Signature},
+		},
+		voter::RoundData,
+		voter_set::{PrimarySelector, RoundRobin},
+		Chain, Error, InvariantLevel,
+	};
+	use futures::{
+		channel::{mpsc, oneshot},
+		executor::LocalPool,
+		task::SpawnExt,
+	};
+	use futures_timer::Delay;
+	use std::{
+		iter,
+		sync::atomic::{AtomicBool, Ordering},
+		time::Duration,
+	};
+
+	// round 1 of the given voters on top of genesis, after a round with the
+	// given state.
+	fn new_round<E>(
+		voters: VoterSet<Id>,
+		last_round_state: Option<RoundState<&'static str, u32>>,
+		env: Arc<E>,
+	) -> VotingRound<&'static str, u32, E>
+	where
+		E: crate::voter::Environment<&'static str, u32, Id = Id>,
+	{
+		let (finalized_sender, _) = mpsc::unbounded();
+		VotingRound::new(
+			1,
+			voters,
+			(GENESIS_HASH, 1),
+			last_round_state.map(|state| LatterView::from_stream(state, stream::pending())),
+			finalized_sender,
+			Arc::new(BestChainCache::new()),
+			env,
+		)
+	}
+
+	// poll the round, which mustn't complete, until the condition holds.
+	fn run_round_until<E>(
+		pool: &mut LocalPool,
+		round: &mut VotingRound<&'static str, u32, E>,
+		mut condition: impl FnMut(&VotingRound<&'static str, u32, E>) -> bool,
+	) where
+		E: crate::voter::Environment<&'static str, u32>,
+	{
+		pool.run_until(future::poll_fn(|cx| {
+			if let Poll::Ready(result) = round.poll(cx) {
+				panic!("round completed early: {:?}", result);
+			}
+
+			if condition(round) {
+				Poll::Ready(())
+			} else {
+				Poll::Pending
+			}
+		}))
+	}
+
+	// send the given votes in round 1 as the given voter.
+	fn send_votes(
+		pool: &LocalPool,
+		network: &Network,
+		id: Id,
+		votes: Vec<Message<&'static str, u32>>,
+	) {
+		let (_, round_sink) = network.make_round_comms(1, id);
+		pool.spawner()
+			.spawn(stream::iter(votes.into_iter().map(Ok)).forward(round_sink).map(|_| ()))
+			.unwrap();
+	}
+
+	// an environment stopping to vote on massive equivocation.
+	struct HaltingEnvironment(Environment);
+
+	testing::environment::wrap_environment!(HaltingEnvironment, 0, {
+		fn halt_on_massive_equivocation(&self) -> bool {
+			true
+		}
+	});
+
+	// an environment whose first `stalled` best chain queries never resolve,
+	// which are retried once after the optional deadline.
+	struct RetryingEnvironment {
+		env: Environment,
+		stalled: Mutex<usize>,
+		deadline: Option<Duration>,
+		retries: Mutex<Vec<(u64, usize)>>,
+	}
+
+	impl RetryingEnvironment {
+		fn new(env: Environment, stalled: usize, deadline: Option<Duration>) -> Self {
+			RetryingEnvironment {
+				env,
+				stalled: Mutex::new(stalled),
+				deadline,
+				retries: Mutex::new(Vec::new()),
+			}
+		}
+
+		fn retries(&self) -> Vec<(u64, usize)> {
+			self.retries.lock().clone()
+		}
+	}
+
+	impl Chain<&'static str, u32> for RetryingEnvironment {
+		testing::environment::wrap_environment!(@forward env: ancestry);
+	}
+
+	impl crate::voter::Environment<&'static str, u32> for RetryingEnvironment {
+		testing::environment::wrap_environment!(@forward env:
+			types,
+			voting_rule,
+			primary_selector,
+			round_data,
+			round_commit_timer,
+			completed,
+			concluded,
+			finalize_block,
+			proposed,
+			prevoted,
+			precommitted,
+			persistence,
+			invariant_level,
+			verify_persisted_vote,
+			is_block_known,
+			prevote_equivocation,
+			precommit_equivocation,
+		);
+
+		fn best_chain_containing(&self, base: &'static str) -> Self::BestChain {
+			let mut stalled = self.stalled.lock();
+			if *stalled > 0 {
+				*stalled -= 1;
+				return Box::new(future::pending())
+			}
+
+			self.env.best_chain_containing(base)
+		}
+
+		fn best_chain_retry_timer(
+			&self,
+			round: u64,
+			_base: (&'static str, u32),
+			retries: usize,
+		) -> Option<Self::Timer> {
+			if retries > 0 {
+				return None
+			}
+
+			self.retries.lock().push((round, retries));
+			Some(Box::new(Delay::new(Duration::from_millis(10)).map(Ok)))
+		}
+
+		fn best_chain_deadline(
+			&self,
+			_round: u64,
+			_base: (&'static str, u32),
+		) -> Option<Self::Timer> {
+			Some(Box::new(Delay::new(self.deadline?).map(Ok)))
+		}
+	}
+
+	// an environment whose prevote timers never fire.
+	struct StalledEnvironment(Environment, Mutex<Vec<u64>>);
+
+	impl Chain<&'static str, u32> for StalledEnvironment {
+		testing::environment::wrap_environment!(@forward 0: ancestry);
+	}
+
+	impl crate::voter::Environment<&'static str, u32> for StalledEnvironment {
+		testing::environment::wrap_environment!(@forward 0:
+			types,
+			best_chain_containing,
+			voting_rule,
+			primary_selector,
+			round_commit_timer,
+			completed,
+			concluded,
+			finalize_block,
+			proposed,
+			prevoted,
+			precommitted,
+			persistence,
+			invariant_level,
+			verify_persisted_vote,
+			is_block_known,
+			prevote_equivocation,
+			precommit_equivocation,
+		);
+
+		fn round_data(&self, round: u64) -> RoundData<Id, Self::Timer, Self::In, Self::Out> {
+			RoundData { prevote_timer: Box::new(future::pending()), ..self.0.round_data(round) }
+		}
+
+		// force a prevote for the round base after three polls without one.
+		fn round_stuck_threshold(&self) -> Option<usize> {
+			Some(3)
+		}
+
+		fn round_stuck(&self, round: u64, _state: RoundState<&'static str, u32>) -> bool {
+			self.1.lock().push(round);
+			true
+		}
+	}
+
+	// an environment whose signing key can be lost, which is either reported
+	// by `can_sign` or only noticed when signing votes in the outgoing sinks.
+	struct SigningEnvironment {
+		env: Environment,
+		signing_disabled: AtomicBool,
+		failing_signatures: Arc<AtomicBool>,
+		signing_unavailable: Mutex<Vec<u64>>,
+	}
+
+	impl SigningEnvironment {
+		fn new(env: Environment) -> Self {
+			SigningEnvironment {
+				env,
+				signing_disabled: AtomicBool::new(false),
+				failing_signatures: Arc::new(AtomicBool::new(false)),
+				signing_unavailable: Mutex::new(Vec::new()),
+			}
+		}
+	}
+
+	impl Chain<&'static str, u32> for SigningEnvironment {
+		testing::environment::wrap_environment!(@forward env: ancestry);
+	}
+
+	impl crate::voter::Environment<&'static str, u32> for SigningEnvironment {
+		testing::environment::wrap_environment!(@forward env:
+			types,
+			best_chain_containing,
+			voting_rule,
+			primary_selector,
+			round_commit_timer,
+			completed,
+			concluded,
+			finalize_block,
+			proposed,
+			prevoted,
+			precommitted,
+			persistence,
+			invariant_level,
+			verify_persisted_vote,
+			is_block_known,
+			prevote_equivocation,
+			precommit_equivocation,
+		);
+
+		fn round_data(&self, round: u64) -> RoundData<Id, Self::Timer, Self::In, Self::Out> {
+			let round_data = self.env.round_data(round);
+			let outgoing = SigningSink {
+				inner: round_data.outgoing,
+				failing: self.failing_signatures.clone(),
+			};
+			RoundData { outgoing: Box::pin(outgoing), ..round_data }
+		}
+
+		fn can_sign(&self, _round: u64) -> bool {
+			!self.signing_disabled.load(Ordering::SeqCst)
+		}
+
+		fn signing_unavailable(&self, round: u64) {
+			self.signing_unavailable.lock().push(round);
+		}
+
+		fn is_signing_failure(&self, error: &Error) -> bool {
+			*error == Error::SigningFailed
+		}
+	}
+
+	// a sink failing to sign the votes passed to it while `failing` is set.
+	struct SigningSink<S> {
+		inner: S,
+		failing: Arc<AtomicBool>,
+	}
+
+	impl<S: Sink<M, Error = Error> + Unpin, M> Sink<M> for SigningSink<S> {
+		type Error = Error;
+
+		fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Error>> {
+			Pin::new(&mut self.inner).poll_ready(cx)
+		}
+
+		fn start_send(mut self: Pin<&mut Self>, item: M) -> Result<(), Error> {
+			if self.failing.load(Ordering::SeqCst) {
+				return Err(Error::SigningFailed)
+			}
+
+			Pin::new(&mut self.inner).start_send(item)
+		}
+
+		fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Error>> {
+			Pin::new(&mut self.inner).poll_flush(cx)
+		}
+
+		fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Error>> {
+			Pin::new(&mut self.inner).poll_close(cx)
+		}
+	}
+
+	#[test]
+	fn drives_round_from_custom_state_stream() {
+		let local_id = Id(0);
+		let voters = VoterSet::new(iter::once((local_id, 1))).unwrap();
+		let (env, _, mut pool) = make_environment(local_id, &["A", "B"]);
+
+		// the prior round has an estimate but has not finalized anything yet.
+		let prior_state = RoundState {
+			round: 0,
+			prevote_ghost: Some((GENESIS_HASH, 1)),
+			finalized: None,
+			estimate: Some((GENESIS_HASH, 1)),
+			completable: true,
+		};
+
+		let (updates_tx, updates_rx) = mpsc::unbounded();
+		let (finalized_sender, _finalized) = mpsc::unbounded();
+		let mut round = VotingRound::new(
+			1,
+			voters,
+			(GENESIS_HASH, 1),
+			Some(LatterView::from_stream(prior_state.clone(), updates_rx)),
+			finalized_sender,
+			Arc::new(BestChainCache::new()),
+			Arc::new(env),
+		);
+
+		// we can vote and finalize in this round, but the round can't complete
+		// until the prior round estimate is finalized.
+		run_round_until(&mut pool, &mut round, |round| {
+			round.votes.completable() && matches!(round.state, Some(State::Precommitted))
+		});
+
+		assert_eq!(round.finalized(), Some(&("B", 3)));
+
+		updates_tx
+			.unbounded_send(RoundState { finalized: Some((GENESIS_HASH, 1)), ..prior_state })
+			.unwrap();
+
+		pool.run_until(future::poll_fn(|cx| round.poll(cx))).unwrap();
+	}
+
+	#[test]
+	fn first_round_votes_without_previous_estimate() {
+		let local_id = Id(0);
+		let voters = VoterSet::new(iter::once((local_id, 1))).unwrap();
+		let (env, _, mut pool) = make_environment(local_id, &["A", "B"]);
+
+		// there is no round before the first one.
+		let no_prior_state = RoundState {
+			round: 0,
+			prevote_ghost: None,
+			finalized: None,
+			estimate: None,
+			completable: false,
+		};
+
+		let mut round = new_round(voters, Some(no_prior_state), Arc::new(env));
+
+		assert!(round.completability_blockers().contains(&Blocker::NoPrevoteGhost));
+		pool.run_until(future::poll_fn(|cx| round.poll(cx))).unwrap();
+
+		assert_eq!(round.finalized(), Some(&("B", 3)));
+		assert!(round.completability_blockers().is_empty());
+	}
+
+	#[test]
+	fn counts_precommits_gated_on_last_round_estimate() {
+		let local_id = Id(0);
+		let voters = VoterSet::new(vec![(local_id, 1), (Id(1), 1)]).unwrap();
+		let (env, network, mut pool) = make_environment(local_id, &["A", "B"]);
+		env.with_chain(|chain| chain.push_blocks(GENESIS_HASH, &["A2"]));
+
+		let prior_state = RoundState {
+			round: 0,
+			prevote_ghost: Some(("B", 3)),
+			finalized: Some((GENESIS_HASH, 1)),
+			estimate: Some(("B", 3)),
+			completable: true,
+		};
+
+		let mut round = new_round(voters, Some(prior_state), Arc::new(env));
+
+		// the other voter prevotes on a different fork, so the prevote-GHOST
+		// is genesis and doesn't include the last round estimate.
+		send_votes(&pool, &network, Id(1), vec![Message::Prevote(Prevote::new("A2", 2))]);
+
+		run_round_until(&mut pool, &mut round, |round| round.precommit_gated() > 0);
+
+		assert_eq!(round.votes.state().prevote_ghost, Some((GENESIS_HASH, 1)));
+		assert!(matches!(round.state, Some(State::Prevoted(_))));
+	}
+
+	#[test]
+	fn halts_on_massive_equivocation_when_configured() {
+		let local_id = Id(0);
+		let voters = VoterSet::new(vec![(local_id, 1), (Id(1), 1)]).unwrap();
+		let (env, _, mut pool) = make_environment(local_id, &["A", "B"]);
+		env.with_chain(|chain| chain.push_blocks(GENESIS_HASH, &["A2"]));
+
+		// the prevote-GHOST of the prior round is not a descendent of its estimate.
+		let prior_state = RoundState {
+			round: 0,
+			prevote_ghost: Some(("B", 3)),
+			finalized: Some((GENESIS_HASH, 1)),
+			estimate: Some(("A2", 2)),
+			completable: true,
+		};
+
+		let mut round = new_round(voters, Some(prior_state), Arc::new(HaltingEnvironment(env)));
+
+		// the primary hints at a block in the ancestry of the prevote-GHOST.
+		round.primary_block = Some(("A", 2));
+
+		assert_eq!(
+			pool.run_until(future::poll_fn(|cx| round.poll(cx))),
+			Err(crate::Error::MassiveEquivocation),
+		);
+	}
+
+	#[test]
+	fn does_not_halt_when_primary_block_ancestry_is_unresolved() {
+		// an environment failing to resolve the ancestry of blocks from `A`.
+		struct UnresolvedEnvironment(Environment);
+
+		impl Chain<&'static str, u32> for UnresolvedEnvironment {
+			fn ancestry(
+				&self,
+				base: &'static str,
+				block: &'static str,
+			) -> Result<Vec<&'static str>, Error> {
+				if base == "A" {
+					return Err(Error::InvalidAncestry)
+				}
+
+				self.0.ancestry(base, block)
+			}
+		}
+
+		impl crate::voter::Environment<&'static str, u32> for UnresolvedEnvironment {
+			testing::environment::wrap_environment!(@environment 0);
+
+			fn halt_on_massive_equivocation(&self) -> bool {
+				true
+			}
+		}
+
+		let local_id = Id(0);
+		let voters = VoterSet::new(vec![(local_id, 1), (Id(1), 1)]).unwrap();
+		let (env, _, mut pool) = make_environment(local_id, &["A", "B", "C"]);
+
+		let prior_state = RoundState {
+			round: 0,
+			prevote_ghost: Some(("C", 4)),
+			finalized: Some((GENESIS_HASH, 1)),
+			estimate: Some(("A", 2)),
+			completable: true,
+		};
+
+		let mut round = new_round(voters, Some(prior_state), Arc::new(UnresolvedEnvironment(env)));
+
+		// the primary hints at a block whose ancestry can't be checked, which is
+		// no evidence of equivocation, so the round prevotes on the estimate.
+		round.primary_block = Some(("B", 3));
+
+		run_round_until(&mut pool, &mut round, |round| {
+			matches!(round.state, Some(State::Prevoted(_)))
+		});
+	}
+
+	#[test]
+	fn skips_precommit_for_unknown_ghost_when_strict() {
+		// an environment which can stop vouching for `B`.
+		struct ForgetfulEnvironment(Environment, AtomicBool);
+
+		impl Chain<&'static str, u32> for ForgetfulEnvironment {
+			fn ancestry(
+				&self,
+				base: &'static str,
+				block: &'static str,
+			) -> Result<Vec<&'static str>, Error> {
+				if block == "B" && self.1.load(Ordering::SeqCst) {
+					return Err(Error::NotDescendent)
+				}
+
+				self.0.ancestry(base, block)
+			}
+		}
+
+		impl crate::voter::Environment<&'static str, u32> for ForgetfulEnvironment {
+			testing::environment::wrap_environment!(@forward 0:
+				types,
+				best_chain_containing,
+				voting_rule,
+				primary_selector,
+				round_data,
+				round_commit_timer,
+				completed,
+				concluded,
+				finalize_block,
+				proposed,
+				prevoted,
+				precommitted,
+				persistence,
+				invariant_level,
+				verify_persisted_vote,
+				prevote_equivocation,
+				precommit_equivocation,
+			);
+
+			fn is_block_known(&self, block: &&'static str) -> bool {
+				*block != "B" || !self.1.load(Ordering::SeqCst)
+			}
+		}
+
+		let run = |level| {
+			let local_id = Id(0);
+			let voters = VoterSet::new(vec![(local_id, 1), (Id(1), 1)]).unwrap();
+			let (env, network, mut pool) = make_environment(local_id, &["A", "B"]);
+			env.set_invariant_level(level);
+			let env = Arc::new(ForgetfulEnvironment(env, AtomicBool::new(false)));
+
+			// the prevote-GHOST will be the last round's estimate, so only its
+			// ancestry from the round base can keep us from precommitting.
+			let last_round_state = RoundState {
+				round: 0,
+				prevote_ghost: Some(("B", 3)),
+				finalized: Some((GENESIS_HASH, 1)),
+				estimate: Some(("B", 3)),
+				completable: true,
+			};
+
+			let mut round = new_round(voters, Some(last_round_state), env.clone());
+
+			send_votes(&pool, &network, Id(1), vec![Message::Prevote(Prevote::new("B", 3))]);
+
+			// both prevotes are in well before the precommit timer fires.
+			run_round_until(&mut pool, &mut round, |round| {
+				round.votes.state().prevote_ghost == Some(("B", 3))
+			});
+
+			// the chain no longer vouches for the block everyone prevoted for.
+			env.1.store(true, Ordering::SeqCst);
+
+			run_round_until(&mut pool, &mut round, |round| {
+				matches!(round.state, Some(State::Precommitted))
+			});
+
+			env.0
+				.noted_votes()
+				.iter()
+				.filter(|(_, vote)| matches!(vote, Message::Precommit(_)))
+				.count()
+		};
+
+		assert_eq!(run(InvariantLevel::Always), 0);
+		assert_eq!(run(InvariantLevel::Off), 1);
+	}
+
+	#[test]
+	fn watchdog_fires_when_stuck_before_prevoting() {
+		let local_id = Id(0);
+		let voters = VoterSet::new(vec![(local_id, 1), (Id(1), 1)]).unwrap();
+		let (env, _, mut pool) = make_environment(local_id, &["A", "B"]);
+
+		// the prevote timer never fires and the other voter never shows up,
+		// so we can't construct a prevote.
+		let env = Arc::new(StalledEnvironment(env, Mutex::new(Vec::new())));
+
+		let mut round =
+			new_round(voters, Some(RoundState::genesis((GENESIS_HASH, 1))), env.clone());
+
+		let mut poll_round = |times| {
+			for _ in 0..times {
+				pool.run_until_stalled();
+				pool.run_until(future::poll_fn(|cx| {
+					assert!(round.poll(cx).is_pending());
+					Poll::Ready(())
+				}));
+			}
+		};
+
+		poll_round(2);
+		assert!(env.1.lock().is_empty());
+
+		// the hook is only called once.
+		poll_round(5);
+		assert_eq!(*env.1.lock(), vec![1]);
+
+		// we were forced to prevote for the round base.
+		assert!(matches!(round.state, Some(State::Prevoted(_))));
+		assert_eq!(
+			round.votes.prevotes(),
+			vec![(local_id, Prevote::new(GENESIS_HASH, 1), testing::environment::Signature(0))],
+		);
+	}
+
+	#[test]
+	fn notes_votes_far_above_nearest_vote() {
+		// an environment noting votes more than three blocks above the nearest
+		// voted block.
+		struct SparseEnvironment(Environment, Mutex<Vec<(u64, usize)>>);
+
+		testing::environment::wrap_environment!(SparseEnvironment, 0, {
+			fn sparse_votes_depth(&self) -> Option<usize> {
+				Some(3)
+			}
+
+			fn sparse_votes(&self, round: u64, depth: usize) {
+				self.1.lock().push((round, depth));
+			}
+		});
+
+		let voters = VoterSet::new((0..2).map(|i| (Id(i), 1))).unwrap();
+		let (env, _, _) = make_environment(Id(0), &["A", "B", "C", "D", "E", "F", "G"]);
+		let env = Arc::new(SparseEnvironment(env, Mutex::new(Vec::new())));
+
+		let mut round = new_round(voters, None, env.clone());
+
+		let prevote = |hash, number, id| SignedMessage {
+			message: Message::Prevote(Prevote::new(hash, number)),
+			signature: Signature(id),
+			id: Id(id),
+		};
+
+		round.handle_vote(prevote("B", 3, 0)).unwrap();
+		assert!(env.1.lock().is_empty());
+
+		// `G` is five blocks above `B`, the nearest block voted on.
+		round.handle_vote(prevote("G", 8, 1)).unwrap();
+		assert_eq!(*env.1.lock(), vec![(1, 5)]);
+		assert_eq!(round.votes.state().prevote_ghost, Some(("B", 3)));
+	}
+
+	#[test]
+	fn drops_votes_with_invalid_targets() {
+		let voters = VoterSet::new((0..2).map(|i| (Id(i), 1))).unwrap();
+		let (env, _, _) = make_environment(Id(0), &["A", "B", "C"]);
+
+		let mut round = new_round(voters, None, Arc::new(env));
+
+		let prevote = |hash, number, id| SignedMessage {
+			message: Message::Prevote(Prevote::new(hash, number)),
+			signature: Signature(id),
+			id: Id(id),
+		};
+
+		// `C` is block 4, the vote is dropped without failing the round.
+		round.handle_vote(prevote("C", 7, 1)).unwrap();
+		assert!(round.votes.prevotes().is_empty());
+
+		round.handle_vote(prevote("C", 4, 1)).unwrap();
+		assert_eq!(round.votes.prevotes(), vec![(Id(1), Prevote::new("C", 4), Signature(1))]);
+	}
+
+	#[test]
+	fn ignores_primary_proposals_from_non_primary() {
+		// an environment recording misbehaving voters.
+		struct MisbehaviorEnvironment(Environment, Mutex<Vec<(u64, Id)>>);
+
+		testing::environment::wrap_environment!(MisbehaviorEnvironment, 0, {
+			fn note_misbehaving_voter(&self, round: u64, id: Id) {
+				self.1.lock().push((round, id));
+			}
+		});
+
+		let voters = VoterSet::new((0..2).map(|i| (Id(i), 1))).unwrap();
+		let (env, _, _) = make_environment(Id(0), &["A", "B"]);
+		let env = Arc::new(MisbehaviorEnvironment(env, Mutex::new(Vec::new())));
+
+		let mut round = new_round(voters, None, env.clone());
+
+		let primary_id = round.primary_voter;
+		let other_id = if primary_id == Id(0) { Id(1) } else { Id(0) };
+		let propose = |id: Id| SignedMessage {
+			message: Message::PrimaryPropose(PrimaryPropose::new("B", 3)),
+			signature: Signature(id.0),
+			id,
+		};
+
+		round.handle_vote(propose(other_id)).unwrap();
+		assert_eq!(round.primary_block, None);
+		assert_eq!(*env.1.lock(), vec![(1, other_id)]);
+
+		round.handle_vote(propose(primary_id)).unwrap();
+		assert_eq!(round.primary_block, Some(("B", 3)));
+		assert_eq!(*env.1.lock(), vec![(1, other_id)]);
+	}
+
+	#[test]
+	fn reports_equivocations_once() {
+		let voters = VoterSet::new((0..2).map(|i| (Id(i), 1))).unwrap();
+		let (env, _, _) = make_environment(Id(0), &["A", "B", "C"]);
+		env.record_equivocations();
+		let env = Arc::new(env);
+
+		let mut round = new_round(voters, None, env.clone());
+
+		let prevote = |hash, number, id| SignedMessage {
+			message: Message::Prevote(Prevote::new(hash, number)),
+			signature: Signature(id),
+			id: Id(id),
+		};
+		let precommit = |hash, number, id| SignedMessage {
+			message: Message::Precommit(Precommit::new(hash, number)),
+			signature: Signature(id),
+			id: Id(id),
+		};
+
+		round.handle_vote(prevote("A", 2, 1)).unwrap();
+		assert!(env.equivocations().is_empty());
+
+		// only the first conflicting vote is reported, any further ones are ignored.
+		round.handle_vote(prevote("B", 3, 1)).unwrap();
+		round.handle_vote(prevote("C", 4, 1)).unwrap();
+		assert_eq!(env.equivocations(), vec![(1, Id(1))]);
+
+		round.handle_vote(precommit("A", 2, 1)).unwrap();
+		round.handle_vote(precommit("A", 2, 1)).unwrap();
+		assert_eq!(env.equivocations(), vec![(1, Id(1))]);
+
+		round.handle_vote(precommit("B", 3, 1)).unwrap();
+		assert_eq!(env.equivocations(), vec![(1, Id(1)), (1, Id(1))]);
+	}
+
+	#[test]
+	fn reports_voters_silent_until_their_timer_fires() {
+		// an environment with silence timers which only fire on `expire`.
+		#[derive(Default)]
+		struct Silence {
+			timers: Vec<(u64, Id, Option<oneshot::Sender<()>>)>,
+			silent: Vec<(u64, Id)>,
+		}
+
+		struct SilenceEnvironment(Environment, Mutex<Silence>);
+
+		impl SilenceEnvironment {
+			fn timers(&self) -> Vec<(u64, Id)> {
+				self.1.lock().timers.iter().map(|(round, id, _)| (*round, *id)).collect()
+			}
+
+			fn expire(&self, voter: Id) {
+				let mut silence = self.1.lock();
+				for (_, _, sender) in silence.timers.iter_mut().filter(|(_, id, _)| *id == voter) {
+					if let Some(sender) = sender.take() {
+						let _ = sender.send(());
+					}
+				}
+			}
+
+			fn silent(&self) -> Vec<(u64, Id)> {
+				self.1.lock().silent.clone()
+			}
+		}
+
+		testing::environment::wrap_environment!(SilenceEnvironment, 0, {
+			fn voter_silence_timer(&self, round: u64, id: &Id) -> Option<Self::Timer> {
+				let (sender, receiver) = oneshot::channel();
+				self.1.lock().timers.push((round, *id, Some(sender)));
+				Some(Box::new(receiver.map(|_| Ok(()))))
+			}
+
+			fn voter_silent(&self, round: u64, id: Id) {
+				self.1.lock().silent.push((round, id));
+			}
+		});
+
+		let voters = VoterSet::new((0..4).map(|i| (Id(i), 1))).unwrap();
+		let (env, network, mut pool) = make_environment(Id(0), &["A", "B"]);
+		let env = Arc::new(SilenceEnvironment(env, Mutex::new(Silence::default())));
+
+		// we don't vote without the previous round, which keeps the round from
+		// completing.
+		let mut round = new_round(voters, None, env.clone());
+
+		// voter 1 is done voting, voter 2 only prevotes and voter 3 doesn't vote
+		// at all.
+		send_votes(
+			&pool,
+			&network,
+			Id(1),
+			vec![
+				Message::Prevote(Prevote::new("B", 3)),
+				Message::Precommit(Precommit::new("B", 3)),
+			],
+		);
+		send_votes(&pool, &network, Id(2), vec![Message::Prevote(Prevote::new("B", 3))]);
+
+		// the timers of the other voters are armed at the start of the round,
+		// and again with each prevote.
+		run_round_until(&mut pool, &mut round, |_| env.timers().len() == 5);
+		let mut timers = env.timers();
+		timers.sort();
+		assert_eq!(timers, vec![(1, Id(1)), (1, Id(1)), (1, Id(2)), (1, Id(2)), (1, Id(3))]);
+
+		// voter 1 isn't watched after its precommit.
+		env.expire(Id(1));
+		env.expire(Id(3));
+		run_round_until(&mut pool, &mut round, |_| !env.silent().is_empty());
+		assert_eq!(env.silent(), vec![(1, Id(3))]);
+
+		env.expire(Id(2));
+		run_round_until(&mut pool, &mut round, |_| env.silent().len() == 2);
+		assert_eq!(env.silent(), vec![(1, Id(3)), (1, Id(2))]);
+
+		// silent voters are reported once.
+		env.expire(Id(2));
+		env.expire(Id(3));
+		run_round_until(&mut pool, &mut round, |_| true);
+		assert_eq!(env.silent(), vec![(1, Id(3)), (1, Id(2))]);
+	}
+
+	#[test]
+	fn stops_voting_when_signing_becomes_unavailable() {
+		let local_id = Id(0);
+		let voters = VoterSet::new(iter::once((local_id, 1))).unwrap();
+		let (env, _, mut pool) = make_environment(local_id, &["A", "B"]);
+		let env = Arc::new(SigningEnvironment::new(env));
+
+		let mut round =
+			new_round(voters, Some(RoundState::genesis((GENESIS_HASH, 1))), env.clone());
+
+		run_round_until(&mut pool, &mut round, |round| {
+			matches!(round.state, Some(State::Prevoted(_)))
+		});
+
+		// the signing key is lost after prevoting.
+		env.signing_disabled.store(true, Ordering::SeqCst);
+
+		run_round_until(&mut pool, &mut round, |round| {
+			matches!(round.state, Some(State::Precommitted))
+		});
+
+		assert_eq!(*env.signing_unavailable.lock(), vec![1]);
+		assert!(!round.is_voting());
+		assert_eq!(round.votes.precommit_participation().1, 0);
+		assert_eq!(round.votes.prevote_participation().1, 1);
+	}
+
+	#[test]
+	fn skips_votes_the_outgoing_sink_fails_to_sign() {
+		let local_id = Id(0);
+		let voters = VoterSet::new(iter::once((local_id, 1))).unwrap();
+		let (env, _, mut pool) = make_environment(local_id, &["A", "B"]);
+		let env = Arc::new(SigningEnvironment::new(env));
+
+		let mut round =
+			new_round(voters, Some(RoundState::genesis((GENESIS_HASH, 1))), env.clone());
+
+		run_round_until(&mut pool, &mut round, |round| {
+			matches!(round.state, Some(State::Prevoted(_)))
+		});
+
+		// signing fails in the sink even though it's reported as available.
+		env.failing_signatures.store(true, Ordering::SeqCst);
+
+		run_round_until(&mut pool, &mut round, |round| {
+			matches!(round.state, Some(State::Precommitted))
+		});
+		pool.run_until(future::poll_fn(|cx| round.poll_flush(cx))).unwrap();
+
+		assert_eq!(*env.signing_unavailable.lock(), vec![1]);
+		assert!(!round.is_voting());
+		assert_eq!(round.votes.precommit_participation().1, 0);
+		assert_eq!(round.votes.prevote_participation().1, 1);
+	}
+
+	#[test]
+	fn restricts_prevote_target_with_voting_rule() {
+		struct RestrictTo(&'static str, u32);
+
+		impl crate::voter::voting_rule::VotingRule<&'static str, u32> for RestrictTo {
+			fn restrict_vote(
+				&self,
+				_base: (&'static str, u32),
+				best_target: (&'static str, u32),
+				current_target: (&'static str, u32),
+			) -> Option<(&'static str, u32)> {
+				assert_eq!(best_target, ("C", 4));
+				assert_eq!(current_target, best_target);
+				Some((self.0, self.1))
+			}
+		}
+
+		let run_round = |restrict_to: RestrictTo| {
+			let local_id = Id(0);
+			let voters = VoterSet::new(iter::once((local_id, 1))).unwrap();
+			let (env, _, mut pool) = make_environment(local_id, &["A", "B", "C"]);
+			env.with_chain(|chain| chain.push_blocks(GENESIS_HASH, &["X"]));
+			let env = Arc::new(env.with_voting_rule(restrict_to));
+
+			let mut round = new_round(voters, Some(RoundState::genesis((GENESIS_HASH, 1))), env);
+			pool.run_until(future::poll_fn(|cx| round.poll(cx))).unwrap();
+
+			round.finalized().cloned()
+		};
+
+		// the prevote is pulled back from the best block.
+		assert_eq!(run_round(RestrictTo("A", 2)), Some(("A", 2)));
+
+		// a target which isn't on the best chain is ignored.
+		assert_eq!(run_round(RestrictTo("X", 2)), Some(("C", 4)));
+	}
+
+	#[test]
+	fn drops_oldest_votes_for_unknown_blocks_past_limit() {
+		// an environment keeping two votes for unknown blocks.
+		struct LimitedEnvironment(Environment);
+
+		testing::environment::wrap_environment!(LimitedEnvironment, 0, {
+			fn max_pending_votes(&self) -> Option<usize> {
+				Some(2)
+			}
+		});
+
+		let voters = VoterSet::new((0..4).map(|i| (Id(i), 1))).unwrap();
+		let (env, _, _) = make_environment(Id(0), &["A"]);
+		let env = Arc::new(LimitedEnvironment(env));
+
+		let mut round = new_round(voters, None, env.clone());
+
+		for (id, block) in [(1, "B"), (2, "C"), (3, "D")] {
+			let vote = SignedMessage {
+				message: Message::Prevote(Prevote::new(block, 3)),
+				signature: Signature(id),
+				id: Id(id),
+			};
+			round.handle_vote(vote).unwrap();
+		}
+
+		for block in ["B", "C", "D"] {
+			env.0.with_chain(|chain| chain.push_blocks("A", &[block]));
+			round.block_imported(&block).unwrap();
+		}
+
+		// the vote from the first voter was dropped to keep the later ones.
+		assert_eq!(round.prevote_ids().collect::<Vec<_>>(), vec![Id(2), Id(3)]);
+	}
+
+	#[test]
+	fn precommits_base_without_prevote_ghost() {
+		let local_id = Id(0);
+		let voters = VoterSet::new((0..4).map(|i| (Id(i), 1))).unwrap();
+		let (env, network, mut pool) = make_environment(local_id, &["A", "B"]);
+
+		let mut round =
+			new_round(voters, Some(RoundState::genesis((GENESIS_HASH, 1))), Arc::new(env));
+
+		// too few voters prevote for a prevote-GHOST, but some precommit the base.
+		send_votes(&pool, &network, Id(1), vec![Message::Prevote(Prevote::new("B", 3))]);
+		send_votes(
+			&pool,
+			&network,
+			Id(1),
+			vec![Message::Precommit(Precommit::new(GENESIS_HASH, 1))],
+		);
+		send_votes(
+			&pool,
+			&network,
+			Id(2),
+			vec![Message::Precommit(Precommit::new(GENESIS_HASH, 1))],
+		);
+
+		run_round_until(&mut pool, &mut round, |round| {
+			round.votes.precommit_participation().1 == 3
+		});
+
+		assert!(matches!(round.state, Some(State::Precommitted)));
+		assert_eq!(round.votes.state().prevote_ghost, None);
+
+		// once the remaining prevote arrives the round completes on the base.
+		send_votes(&pool, &network, Id(2), vec![Message::Prevote(Prevote::new("B", 3))]);
+		pool.run_until(future::poll_fn(|cx| round.poll(cx))).unwrap();
+
+		assert_eq!(round.finalized(), Some(&(GENESIS_HASH, 1)));
+		assert_eq!(round.round_state().estimate, Some((GENESIS_HASH, 1)));
+	}
+
+	#[test]
+	fn prevotes_without_timer_when_last_round_estimate_finalized() {
+		let local_id = Id(0);
+		let voters = VoterSet::new(iter::once((local_id, 1))).unwrap();
+		let (env, _, mut pool) = make_environment(local_id, &["A", "B"]);
+
+		// the prevote timer never fires.
+		let env = Arc::new(StalledEnvironment(env, Mutex::new(Vec::new())));
+
+		// we caught up to a round whose estimate was finalized and is the base
+		// of the next round.
+		let last_round_state = RoundState {
+			round: 4,
+			prevote_ghost: Some(("A", 2)),
+			finalized: Some(("A", 2)),
+			estimate: Some(("A", 2)),
+			completable: true,
+		};
+
+		let (finalized_sender, _finalized) = mpsc::unbounded();
+		let mut round = VotingRound::new(
+			5,
+			voters,
+			("A", 2),
+			Some(LatterView::from_stream(last_round_state, stream::pending())),
+			finalized_sender,
+			Arc::new(BestChainCache::new()),
+			env,
+		);
+		round.note_caught_up();
+
+		pool.run_until(future::poll_fn(|cx| round.poll(cx))).unwrap();
+
+		assert_eq!(round.finalized(), Some(&("B", 3)));
+		assert_eq!(round.votes.prevotes(), vec![(local_id, Prevote::new("B", 3), Signature(0))]);
+	}
+
+	#[test]
+	fn rearms_prevote_timer_before_prevoting() {
+		// an environment re-arming the prevote timer twice per round.
+		struct RearmingEnvironment(Environment, Mutex<Vec<(u64, usize)>>);
+
+		testing::environment::wrap_environment!(RearmingEnvironment, 0, {
+			fn rearm_prevote_timer(&self, round: u64, rearmed: usize) -> Option<Self::Timer> {
+				if rearmed >= 2 {
+					return None
+				}
+
+				self.1.lock().push((round, rearmed));
+				Some(Box::new(Delay::new(Duration::from_millis(10)).map(Ok)))
+			}
+		});
+
+		let local_id = Id(0);
+		let voters = VoterSet::new(iter::once((local_id, 1))).unwrap();
+		let (env, _, mut pool) = make_environment(local_id, &["A", "B"]);
+		let env = Arc::new(RearmingEnvironment(env, Mutex::new(Vec::new())));
+
+		let mut round =
+			new_round(voters, Some(RoundState::genesis((GENESIS_HASH, 1))), env.clone());
+		pool.run_until(future::poll_fn(|cx| round.poll(cx))).unwrap();
+
+		// the timer is only re-armed before prevoting, and we prevote once.
+		assert_eq!(*env.1.lock(), vec![(1, 0), (1, 1)]);
+		let prevotes = env
+			.0
+			.noted_votes()
+			.into_iter()
+			.filter(|(_, vote)| matches!(vote, Message::Prevote(_)));
+		assert_eq!(prevotes.count(), 1);
+		assert_eq!(round.finalized(), Some(&("B", 3)));
+	}
+
+	#[test]
+	fn gives_up_on_best_chain_after_deadline() {
+		let local_id = Id(0);
+		let voters = VoterSet::new(iter::once((local_id, 1))).unwrap();
+		let (env, _, mut pool) = make_environment(local_id, &["A", "B"]);
+
+		// the first best chain query hangs, so we retry it after the deadline.
+		let env = Arc::new(RetryingEnvironment::new(env, 1, Some(Duration::from_millis(10))));
+
+		let mut round =
+			new_round(voters, Some(RoundState::genesis((GENESIS_HASH, 1))), env.clone());
+		pool.run_until(future::poll_fn(|cx| round.poll(cx))).unwrap();
+
+		assert_eq!(env.retries(), vec![(1, 0)]);
+		assert_eq!(round.votes.prevotes(), vec![(local_id, Prevote::new("B", 3), Signature(0))]);
+		assert_eq!(round.finalized(), Some(&("B", 3)));
+	}
+
+	#[test]
+	fn gives_up_on_best_chain_when_precommit_timer_fires() {
+		let local_id = Id(0);
+		let voters = VoterSet::new(iter::once((local_id, 1))).unwrap();
+		let (env, _, mut pool) = make_environment(local_id, &["A", "B"]);
+
+		// the first best chain query hangs without a deadline, so we retry it
+		// once the prevote window is over.
+		let env = Arc::new(RetryingEnvironment::new(env, 1, None));
+
+		let mut round =
+			new_round(voters, Some(RoundState::genesis((GENESIS_HASH, 1))), env.clone());
+		pool.run_until(future::poll_fn(|cx| round.poll(cx))).unwrap();
+
+		assert_eq!(env.retries(), vec![(1, 0)]);
+		assert_eq!(round.votes.prevotes(), vec![(local_id, Prevote::new("B", 3), Signature(0))]);
+
+		// the precommit timer already fired, so we precommitted before our
+		// prevote was imported.
+		assert_eq!(
+			round.votes.precommits(),
+			vec![(local_id, Precommit::new(GENESIS_HASH, 1), Signature(0))],
+		);
+	}
+
+	#[test]
+	fn retries_best_chain_for_unsynced_blocks() {
+		let local_id = Id(0);
+		let voters = VoterSet::new(iter::once((local_id, 1))).unwrap();
+
+		// the last round finalized `B`, which we haven't synced yet.
+		let last_round_state = RoundState {
+			round: 0,
+			prevote_ghost: Some(("B", 3)),
+			finalized: Some(("B", 3)),
+			estimate: Some(("B", 3)),
+			completable: true,
+		};
+
+		// without retries we abstain from the round.
+		let (env, _, mut pool) = make_environment(local_id, &[]);
+		let env = Arc::new(env);
+
+		let mut abstaining = new_round(voters.clone(), Some(last_round_state.clone()), env.clone());
+		pool.run_until(future::poll_fn(|cx| {
+			let _ = abstaining.poll(cx);
+			if abstaining.is_voting() {
+				Poll::Pending
+			} else {
+				Poll::Ready(())
+			}
+		}));
+		assert!(env.noted_votes().is_empty());
+
+		// with retries we prevote once the block is synced.
+		let (env, _, mut pool) = make_environment(local_id, &[]);
+		let env = Arc::new(RetryingEnvironment::new(env, 0, None));
+
+		let mut retrying = new_round(voters, Some(last_round_state), env.clone());
+		let mut synced = false;
+		pool.run_until(future::poll_fn(|cx| {
+			if !synced && !env.retries().is_empty() {
+				env.env.with_chain(|chain| chain.push_blocks(GENESIS_HASH, &["A", "B"]));
+				synced = true;
+			}
+
+			retrying.poll(cx)
+		}))
+		.unwrap();
+
+		assert_eq!(env.retries(), vec![(1, 0)]);
+		assert!(env
+			.env
+			.noted_votes()
+			.contains(&(1, Message::Prevote(Prevote { target_hash: "B", target_number: 3 }))));
+	}
+
+	#[test]
+	fn primary_proposes_latest_estimate_of_last_round() {
+		let local_id = Id(0);
+		let voters = VoterSet::new(iter::once((local_id, 1))).unwrap();
+		let (env, _, _) = make_environment(local_id, &["A", "B"]);
+		let env = Arc::new(env);
+
+		// the last round's estimate moved on before the round is first polled.
+		let update = RoundState {
+			round: 0,
+			prevote_ghost: Some(("B", 3)),
+			finalized: Some((GENESIS_HASH, 1)),
+			estimate: Some(("B", 3)),
+			completable: true,
+		};
+
+		let (finalized_sender, _finalized) = mpsc::unbounded();
+		let mut round = VotingRound::new(
+			1,
+			voters,
+			(GENESIS_HASH, 1),
+			Some(LatterView::from_stream(
+				RoundState::genesis((GENESIS_HASH, 1)),
+				stream::iter(Some(update)).chain(stream::pending()),
+			)),
+			finalized_sender,
+			Arc::new(BestChainCache::new()),
+			env.clone(),
+		);
+
+		assert!(round.voting.is_primary());
+		let _ = round.poll(&mut Context::from_waker(futures::task::noop_waker_ref()));
+
+		assert_eq!(
+			env.noted_votes().first(),
+			Some(&(1, Message::PrimaryPropose(PrimaryPropose::new("B", 3)))),
+		);
+	}
+
+	#[test]
+	fn selects_primary_voter_with_environment_strategy() {
+		struct LastVoter;
+
+		impl PrimarySelector<Id> for LastVoter {
+			fn primary_voter<'a>(
+				&self,
+				_round_number: u64,
+				voters: &'a VoterSet<Id>,
+			) -> (&'a Id, &'a crate::voter_set::VoterInfo) {
+				voters.nth_mod(voters.len().get() - 1)
+			}
+		}
+
+		let voters = VoterSet::new((0..3).map(|i| (Id(i), 1))).unwrap();
+		let (env, _, _) = make_environment(Id(2), &["A", "B"]);
+		let env = Arc::new(env.with_primary_selector(LastVoter));
+
+		let mut round = new_round(voters, None, env);
+
+		// round-robin would select the second voter in the first round.
+		assert_eq!(*round.votes.primary_voter(&RoundRobin).0, Id(1));
+		assert_eq!(round.primary_voter, Id(2));
+		assert!(round.voting.is_primary());
+
+		let propose = |id: Id| SignedMessage {
+			message: Message::PrimaryPropose(PrimaryPropose::new("B", 3)),
+			signature: Signature(id.0),
+			id,
+		};
+
+		round.handle_vote(propose(Id(1))).unwrap();
+		assert_eq!(round.primary_block, None);
+
+		round.handle_vote(propose(Id(2))).unwrap();
+		assert_eq!(round.primary_block, Some(("B", 3)));
+	}
+}
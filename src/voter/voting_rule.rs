@@ -0,0 +1,62 @@
+// Copyright 2018-2019 Parity Technologies (UK) Ltd
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rules restricting the block the voter prevotes for.
+//!
+//! By default the voter prevotes for the head of the best chain containing the
+//! block it must build on, see `Environment::best_chain_containing`. A voting
+//! rule can pull the prevote back from that head, e.g. to a number of blocks
+//! below it or to the last session boundary, so that voters don't finalize
+//! blocks too eagerly.
+
+/// A rule restricting the target of the voter's prevotes, see
+/// `Environment::voting_rule`.
+pub trait VotingRule<H, N> {
+	/// Restrict the prevote target in a round. `base` is the block whose best
+	/// chain is voted on, `best_target` is the head of that chain and
+	/// `current_target` is the target after any other rule was applied, i.e.
+	/// the same as `best_target` unless rules are combined.
+	///
+	/// Returns the block to vote for instead, which must be on the chain from
+	/// `base` to `current_target`. A block outside of it is ignored by the
+	/// voter. By default the target isn't restricted and `None` is returned.
+	fn restrict_vote(
+		&self,
+		_base: (H, N),
+		_best_target: (H, N),
+		_current_target: (H, N),
+	) -> Option<(H, N)> {
+		None
+	}
+}
+
+/// Doesn't restrict the vote.
+impl<H, N> VotingRule<H, N> for () {}
+
+/// Applies both rules in order, the second one restricting the target of the
+/// first one.
+impl<H: Clone, N: Clone, A: VotingRule<H, N>, B: VotingRule<H, N>> VotingRule<H, N> for (A, B) {
+	fn restrict_vote(
+		&self,
+		base: (H, N),
+		best_target: (H, N),
+		current_target: (H, N),
+	) -> Option<(H, N)> {
+		let restricted =
+			self.0.restrict_vote(base.clone(), best_target.clone(), current_target.clone());
+		let current_target = restricted.clone().unwrap_or(current_target);
+
+		self.1.restrict_vote(base, best_target, current_target).or(restricted)
+	}
+}
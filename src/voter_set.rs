@@ -19,10 +19,13 @@
 use crate::{
 	std::{
 		collections::{btree_map::Entry, BTreeMap},
+		fmt,
 		num::{NonZeroU64, NonZeroUsize},
+		sync::Arc,
 		vec::Vec,
 	},
 	weights::VoterWeight,
+	Error,
 };
 
 /// A (non-empty) set of voters and associated weights.
@@ -30,10 +33,15 @@ use crate::{
 /// A `VoterSet` identifies all voters that are permitted to vote in a round
 /// of the protocol and their associated weights. A `VoterSet` is furthermore
 /// equipped with a total order, given by the ordering of the voter's IDs.
-#[derive(Clone, PartialEq, Eq, Debug)]
+///
+/// The voters are shared between clones of a `VoterSet`, so cloning it is cheap.
+///
+/// A `VoterSet` is displayed as a summary of its size, total weight and
+/// threshold, and its `Debug` output adds the weight of each voter.
+#[derive(Clone, Eq)]
 pub struct VoterSet<Id: Eq + Ord> {
 	/// The voters in the voter set, this vec is always sorted by the voter ID.
-	voters: Vec<(Id, VoterInfo)>,
+	voters: Arc<Vec<(Id, VoterInfo)>>,
 	/// The required weight threshold for supermajority w.r.t. this set.
 	threshold: VoterWeight,
 	/// The total weight of all voters.
@@ -94,12 +102,41 @@ impl<Id: Eq + Ord> VoterSet<Id> {
 				(id, info)
 			})
 			.collect();
+		let voters = Arc::new(voters);
 
 		let total_weight = VoterWeight::new(total_weight).expect("voters nonempty; qed");
 
 		Some(VoterSet { voters, total_weight, threshold: threshold(total_weight) })
 	}
 
+	/// Create a voter set from the given voters and their weights, rejecting
+	/// a distribution that is likely to be a configuration error instead of
+	/// correcting it like `new` does.
+	///
+	/// Returns `Error::DuplicateVoter` if a voter ID is given more than once
+	/// and `Error::InvalidVoterWeight` if a voter has zero weight or if the
+	/// total weight is zero or exceeds `u64::MAX`. As with `new`, the voters
+	/// are ordered by their IDs, so the order in which they are given doesn't
+	/// affect e.g. the selection of primary voters.
+	pub fn try_new<I>(weights: I) -> Result<Self, Error>
+	where
+		Id: Ord + Clone,
+		I: IntoIterator<Item = (Id, u64)>,
+	{
+		let mut voters = BTreeMap::new();
+		for (id, weight) in weights {
+			if weight == 0 {
+				return Err(Error::InvalidVoterWeight)
+			}
+
+			if voters.insert(id, weight).is_some() {
+				return Err(Error::DuplicateVoter)
+			}
+		}
+
+		VoterSet::new(voters).ok_or(Error::InvalidVoterWeight)
+	}
+
 	/// Get the voter info for the voter with the given ID, if any.
 	pub fn get(&self, id: &Id) -> Option<&VoterInfo> {
 		self.voters
@@ -108,6 +145,11 @@ impl<Id: Eq + Ord> VoterSet<Id> {
 			.map(|idx| &self.voters[idx].1)
 	}
 
+	/// Get the weight of the voter with the given ID, if any.
+	pub fn weight_of(&self, id: &Id) -> Option<VoterWeight> {
+		self.get(id).map(|info| info.weight)
+	}
+
 	/// Get the size of the set.
 	pub fn len(&self) -> NonZeroUsize {
 		unsafe {
@@ -136,6 +178,13 @@ impl<Id: Eq + Ord> VoterSet<Id> {
 
 	/// Get the threshold vote weight required for supermajority
 	/// w.r.t. this set of voters.
+	///
+	/// This is the total weight minus the weight `f` of faulty voters which can
+	/// be tolerated, the largest with `3f < total`, so it never exceeds the
+	/// total weight. Small sets of equal weights tolerate no faulty voter until
+	/// there are four of them: a single voter finalizes alone, but with two or
+	/// three voters every one of them must vote and any one being offline halts
+	/// finality.
 	pub fn threshold(&self) -> VoterWeight {
 		self.threshold
 	}
@@ -152,6 +201,74 @@ impl<Id: Eq + Ord> VoterSet<Id> {
 	}
 }
 
+impl<Id: Eq + Ord> PartialEq for VoterSet<Id> {
+	fn eq(&self, other: &Self) -> bool {
+		// the threshold and total weight are derived from the voters.
+		Arc::ptr_eq(&self.voters, &other.voters) || self.voters == other.voters
+	}
+}
+
+impl<Id: Eq + Ord> fmt::Display for VoterSet<Id> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"{} voters, total weight {}, threshold {}",
+			self.len(),
+			self.total_weight,
+			self.threshold,
+		)
+	}
+}
+
+// implemented for `core`'s `Debug`, as the crate's `Debug` is only a marker
+// without `std`.
+impl<Id: Eq + Ord + core::fmt::Debug> core::fmt::Debug for VoterSet<Id> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		struct Weights<'a, Id: Eq + Ord>(&'a VoterSet<Id>);
+
+		impl<Id: Eq + Ord + core::fmt::Debug> core::fmt::Debug for Weights<'_, Id> {
+			fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+				f.debug_map()
+					.entries(self.0.iter().map(|(id, info)| (id, info.weight.get())))
+					.finish()
+			}
+		}
+
+		f.debug_struct("VoterSet")
+			.field("len", &self.len())
+			.field("total_weight", &self.total_weight.get())
+			.field("threshold", &self.threshold.get())
+			.field("voters", &Weights(self))
+			.finish()
+	}
+}
+
+/// A strategy for selecting the primary voter of a round, who proposes the
+/// block to build on. Every voter must select the same primary in a round,
+/// otherwise proposals are ignored as coming from the wrong voter.
+pub trait PrimarySelector<Id: Eq + Ord> {
+	/// Select the primary voter of the given round among the given voters.
+	fn primary_voter<'a>(
+		&self,
+		round_number: u64,
+		voters: &'a VoterSet<Id>,
+	) -> (&'a Id, &'a VoterInfo);
+}
+
+/// Selects the voters in turn, in the order of their IDs.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RoundRobin;
+
+impl<Id: Eq + Ord> PrimarySelector<Id> for RoundRobin {
+	fn primary_voter<'a>(
+		&self,
+		round_number: u64,
+		voters: &'a VoterSet<Id>,
+	) -> (&'a Id, &'a VoterInfo) {
+		voters.nth_mod(round_number as usize)
+	}
+}
+
 /// Information about a voter in a `VoterSet`.
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct VoterInfo {
@@ -197,7 +314,7 @@ mod tests {
 
 				// we might generate an invalid voter set above if:
 				// - all validators have 0 weight
-				// - the total weight is higher than `u64::max_value()`
+				// - the total weight is higher than `u64::MAX`
 				//
 				// the easiest thing to do is to just retry generating another instance.
 				if let Some(set) = VoterSet::new(ids.into_iter().zip(weights)) {
@@ -207,6 +324,32 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn clones_share_voters() {
+		let v1 = VoterSet::new((0..10_000).map(|id| (id, 1))).expect("nonempty");
+		let v2 = v1.clone();
+
+		assert!(Arc::ptr_eq(&v1.voters, &v2.voters));
+		assert_eq!(v1, v2);
+
+		// sets built separately don't share storage but still compare equal.
+		let v3 = VoterSet::new((0..10_000).rev().map(|id| (id, 1))).expect("nonempty");
+		assert!(!Arc::ptr_eq(&v1.voters, &v3.voters));
+		assert_eq!(v1, v3);
+		assert_eq!(v3.nth(0), v1.nth(0));
+	}
+
+	#[test]
+	fn summary() {
+		let voters = VoterSet::new([(3, 3), (1, 1), (2, 2)]).expect("nonempty");
+
+		assert_eq!(voters.to_string(), "3 voters, total weight 6, threshold 5");
+		assert_eq!(
+			format!("{:?}", voters),
+			"VoterSet { len: 3, total_weight: 6, threshold: 5, voters: {1: 1, 2: 2, 3: 3} }",
+		);
+	}
+
 	#[test]
 	fn equality() {
 		fn prop(mut v: Vec<(usize, u64)>) {
@@ -219,7 +362,7 @@ mod tests {
 					// either no authority has a valid weight
 					v.iter().all(|(_, w)| w == &0) ||
 					// or the total weight overflows a u64
-					v.iter().map(|(_, w)| *w as u128).sum::<u128>() > u64::max_value() as u128
+					v.iter().map(|(_, w)| *w as u128).sum::<u128>() > u64::MAX as u128
 				);
 			}
 		}
@@ -233,7 +376,7 @@ mod tests {
 			let total_weight = v.iter().map(|(_, weight)| *weight as u128).sum::<u128>();
 
 			// this validator set is invalid
-			if total_weight > u64::max_value() as u128 {
+			if total_weight > u64::MAX as u128 {
 				return
 			}
 
@@ -249,6 +392,57 @@ mod tests {
 		quickcheck(prop as fn(_))
 	}
 
+	#[test]
+	fn small_set_thresholds() {
+		for (n, expected) in [(1, 1), (2, 2), (3, 3), (4, 3), (5, 4), (7, 5)] {
+			let v = VoterSet::new((0..n).map(|id| (id, 1))).expect("nonempty");
+			assert_eq!(v.threshold().get(), expected, "threshold of {} voters", n);
+			assert!(v.threshold().get() <= v.total_weight().get());
+		}
+	}
+
+	#[test]
+	fn weighted_thresholds() {
+		// a single voter finalizes alone.
+		let v = VoterSet::new(iter::once(("Alice", 5))).expect("nonempty");
+		assert_eq!(v.weight_of(&"Alice"), Some(v.total_weight()));
+		assert!(v.threshold().get() <= v.total_weight().get());
+
+		// partial weights of a voter are accumulated, unknown voters have none.
+		let v = VoterSet::new(vec![("Alice", 2), ("Bob", 2), ("Alice", 2)]).expect("nonempty");
+		assert_eq!(v.weight_of(&"Alice").map(|w| w.get()), Some(4));
+		assert_eq!(v.weight_of(&"Eve"), None);
+
+		// the threshold is over the weights, not the number of voters: a voter
+		// with most of the weight reaches it alone.
+		let v = VoterSet::new(vec![("Alice", 1), ("Bob", 1), ("Charlie", 1), ("Dave", 7)])
+			.expect("nonempty");
+		assert_eq!(v.total_weight().get(), 10);
+		assert_eq!(v.threshold().get(), 7);
+		assert_eq!(v.weight_of(&"Dave"), Some(v.threshold()));
+	}
+
+	#[test]
+	fn strict_construction() {
+		let v = VoterSet::try_new(vec![("Bob", 2), ("Alice", 1)]).unwrap();
+		assert_eq!(v, VoterSet::new(vec![("Alice", 1), ("Bob", 2)]).unwrap());
+		assert_eq!(v.nth(0).map(|(id, _)| *id), Some("Alice"));
+
+		assert_eq!(
+			VoterSet::try_new(vec![("Alice", 1), ("Bob", 2), ("Alice", 1)]),
+			Err(Error::DuplicateVoter),
+		);
+		assert_eq!(
+			VoterSet::try_new(vec![("Alice", 1), ("Bob", 0)]),
+			Err(Error::InvalidVoterWeight),
+		);
+		assert_eq!(VoterSet::<&str>::try_new(vec![]), Err(Error::InvalidVoterWeight));
+		assert_eq!(
+			VoterSet::try_new(vec![("Alice", u64::MAX), ("Bob", 1)]),
+			Err(Error::InvalidVoterWeight),
+		);
+	}
+
 	#[test]
 	fn min_threshold() {
 		fn prop(v: VoterSet<usize>) -> bool {